@@ -0,0 +1,197 @@
+//! スペースの差を計算するときに時間がかかる場合のベンチマーク
+//!
+//! コンストラクタどうしの差は引数ごとに和集合の要素を1つ作るので、
+//! 引数が多い場合や、入れ子になったコンストラクタで計算量が大きくなる。
+//! 次のような最悪に近いケースを生成して、網羅性検査 ([`check_match`]) にかかる時間を測る。
+//!
+//! - `wide_tuple`: ブール値を n 個並べたタプルについて、各アームが1つの位置だけ True を指定する。
+//! - `deep_chain`: `NonZero(_, NonZero(_, ... Zero))` の深さが 0〜n のアームを並べる。
+//! - `many_arms`: ブール値を n 個並べたタプルの値を、すべて (2^n 個の) アームで列挙する。
+//!
+//! 大きさは `wide_tuple` が 4〜32, `deep_chain` が 2〜32, `many_arms` が 2〜6 で、
+//! 最も遅いケースでも1回が0.1秒以内に収まるように選んでいる。
+//! (メモ化を入れた現在、最も重いのは `many_arms` の n = 6 で約50ミリ秒、
+//! 次が `wide_tuple` の n = 32 で約40ミリ秒。)
+//!
+//! 各ケースの前に、網羅性検査のメモ化のヒット数 ([`CheckStats`]) を表示する。
+//!
+//! 比較のために、有用性に基づくアルゴリズム ([`UsefulnessChecker`]) でも同じケースを測る。
+//! (`space/<n>` と `usefulness/<n>` という名前になる。)
+//!
+//! `cargo bench` で実行する。`cargo bench -- deep_chain` のように名前で絞り込める。
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use pmxc_analyzer::*;
+
+fn boolean_ty() -> Ty {
+    Ty::Enum {
+        name: "Boolean".to_string(),
+    }
+}
+
+fn ctor(name: &str, args: Vec<Pattern>) -> Pattern {
+    Pattern::Constructor {
+        name: name.to_string(),
+        args,
+    }
+}
+
+fn discard(ty: Ty) -> Pattern {
+    Pattern::Discard { ty }
+}
+
+fn boolean(value: bool) -> Pattern {
+    ctor(if value { "True" } else { "False" }, vec![])
+}
+
+fn new_ty_database(tuple_width: usize) -> TyDatabase {
+    let mut td = TyDatabase::default();
+    td.add_definition(TyDefinition::Enum {
+        name: "Boolean".to_string(),
+        constructors: vec![
+            ConstructorDefinition::new("False", vec![]),
+            ConstructorDefinition::new("True", vec![]),
+        ],
+    });
+    td.add_definition(TyDefinition::Enum {
+        name: "Binary".to_string(),
+        constructors: vec![
+            ConstructorDefinition::new("Zero", vec![]),
+            ConstructorDefinition::new(
+                "NonZero",
+                vec![
+                    boolean_ty(),
+                    Ty::Enum {
+                        name: "Binary".to_string(),
+                    },
+                ],
+            ),
+        ],
+    });
+    td.add_definition(TyDefinition::Enum {
+        name: "Tuple".to_string(),
+        constructors: vec![ConstructorDefinition::new(
+            "Tuple",
+            vec![boolean_ty(); tuple_width],
+        )],
+    });
+    td
+}
+
+fn match_expression(condition_ty_name: &str, patterns: Vec<Pattern>) -> MatchExpression {
+    MatchExpression {
+        condition_ty: Ty::Enum {
+            name: condition_ty_name.to_string(),
+        },
+        arms: patterns
+            .into_iter()
+            .map(|pattern| MatchArm { pattern })
+            .collect(),
+    }
+}
+
+/// `Tuple(True, _, ..)`, `Tuple(_, True, ..)`, ... (`Tuple(False, False, ..)` にマッチするアームがない。)
+fn wide_tuple(n: usize) -> MatchExpression {
+    let patterns = (0..n)
+        .map(|i| {
+            let args = (0..n)
+                .map(|j| {
+                    if i == j {
+                        boolean(true)
+                    } else {
+                        discard(boolean_ty())
+                    }
+                })
+                .collect();
+            ctor("Tuple", args)
+        })
+        .collect();
+    match_expression("Tuple", patterns)
+}
+
+/// `Zero`, `NonZero(_, Zero)`, `NonZero(_, NonZero(_, Zero))`, ... (深さ n を超える値にマッチするアームがない。)
+fn deep_chain(n: usize) -> MatchExpression {
+    let patterns = (0..=n)
+        .map(|depth| {
+            (0..depth).fold(ctor("Zero", vec![]), |tail, _| {
+                ctor("NonZero", vec![discard(boolean_ty()), tail])
+            })
+        })
+        .collect();
+    match_expression("Binary", patterns)
+}
+
+/// `Tuple(False, False, ..)`, `Tuple(False, True, ..)`, ..., `Tuple(True, True, ..)` (網羅的)
+fn many_arms(n: usize) -> MatchExpression {
+    let patterns = (0..1_usize << n)
+        .map(|bits| {
+            let args = (0..n).map(|i| boolean(bits >> i & 1 != 0)).collect();
+            ctor("Tuple", args)
+        })
+        .collect();
+    match_expression("Tuple", patterns)
+}
+
+/// `ヒット数/引いた回数 (ヒット率)` の形式にする。
+fn format_stats(stats: MemoStats) -> String {
+    format!(
+        "{}/{} ({:.0}%)",
+        stats.hits,
+        stats.hits + stats.misses,
+        stats.hit_rate() * 100.0
+    )
+}
+
+fn bench_group(
+    c: &mut Criterion,
+    group_name: &str,
+    sizes: &[usize],
+    gen: impl Fn(usize) -> MatchExpression,
+) {
+    let mut group = c.benchmark_group(group_name);
+    group.sample_size(10);
+    for &n in sizes {
+        let td = new_ty_database(n);
+        let expression = gen(n);
+
+        // 判定と、メモ化がどれだけ効いているかを表示する。
+        let result = check_match(&expression, &td);
+        let stats = result.stats;
+        println!(
+            "{}/{}: {:?}, subtraction {}, intersection {}, normalization {}, emptiness {}",
+            group_name,
+            n,
+            result.verdict,
+            format_stats(stats.subtraction),
+            format_stats(stats.intersection),
+            format_stats(stats.normalization),
+            format_stats(stats.emptiness)
+        );
+
+        // 計算量の上限に達して判定できなかったケースは、測っても意味がないので飛ばす。
+        if let Verdict::Undetermined(_) = result.verdict {
+            continue;
+        }
+
+        group.bench_with_input(
+            BenchmarkId::new("space", n),
+            &expression,
+            |b, expression| b.iter(|| check_match(expression, &td)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("usefulness", n),
+            &expression,
+            |b, expression| b.iter(|| UsefulnessChecker::default().check(expression, &td)),
+        );
+    }
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    bench_group(c, "wide_tuple", &[4, 8, 16, 32], wide_tuple);
+    bench_group(c, "deep_chain", &[2, 4, 8, 16, 32], deep_chain);
+    bench_group(c, "many_arms", &[2, 4, 6], many_arms);
+}
+
+criterion_group!(space_subtraction, benches);
+criterion_main!(space_subtraction);
@@ -0,0 +1,10 @@
+//! 構文解析と抽象構文木の生成がパニックせず、具象構文木がソースコードのテキストを失わないことを検査する。
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pmxc_analyzer::fuzzing;
+
+fuzz_target!(|source_code: &str| {
+    assert_eq!(fuzzing::parse(source_code), source_code);
+});
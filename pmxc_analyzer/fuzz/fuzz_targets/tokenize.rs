@@ -0,0 +1,10 @@
+//! 字句解析がパニックせず、ソースコードのテキストを失わないことを検査する。
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pmxc_analyzer::fuzzing;
+
+fuzz_target!(|source_code: &str| {
+    assert_eq!(fuzzing::tokenize(source_code), source_code);
+});
@@ -0,0 +1,10 @@
+//! エディタの検証 (構文解析、網羅性検査、診断の表示) がパニックしないことを検査する。
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pmxc_analyzer::fuzzing;
+
+fuzz_target!(|source_code: &str| {
+    fuzzing::validate(source_code);
+});
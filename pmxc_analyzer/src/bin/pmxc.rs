@@ -0,0 +1,16 @@
+use std::env;
+use std::io;
+use std::process;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    // ロックしたままにすると、pmxc lsp が標準入出力を別のスレッドで使えなくなる。
+    let code = pmxc_analyzer::run_cli(
+        &args,
+        &mut io::stdin(),
+        &mut io::stdout(),
+        &mut io::stderr(),
+    );
+    process::exit(code);
+}
@@ -0,0 +1,402 @@
+//! コマンドラインツール (pmxc) の実装
+//!
+//! 入出力を引数で受け取るようにして、テストから呼べるようにしている。
+
+use crate::crash;
+use crate::diagnostic::{self, Diagnostic, Severity};
+use crate::json;
+use crate::messages::{Locale, Message};
+use crate::render::Renderer;
+use crate::report;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::rc::Rc;
+
+pub(crate) const EXIT_OK: i32 = 0;
+pub(crate) const EXIT_NOT_EXHAUSTIVE: i32 = 1;
+pub(crate) const EXIT_ERROR: i32 = 2;
+
+/// 環境変数から表示する言語を決める。(POSIX のロケールと同じ優先順位。)
+fn locale_from_env() -> Locale {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| Locale::from_tag(&value))
+        .unwrap_or_default()
+}
+
+/// 出力に色を付けるか
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ColorChoice {
+    /// 標準出力が端末で、環境変数 NO_COLOR が設定されていなければ付ける。
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn from_str(value: &str) -> Option<ColorChoice> {
+        match value {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    fn is_enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && io::stdout().is_terminal()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// 診断の出力形式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// 人が読むための形式 (ソースコードを引用する)
+    Human,
+    Json,
+    Sarif,
+}
+
+impl Format {
+    fn from_str(value: &str) -> Option<Format> {
+        match value {
+            "human" => Some(Format::Human),
+            "json" => Some(Format::Json),
+            "sarif" => Some(Format::Sarif),
+            _ => None,
+        }
+    }
+}
+
+/// コマンドの引数
+struct Options<'a> {
+    locale: Locale,
+    color: ColorChoice,
+    format: Format,
+    path_opt: Option<&'a str>,
+}
+
+fn parse_options<'a>(args: &[&'a str]) -> Option<Options<'a>> {
+    let mut locale_opt = None;
+    let mut color = ColorChoice::Auto;
+    let mut format = Format::Human;
+    let mut path_opt = None;
+
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        // `--name value` と `--name=value` のどちらも受け付ける。
+        let (name, value_opt) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+            _ => (arg, None),
+        };
+
+        match name {
+            "--locale" | "--color" | "--format" => {
+                let value = match value_opt {
+                    Some(value) => value,
+                    None => *args.next()?,
+                };
+
+                match name {
+                    "--locale" => locale_opt = Some(Locale::from_tag(value)?),
+                    "--color" => color = ColorChoice::from_str(value)?,
+                    _ => format = Format::from_str(value)?,
+                }
+            }
+            _ if path_opt.is_none() && (arg == "-" || !arg.starts_with('-')) => {
+                path_opt = Some(arg)
+            }
+            _ => return None,
+        }
+    }
+
+    Some(Options {
+        locale: locale_opt.unwrap_or_else(locale_from_env),
+        color,
+        format,
+        path_opt,
+    })
+}
+
+fn read_input(path_opt: Option<&str>, stdin: &mut dyn Read) -> Result<String, Message> {
+    match path_opt {
+        None | Some("-") => {
+            let mut input = String::new();
+            stdin
+                .read_to_string(&mut input)
+                .map_err(|err| Message::CliCannotReadStdin {
+                    error: err.to_string(),
+                })?;
+            Ok(input)
+        }
+        Some(path) => fs::read_to_string(path).map_err(|err| Message::CliCannotReadFile {
+            path: path.to_string(),
+            error: err.to_string(),
+        }),
+    }
+}
+
+fn check(
+    options: &Options,
+    stdin: &mut dyn Read,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> i32 {
+    let locale = options.locale;
+    let source_code = match read_input(options.path_opt, stdin) {
+        Ok(source_code) => source_code,
+        Err(message) => {
+            writeln!(stderr, "error: {}", message.render(locale)).ok();
+            return EXIT_ERROR;
+        }
+    };
+
+    let path = match options.path_opt {
+        None | Some("-") => "<stdin>",
+        Some(path) => path,
+    };
+
+    // 検査の途中でパニックが起きたら、内部エラーの診断として報告する。
+    let diagnostics =
+        crash::catch_panic(|| diagnostic::collect_diagnostics(Rc::new(source_code.clone())))
+            .unwrap_or_else(|message| vec![Diagnostic::internal_error(message)]);
+    match options.format {
+        Format::Human => {
+            let renderer = Renderer::new(path, &source_code, locale, options.color.is_enabled());
+            for diagnostic in diagnostics.iter() {
+                write!(stdout, "{}", renderer.render(diagnostic)).ok();
+            }
+        }
+        Format::Json => {
+            let report = report::json_report(path, &diagnostics, locale);
+            writeln!(stdout, "{}", serde_json::to_string_pretty(&report).unwrap()).ok();
+        }
+        Format::Sarif => {
+            let log = report::sarif_report(path, &diagnostics, locale);
+            writeln!(stdout, "{}", serde_json::to_string_pretty(&log).unwrap()).ok();
+        }
+    }
+
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        EXIT_NOT_EXHAUSTIVE
+    } else {
+        EXIT_OK
+    }
+}
+
+fn check_json(
+    options: &Options,
+    stdin: &mut dyn Read,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> i32 {
+    let locale = options.locale;
+    let input = match read_input(options.path_opt, stdin) {
+        Ok(input) => input,
+        Err(message) => {
+            writeln!(stderr, "error: {}", message.render(locale)).ok();
+            return EXIT_ERROR;
+        }
+    };
+
+    let request = match serde_json::from_str::<json::CheckRequest>(&input) {
+        Ok(request) => request,
+        Err(err) => {
+            let message = Message::CliInvalidJson {
+                error: err.to_string(),
+            };
+            writeln!(stderr, "error: {}", message.render(locale)).ok();
+            return EXIT_ERROR;
+        }
+    };
+
+    let response = match json::check_request(&request) {
+        Ok(response) => response,
+        Err(error) => {
+            let message = Message::CliInvalidRequest { error };
+            writeln!(stderr, "error: {}", message.render(locale)).ok();
+            return EXIT_ERROR;
+        }
+    };
+    writeln!(
+        stdout,
+        "{}",
+        serde_json::to_string_pretty(&response).unwrap()
+    )
+    .ok();
+
+    // 判定できなかった match 式は、check コマンドと同様に警告の扱いとする。
+    if response
+        .results
+        .iter()
+        .any(|result| result.verdict == json::VerdictKind::NonExhaustive)
+    {
+        EXIT_NOT_EXHAUSTIVE
+    } else {
+        EXIT_OK
+    }
+}
+
+/// コマンドを実行して、終了コードを返す。(args にプログラム名は含まない。)
+pub fn run(
+    args: &[String],
+    stdin: &mut dyn Read,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> i32 {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let (command, rest) = match args.split_first() {
+        Some((&command, rest)) => (command, rest),
+        None => ("", &[][..]),
+    };
+    let options_opt = parse_options(rest);
+
+    match (command, options_opt) {
+        ("check", Some(options)) => check(&options, stdin, stdout, stderr),
+        ("check-json", Some(options)) => check_json(&options, stdin, stdout, stderr),
+        // LSP のサーバーはプロセスの標準入出力で通信する。(引数の stdin, stdout は使わない。)
+        #[cfg(not(target_arch = "wasm32"))]
+        ("lsp", Some(options)) => match crate::lsp::run_stdio() {
+            Ok(()) => EXIT_OK,
+            Err(error) => {
+                let message = Message::CliLspError {
+                    error: error.to_string(),
+                };
+                writeln!(stderr, "error: {}", message.render(options.locale)).ok();
+                EXIT_ERROR
+            }
+        },
+        ("help", Some(options)) | ("--help", Some(options)) | ("-h", Some(options)) => {
+            write!(stdout, "{}", Message::CliUsage.render(options.locale)).ok();
+            EXIT_OK
+        }
+        _ => {
+            write!(stderr, "{}", Message::CliUsage.render(locale_from_env())).ok();
+            EXIT_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with_stdin(args: &[&str], input: &str) -> (i32, String, String) {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        let code = run(&args, &mut input.as_bytes(), &mut stdout, &mut stderr);
+        (
+            code,
+            String::from_utf8(stdout).unwrap(),
+            String::from_utf8(stderr).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_check_json_from_stdin() {
+        let input = r#"{
+            "definitions": [
+                { "kind": "enum", "name": "Boolean", "constructors": [{ "name": "False" }, { "name": "True" }] }
+            ],
+            "expressions": [
+                {
+                    "condition_ty": { "kind": "enum", "name": "Boolean" },
+                    "arms": [{ "pattern": { "kind": "constructor", "name": "True" } }]
+                }
+            ]
+        }"#;
+
+        let (code, stdout, _) = run_with_stdin(&["check-json", "-"], input);
+        assert_eq!(code, EXIT_NOT_EXHAUSTIVE);
+        assert!(stdout.contains(r#""text": "False""#));
+    }
+
+    #[test]
+    fn test_check_json_rejects_invalid_request() {
+        let input = r#"{
+            "definitions": [
+                { "kind": "enum", "name": "Boolean", "constructors": [{ "name": "False" }, { "name": "True" }] }
+            ],
+            "expressions": [
+                {
+                    "condition_ty": { "kind": "enum", "name": "Boolean" },
+                    "arms": [{ "pattern": { "kind": "constructor", "name": "True", "args": [{ "kind": "constructor", "name": "True" }] } }]
+                }
+            ]
+        }"#;
+
+        let (code, stdout, stderr) = run_with_stdin(&["check-json", "--locale", "en", "-"], input);
+        assert_eq!(code, EXIT_ERROR);
+        assert_eq!(stdout, "");
+        assert!(
+            stderr.starts_with("error: invalid request: expressions[0].arms[0].pattern:"),
+            "{}",
+            stderr
+        );
+    }
+
+    #[test]
+    fn test_check_with_locale() {
+        let input = "enum Boolean {\n    True,\n    False,\n}\nmatch True {\n    True => {}\n}\n";
+
+        let (code, stdout, _) =
+            run_with_stdin(&["check", "--locale", "en", "--color", "never"], input);
+        assert_eq!(code, EXIT_NOT_EXHAUSTIVE);
+        assert!(stdout.starts_with(
+            "error[E0001]: non-exhaustive patterns: False not covered\n --> <stdin>:5:1\n"
+        ));
+
+        let (_, stdout, _) = run_with_stdin(&["check", "--locale=ja", "--color=never", "-"], input);
+        assert!(stdout.starts_with("error[E0001]: 網羅的ではありません (例: False)\n"));
+        assert!(stdout.contains("enum Boolean はここで定義されています"));
+
+        let (_, stdout, _) = run_with_stdin(&["check", "--color=always"], input);
+        assert!(stdout.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_check_with_format() {
+        let input = "enum Boolean {\n    True,\n    False,\n}\nmatch True {\n    True => {}\n}\n";
+
+        let (code, stdout, _) = run_with_stdin(&["check", "--format=json"], input);
+        assert_eq!(code, EXIT_NOT_EXHAUSTIVE);
+        let report = serde_json::from_str::<serde_json::Value>(&stdout).unwrap();
+        assert_eq!(report["diagnostics"][0]["witnesses"][0], "False");
+
+        let (code, stdout, _) = run_with_stdin(&["check", "--format", "sarif"], input);
+        assert_eq!(code, EXIT_NOT_EXHAUSTIVE);
+        let log = serde_json::from_str::<serde_json::Value>(&stdout).unwrap();
+        assert_eq!(log["runs"][0]["results"][0]["ruleId"], "E0001");
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let (code, _, stderr) = run_with_stdin(&["unknown"], "");
+        assert_eq!(code, EXIT_ERROR);
+        assert!(stderr.contains("check-json"));
+
+        let (code, _, _) = run_with_stdin(&["check", "--locale", "xx"], "");
+        assert_eq!(code, EXIT_ERROR);
+
+        let (code, _, _) = run_with_stdin(&["check", "--color=sometimes"], "");
+        assert_eq!(code, EXIT_ERROR);
+
+        let (code, _, _) = run_with_stdin(&["check", "--format=xml"], "");
+        assert_eq!(code, EXIT_ERROR);
+
+        let (code, _, stderr) = run_with_stdin(&["check-json", "--locale", "en"], "{");
+        assert_eq!(code, EXIT_ERROR);
+        assert!(stderr.starts_with("error: invalid JSON"));
+    }
+}
@@ -0,0 +1,61 @@
+//! 内部エラー (パニック) の捕捉
+//!
+//! 検査の途中でパニックが起きても、処理を止めずに「内部エラー」(`E0901`) として報告するためのもの。
+//! パニックフックでメッセージを記録しておき、`catch_panic` で捕捉したときに返す。
+//!
+//! 捕捉できるのはパニックが巻き戻される環境 (ネイティブの pmxc やテスト) だけである。
+//! wasm32 ではパニックが即座にトラップになるので、`catch_panic` は何もしない。
+//! その場合は呼び出し側 (プレイグラウンド) が例外を受け取り、`last_panic_message` でメッセージを取り出してから、
+//! 壊れたインスタンスを捨てて wasm モジュールを読み込み直す。
+
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+thread_local! {
+    static LAST_PANIC_MESSAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static INSTALL_PANIC_HOOK: Once = Once::new();
+
+/// パニックのメッセージを記録するフックを設定する。(何度呼んでもよい。)
+///
+/// 元のフックも呼ぶので、標準エラー出力への表示はそのまま残る。
+pub(crate) fn install_panic_hook() {
+    INSTALL_PANIC_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let message = info.to_string();
+            LAST_PANIC_MESSAGE.with(|last| *last.borrow_mut() = Some(message));
+            default_hook(info);
+        }));
+    });
+}
+
+/// 最後に起きたパニックのメッセージ
+pub(crate) fn last_panic_message() -> Option<String> {
+    LAST_PANIC_MESSAGE.with(|last| last.borrow().clone())
+}
+
+/// 関数を呼び出す。パニックが起きたときは、そのメッセージを Err で返す。
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    install_panic_hook();
+    LAST_PANIC_MESSAGE.with(|last| *last.borrow_mut() = None);
+
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .map_err(|_| last_panic_message().unwrap_or_else(|| "unknown panic".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(catch_panic(|| 1 + 1), Ok(2));
+
+        let message = catch_panic(|| -> usize { panic!("boom") }).unwrap_err();
+        assert!(message.contains("boom"), "{}", message);
+        assert_eq!(last_panic_message(), Some(message));
+    }
+}
@@ -0,0 +1,274 @@
+//! 診断 (エラーや警告)
+//!
+//! 構文解析、中間表現への変換、網羅性検査の各段階で見つかった問題を同じ形式で表す。
+
+use crate::match_exhaustivity::lower;
+use crate::messages::{Locale, Message};
+use crate::syntax::{self, ast_gen, parse, ParseError, TextPosition, TextRange};
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+
+/// 診断の種類
+///
+/// コード (`E0001` など) は外部のツールから参照されるので、一度決めたら変更しない。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum DiagnosticCode {
+    // 網羅性検査
+    NonExhaustive,
+    UnreachableArm,
+    Undetermined,
+
+    // 名前解決・型検査
+    UndefinedName,
+    TypeMismatch,
+    ArityMismatch,
+
+    // 構文
+    ExpectedCommaOrEol,
+    ExpectedExpr,
+    ExpectedFatArrow,
+    ExpectedIdent,
+    ExpectedLeftBrace,
+    ExpectedRightBrace,
+    ExpectedRightParen,
+    UnexpectedChars,
+    NestingTooDeep,
+
+    // 処理系の不具合
+    InternalError,
+}
+
+impl DiagnosticCode {
+    pub(crate) const ALL: [DiagnosticCode; 16] = [
+        DiagnosticCode::NonExhaustive,
+        DiagnosticCode::UnreachableArm,
+        DiagnosticCode::Undetermined,
+        DiagnosticCode::UndefinedName,
+        DiagnosticCode::TypeMismatch,
+        DiagnosticCode::ArityMismatch,
+        DiagnosticCode::ExpectedCommaOrEol,
+        DiagnosticCode::ExpectedExpr,
+        DiagnosticCode::ExpectedFatArrow,
+        DiagnosticCode::ExpectedIdent,
+        DiagnosticCode::ExpectedLeftBrace,
+        DiagnosticCode::ExpectedRightBrace,
+        DiagnosticCode::ExpectedRightParen,
+        DiagnosticCode::UnexpectedChars,
+        DiagnosticCode::NestingTooDeep,
+        DiagnosticCode::InternalError,
+    ];
+
+    pub(crate) fn code(self) -> &'static str {
+        match self {
+            DiagnosticCode::NonExhaustive => "E0001",
+            DiagnosticCode::UnreachableArm => "W0001",
+            DiagnosticCode::Undetermined => "W0002",
+            DiagnosticCode::UndefinedName => "E0101",
+            DiagnosticCode::TypeMismatch => "E0102",
+            DiagnosticCode::ArityMismatch => "E0103",
+            DiagnosticCode::ExpectedCommaOrEol => "E0201",
+            DiagnosticCode::ExpectedExpr => "E0202",
+            DiagnosticCode::ExpectedFatArrow => "E0203",
+            DiagnosticCode::ExpectedIdent => "E0204",
+            DiagnosticCode::ExpectedLeftBrace => "E0205",
+            DiagnosticCode::ExpectedRightBrace => "E0206",
+            DiagnosticCode::ExpectedRightParen => "E0207",
+            DiagnosticCode::UnexpectedChars => "E0208",
+            DiagnosticCode::NestingTooDeep => "E0209",
+            DiagnosticCode::InternalError => "E0901",
+        }
+    }
+
+    /// 人が読むための名前 (例: `non-exhaustive`)。コードと同様に変更しない。
+    pub(crate) fn slug(self) -> &'static str {
+        match self {
+            DiagnosticCode::NonExhaustive => "non-exhaustive",
+            DiagnosticCode::UnreachableArm => "unreachable-arm",
+            DiagnosticCode::Undetermined => "undetermined",
+            DiagnosticCode::UndefinedName => "undefined-name",
+            DiagnosticCode::TypeMismatch => "type-mismatch",
+            DiagnosticCode::ArityMismatch => "arity-mismatch",
+            DiagnosticCode::ExpectedCommaOrEol => "expected-comma-or-eol",
+            DiagnosticCode::ExpectedExpr => "expected-expr",
+            DiagnosticCode::ExpectedFatArrow => "expected-fat-arrow",
+            DiagnosticCode::ExpectedIdent => "expected-ident",
+            DiagnosticCode::ExpectedLeftBrace => "expected-left-brace",
+            DiagnosticCode::ExpectedRightBrace => "expected-right-brace",
+            DiagnosticCode::ExpectedRightParen => "expected-right-paren",
+            DiagnosticCode::UnexpectedChars => "unexpected-chars",
+            DiagnosticCode::NestingTooDeep => "nesting-too-deep",
+            DiagnosticCode::InternalError => "internal-error",
+        }
+    }
+
+    pub(crate) fn severity(self) -> Severity {
+        match self {
+            DiagnosticCode::UnreachableArm | DiagnosticCode::Undetermined => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl From<ParseError> for DiagnosticCode {
+    fn from(error: ParseError) -> DiagnosticCode {
+        match error {
+            ParseError::ExpectedCommaOrEol => DiagnosticCode::ExpectedCommaOrEol,
+            ParseError::ExpectedExpr => DiagnosticCode::ExpectedExpr,
+            ParseError::ExpectedFatArrow => DiagnosticCode::ExpectedFatArrow,
+            ParseError::ExpectedIdent => DiagnosticCode::ExpectedIdent,
+            ParseError::ExpectedLeftBrace => DiagnosticCode::ExpectedLeftBrace,
+            ParseError::ExpectedRightBrace => DiagnosticCode::ExpectedRightBrace,
+            ParseError::ExpectedRightParen => DiagnosticCode::ExpectedRightParen,
+            ParseError::UnexpectedChars => DiagnosticCode::UnexpectedChars,
+            ParseError::NestingTooDeep => DiagnosticCode::NestingTooDeep,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// 診断に関連する別の箇所
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RelatedInfo {
+    pub(crate) range: TextRange,
+    pub(crate) message: Message,
+}
+
+/// 問題を解消するための修正案
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Fix {
+    pub(crate) title: Message,
+
+    /// 置き換える範囲と、置き換え後のテキスト
+    pub(crate) edits: Vec<(TextRange, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Diagnostic {
+    pub(crate) code: DiagnosticCode,
+    pub(crate) severity: Severity,
+    pub(crate) range: TextRange,
+    pub(crate) message: Message,
+    pub(crate) related: Vec<RelatedInfo>,
+    pub(crate) notes: Vec<Message>,
+    pub(crate) fixes: Vec<Fix>,
+
+    /// 不要なコードを指している診断か？ (エディタで薄く表示する。)
+    pub(crate) is_unnecessary: bool,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(code: DiagnosticCode, range: TextRange, message: Message) -> Self {
+        Diagnostic {
+            code,
+            severity: code.severity(),
+            range,
+            message,
+            related: vec![],
+            notes: vec![],
+            fixes: vec![],
+            is_unnecessary: false,
+        }
+    }
+
+    /// 検査の途中でパニックが起きたことを表す診断。(位置は文書の先頭とする。)
+    pub(crate) fn internal_error(message: String) -> Self {
+        let start = TextPosition::new(1, 1);
+        Diagnostic::new(
+            DiagnosticCode::InternalError,
+            TextRange::new(start, start),
+            Message::InternalError { message },
+        )
+        .with_note(Message::InternalErrorNote)
+    }
+
+    pub(crate) fn with_related(mut self, range: TextRange, message: Message) -> Self {
+        self.related.push(RelatedInfo { range, message });
+        self
+    }
+
+    pub(crate) fn with_note(mut self, note: Message) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub(crate) fn with_fix(mut self, fix: Fix) -> Self {
+        self.fixes.push(fix);
+        self
+    }
+
+    pub(crate) fn unnecessary(mut self) -> Self {
+        self.is_unnecessary = true;
+        self
+    }
+
+    /// マッチしないパターンの例を列挙する。(網羅的でない match 式の診断でなければ空。)
+    pub(crate) fn witnesses(&self) -> Vec<&str> {
+        let mut witnesses = vec![];
+        if let Message::NonExhaustive {
+            witness_opt: Some(witness),
+        } = &self.message
+        {
+            witnesses.push(witness.as_str());
+        }
+        for note in self.notes.iter() {
+            if let Message::AlsoNotMatched { pattern } = note {
+                witnesses.push(pattern.as_str());
+            }
+        }
+        witnesses
+    }
+
+    /// `15:1-15:6 error[E0001]: ...` の形式の文字列にする。(関連箇所や注記は含まない。)
+    pub(crate) fn render(&self, locale: Locale) -> String {
+        format!(
+            "{} {}[{}]: {}",
+            self.range,
+            self.severity.as_str(),
+            self.code.code(),
+            self.message.render(locale)
+        )
+    }
+}
+
+/// 既定の言語で `render` と同じ形式で表示する。
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(Locale::default()))
+    }
+}
+
+/// ソースコードを検査して、すべての診断を列挙する。
+///
+/// 構文エラーがあるときは、構文エラーだけを報告する。
+pub(crate) fn collect_diagnostics(source_code: Rc<String>) -> Vec<Diagnostic> {
+    let root = parse::parse(source_code);
+
+    let mut cursor = syntax::TextCursor::default();
+    let mut diagnostics = vec![];
+    parse::collect_errors(&root, &mut cursor, &mut diagnostics);
+    if !diagnostics.is_empty() {
+        return diagnostics;
+    }
+
+    let root = Rc::new(root);
+    let token_range_map = syntax::TokenRangeMap::new(&root);
+    let ast = ast_gen::gen_root(root);
+    let mut model = lower::from_ast(&ast, token_range_map);
+    lower::check(&mut model);
+
+    model.diagnostics
+}
@@ -0,0 +1,81 @@
+//! ファジング (fuzz ディレクトリ) のための入口
+//!
+//! 内部の関数を呼び出して、不変条件を検査しやすい形で結果を返す。ライブラリの利用者向けではない。
+
+use crate::diagnostic;
+use crate::messages::Locale;
+use crate::render::Renderer;
+use crate::report;
+use crate::syntax::{ast_gen, parse, tokenize, Element, NodeData, TokenData, Trivia};
+use std::rc::Rc;
+
+fn write_token(token: &TokenData, out: &mut String) {
+    for trivia in token.leading() {
+        if let Trivia::Token(trivia) = trivia {
+            write_token(trivia, out);
+        }
+    }
+    out.push_str(token.text());
+    for trivia in token.trailing() {
+        if let Trivia::Token(trivia) = trivia {
+            write_token(trivia, out);
+        }
+    }
+}
+
+fn write_node(node: &NodeData, out: &mut String) {
+    for element in node.children() {
+        match element {
+            Element::Token(token) => write_token(token, out),
+            Element::Error(_) => {}
+            Element::Node(node) => write_node(node, out),
+        }
+    }
+}
+
+/// 字句解析して、すべての字句 (トリビアを含む) のテキストをつなげたものを返す。
+///
+/// 字句解析は情報を失わないので、元のソースコードと一致するはず。
+pub fn tokenize(source_code: &str) -> String {
+    let tokens = tokenize::tokenize(Rc::new(source_code.to_string()));
+
+    let mut text = String::new();
+    for token in tokens.iter() {
+        write_token(token, &mut text);
+    }
+    text
+}
+
+/// 構文解析して抽象構文木を作り、具象構文木のテキストを返す。
+///
+/// 具象構文木は情報を失わないので、元のソースコードと一致するはず。
+pub fn parse(source_code: &str) -> String {
+    let root = Rc::new(parse::parse(Rc::new(source_code.to_string())));
+
+    let mut text = String::new();
+    write_node(&root, &mut text);
+
+    ast_gen::gen_root(root);
+    text
+}
+
+/// エディタの検証 (wasm の `validate`) と同じく、すべての診断を作って表示する。
+pub fn validate(source_code: &str) {
+    let diagnostics = diagnostic::collect_diagnostics(Rc::new(source_code.to_string()));
+
+    for &locale in [Locale::Ja, Locale::En].iter() {
+        let renderer = Renderer::new("fuzz.pmxclang", source_code, locale, true);
+        for diagnostic in diagnostics.iter() {
+            renderer.render(diagnostic);
+
+            #[cfg(feature = "wasm")]
+            serde_json::to_string(&crate::wasm::monaco::editor::MarkerData::new(
+                diagnostic, locale,
+            ))
+            .unwrap();
+        }
+
+        serde_json::to_string(&report::sarif_report("fuzz.pmxclang", &diagnostics, locale))
+            .unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+//! エディタ向けの機能
+//!
+//! 定義への移動や名前の変更など、エディタから利用する問い合わせを提供する。
+//! (網羅性検査アルゴリズムとは無関係。)
+
+// LSP のサーバーはアウトラインだけを使う。それ以外は wasm からだけ使う。
+#[cfg(any(test, feature = "wasm"))]
+pub(crate) mod completion;
+#[cfg(any(test, feature = "wasm"))]
+pub(crate) mod navigation;
+pub(crate) mod outline;
+#[cfg(any(test, feature = "wasm"))]
+pub(crate) mod rename;
+#[cfg(any(test, feature = "wasm"))]
+pub(crate) mod semantic_tokens;
+#[cfg(any(test, feature = "wasm"))]
+pub(crate) mod symbols;
+
+#[cfg(any(test, feature = "wasm"))]
+pub(crate) use symbols::SymbolIndex;
+
+use crate::match_exhaustivity::lower::{self, MatchExhaustivityModel};
+use crate::syntax::{self, ast_gen, parse, Root};
+use std::rc::Rc;
+
+/// ソースコードを解析した結果。エディタの問い合わせはこれをもとに答える。
+pub(crate) struct Analysis {
+    pub(crate) ast: Root,
+    pub(crate) model: MatchExhaustivityModel,
+    #[cfg(any(test, feature = "wasm"))]
+    pub(crate) symbol_index: SymbolIndex,
+}
+
+impl Analysis {
+    pub(crate) fn new(source_code: Rc<String>) -> Self {
+        let root = Rc::new(parse::parse(source_code));
+        let token_range_map = syntax::TokenRangeMap::new(&root);
+        let ast = ast_gen::gen_root(root);
+        #[cfg(any(test, feature = "wasm"))]
+        let symbol_index = SymbolIndex::new(&ast, &token_range_map);
+        let model = lower::from_ast(&ast, token_range_map);
+
+        Analysis {
+            ast,
+            model,
+            #[cfg(any(test, feature = "wasm"))]
+            symbol_index,
+        }
+    }
+}
@@ -0,0 +1,460 @@
+//! 入力補完
+//!
+//! match 式のアームのパターンを書く位置で、条件式の型のコンストラクタを候補として提示する。
+//! まだどのアームにもマッチしないコンストラクタを先に並べる。
+
+use super::Analysis;
+use crate::match_exhaustivity::space::{SpaceCx, SpaceId};
+use crate::match_exhaustivity::space_from_ty::{
+    space_can_decompose, space_decompose, space_from_ty,
+};
+use crate::match_exhaustivity::space_intersection::space_intersection;
+use crate::match_exhaustivity::{use_cases, MatchArm, MatchExpression, Space, Ty, TyDatabase};
+use crate::syntax::{Element, MatchStmt, Node, NodeData, Stmt, TextPosition, Token, TokenRangeMap};
+use std::rc::Rc;
+
+/// 補完候補
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CompletionItem {
+    /// コンストラクタの名前
+    pub(crate) label: String,
+
+    /// コンストラクタが属する enum の名前
+    pub(crate) detail: String,
+
+    /// 挿入するテキスト。引数の位置に `_` を置いたスニペットになっている。
+    /// 例: `Two(${1:_})`
+    pub(crate) insert_text: String,
+
+    /// 並び順を決めるためのキー
+    pub(crate) sort_text: String,
+}
+
+/// カーソルを囲んでいるコンストラクタパターンの1つ
+struct PatStep {
+    /// コンストラクタの名前
+    name: String,
+
+    /// カーソルがある引数の番号
+    index: usize,
+
+    /// カーソルがない位置に書かれている引数のパターンと、その番号
+    siblings: Vec<(usize, Rc<NodeData>)>,
+}
+
+/// パターンの中でカーソルがある位置。
+///
+/// 外側から順に、カーソルを囲んでいるコンストラクタパターンを並べたもの。
+/// 例えば `NonZero(True, Two(|))` なら `NonZero` の 1 番目 (兄弟は `True`)、`Two` の 0 番目になる。
+type PatPath = Vec<PatStep>;
+
+/// パターンの中のカーソルの位置を調べる。
+fn pat_path(node: &NodeData, position: TextPosition, map: &TokenRangeMap) -> Option<PatPath> {
+    match node.node() {
+        Node::Call => {
+            let left = map.token_range(node, Token::LeftParen)?;
+            if position < left.end() {
+                return Some(vec![]);
+            }
+
+            if let Some(right) = map.token_range(node, Token::RightParen) {
+                if position > right.start() {
+                    return None;
+                }
+            }
+
+            let name = node
+                .first_node(|node| node.node() == Node::Name)
+                .and_then(|name| name.first_ident())?;
+
+            // ある位置より前にあるカンマの個数が、その位置の引数の番号になる。
+            let index_at = |position: TextPosition| {
+                node.children()
+                    .iter()
+                    .filter(|element| match element {
+                        Element::Token(token) if token.token() == Token::Comma => {
+                            map.get(token).is_some_and(|r| r.end() <= position)
+                        }
+                        _ => false,
+                    })
+                    .count()
+            };
+            let index = index_at(position);
+
+            let mut arg_pat_opt = None;
+            let mut siblings = vec![];
+            for arg in node.filter_node(|node| node.node() == Node::Argument) {
+                let pat = match arg.first_node(|node| node.node().is_pat()) {
+                    Some(pat) => pat,
+                    None => continue,
+                };
+                let range = match map.node_range(&pat) {
+                    Some(range) => range,
+                    None => continue,
+                };
+
+                if range.contains(position) {
+                    arg_pat_opt = Some(pat);
+                } else {
+                    let arg_index = index_at(range.start());
+                    if arg_index != index {
+                        siblings.push((arg_index, pat));
+                    }
+                }
+            }
+
+            let mut path = vec![PatStep {
+                name,
+                index,
+                siblings,
+            }];
+            if let Some(arg_pat) = arg_pat_opt {
+                path.extend(pat_path(&arg_pat, position, map)?);
+            }
+
+            Some(path)
+        }
+        Node::Group => match node.first_node(|node| node.node().is_pat()) {
+            Some(body) => pat_path(&body, position, map),
+            None => Some(vec![]),
+        },
+        _ => Some(vec![]),
+    }
+}
+
+/// アームの中のカーソルの位置を調べる。パターンの外にあるときは None。
+fn arm_path(arm: &NodeData, position: TextPosition, map: &TokenRangeMap) -> Option<PatPath> {
+    // `=>` 以降はパターンの位置ではない。
+    if let Some(arrow) = map.token_range(arm, Token::Equal) {
+        if position >= arrow.start() {
+            return None;
+        }
+    }
+
+    let pat = arm.first_node(|node| node.node().is_pat())?;
+    let range = map.node_range(&pat)?;
+
+    // 閉じ括弧がまだ書かれていないなら、パターンの後ろも引数の位置とみなす。
+    let is_open = pat.node() == Node::Call
+        && pat
+            .first_token(|t| t.token() == Token::RightParen)
+            .is_none();
+
+    if position < range.start() {
+        Some(vec![])
+    } else if range.contains(position) || is_open {
+        pat_path(&pat, position, map)
+    } else {
+        None
+    }
+}
+
+/// スペースの最も外側にあるコンストラクタの名前を列挙する。
+fn constructor_names(space: SpaceId, cx: &mut SpaceCx<TyDatabase>, names: &mut Vec<String>) {
+    match cx.space(space).clone() {
+        Space::Constructor { name, .. } => names.push(cx.symbol_str(name).to_string()),
+        Space::Union(spaces) => {
+            for space in spaces {
+                constructor_names(space, cx, names);
+            }
+        }
+        Space::Ty(..) if space_can_decompose(space, cx) => {
+            let space = space_decompose(space, cx);
+            constructor_names(space, cx, names);
+        }
+        Space::Ty(..) => {}
+    }
+}
+
+/// スペースのうち、コンストラクタ `name` の `index` 番目の引数の部分を取り出す。
+fn project(space: SpaceId, name: &str, index: usize, cx: &mut SpaceCx<TyDatabase>) -> SpaceId {
+    // (空の引数を持つコンストラクタスペースは空なので、他の引数を取り出しても値はない。)
+    if cx.is_empty(space) {
+        return cx.new_empty();
+    }
+
+    match cx.space(space).clone() {
+        Space::Constructor {
+            name: space_name,
+            args,
+        } if cx.symbol_str(space_name) == name && index < args.len() => args[index],
+        Space::Constructor { .. } => cx.new_empty(),
+        Space::Union(spaces) => {
+            let spaces = spaces
+                .into_iter()
+                .map(|space| project(space, name, index, cx))
+                .collect::<Vec<_>>();
+            cx.new_union(spaces)
+        }
+        Space::Ty(..) if space_can_decompose(space, cx) => {
+            let space = space_decompose(space, cx);
+            project(space, name, index, cx)
+        }
+        Space::Ty(..) => cx.new_empty(),
+    }
+}
+
+/// 書かれているパターンが表すスペースを作る。
+///
+/// 書きかけのパターンや、解決できない名前を含むパターンは、型 `ty` のすべての値とみなす。
+fn pat_space(pat: &NodeData, ty: &Ty, td: &TyDatabase, cx: &mut SpaceCx<TyDatabase>) -> SpaceId {
+    pat_space_opt(pat, ty, td, cx).unwrap_or_else(|| space_from_ty(ty, cx))
+}
+
+fn pat_space_opt(
+    pat: &NodeData,
+    ty: &Ty,
+    td: &TyDatabase,
+    cx: &mut SpaceCx<TyDatabase>,
+) -> Option<SpaceId> {
+    let (name, arg_pats) = match pat.node() {
+        Node::Group => {
+            let body = pat.first_node(|node| node.node().is_pat())?;
+            return pat_space_opt(&body, ty, td, cx);
+        }
+        Node::Name => (pat.first_ident()?, vec![]),
+        Node::Call => {
+            let name = pat
+                .first_node(|node| node.node() == Node::Name)
+                .and_then(|name| name.first_ident())?;
+            let arg_pats = pat
+                .filter_node(|node| node.node() == Node::Argument)
+                .into_iter()
+                .map(|arg| arg.first_node(|node| node.node().is_pat()))
+                .collect::<Option<Vec<_>>>()?;
+            (name, arg_pats)
+        }
+        _ => return None,
+    };
+
+    let (enum_name, constructor_definition) = td.find_constructor_by_name(&name)?;
+    match ty {
+        Ty::Enum { name } if name == enum_name => {}
+        _ => return None,
+    }
+    if arg_pats.len() != constructor_definition.arg_tys.len() {
+        return None;
+    }
+
+    let args = arg_pats
+        .iter()
+        .zip(&constructor_definition.arg_tys)
+        .map(|(arg_pat, arg_ty)| pat_space(arg_pat, arg_ty, td, cx))
+        .collect();
+    let name = cx.intern_symbol(&name);
+    Some(cx.new_constructor(name, args))
+}
+
+fn snippet(name: &str, arity: usize) -> String {
+    if arity == 0 {
+        return name.to_string();
+    }
+
+    let args = (1..=arity)
+        .map(|i| format!("${{{}:_}}", i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{}({})", name, args)
+}
+
+/// 指定した位置での補完候補を列挙する。
+pub(crate) fn completion(analysis: &Analysis, position: TextPosition) -> Vec<CompletionItem> {
+    completion_opt(analysis, position).unwrap_or_default()
+}
+
+fn completion_opt(analysis: &Analysis, position: TextPosition) -> Option<Vec<CompletionItem>> {
+    let model = &analysis.model;
+    let map = model.token_range_map();
+    let td = &model.ty_database;
+
+    // カーソルを中括弧の中に含む match 文を探す。
+    let node = analysis.ast.stmts.iter().find_map(|stmt| match stmt {
+        Stmt::Match(MatchStmt { node, .. }) => {
+            let left = map.token_range(node, Token::LeftBrace)?;
+            let right_opt = map.token_range(node, Token::RightBrace);
+            let end = right_opt.or_else(|| map.node_range(node))?.end();
+
+            if left.end() <= position && position <= end {
+                Some(node)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    })?;
+
+    let (path, arm_range_opt) = match node
+        .filter_node(|node| node.node() == Node::MatchArm)
+        .into_iter()
+        .find_map(|arm| {
+            let range = map.node_range(&arm)?;
+            if range.contains(position) {
+                Some((arm, range))
+            } else {
+                None
+            }
+        }) {
+        Some((arm, range)) => (arm_path(&arm, position, map)?, Some(range)),
+        None => (vec![], None),
+    };
+
+    let match_range = map.token_range(node, Token::Match)?;
+    let lowered = model
+        .match_expressions
+        .iter()
+        .find(|lowered| lowered.range == match_range)?;
+
+    // カーソルの位置にくる値の型を求める。
+    let mut ty = lowered.expression.condition_ty.clone();
+    for step in &path {
+        let constructor_definition = td.find_constructor_definition(&step.name)?;
+        ty = constructor_definition.arg_tys.get(step.index)?.clone();
+    }
+
+    let enum_name = match ty {
+        Ty::Enum { name } => name,
+        Ty::Constructor { .. } => return None,
+    };
+    let constructor_definitions = td.find_enum_definition(&enum_name)?;
+
+    // 書きかけのアームを除いて、どのアームにもマッチしない値のスペースを求める。
+    let expression = MatchExpression {
+        condition_ty: lowered.expression.condition_ty.clone(),
+        arms: lowered
+            .expression
+            .arms
+            .iter()
+            .zip(&lowered.arm_ranges)
+            .filter(|&(_, range)| Some(*range) != arm_range_opt)
+            .map(|(arm, _)| MatchArm {
+                pattern: arm.pattern.clone(),
+            })
+            .collect(),
+    };
+    // 外側から順に、書かれているコンストラクタと兄弟の引数にマッチする部分に絞ってから、
+    // カーソルがある引数の部分を取り出す。
+    let mut cx = SpaceCx::new(td);
+    let mut leaked_space = use_cases::leaked_space(&expression, &mut cx);
+    for step in &path {
+        let arg_tys = td.find_constructor_definition(&step.name)?.arg_tys.clone();
+        let mut args = arg_tys
+            .iter()
+            .map(|arg_ty| space_from_ty(arg_ty, &mut cx))
+            .collect::<Vec<_>>();
+        for (i, sibling) in &step.siblings {
+            if let Some(arg_ty) = arg_tys.get(*i) {
+                args[*i] = pat_space(sibling, arg_ty, td, &mut cx);
+            }
+        }
+        let name = cx.intern_symbol(&step.name);
+        let prefix = cx.new_constructor(name, args);
+
+        leaked_space = space_intersection(leaked_space, prefix, &mut cx);
+        leaked_space = project(leaked_space, &step.name, step.index, &mut cx);
+    }
+
+    let mut missing_names = vec![];
+    constructor_names(leaked_space, &mut cx, &mut missing_names);
+
+    let items = constructor_definitions
+        .iter()
+        .enumerate()
+        .map(|(i, constructor_definition)| {
+            let name = &constructor_definition.name;
+            let group = if missing_names.contains(name) { 0 } else { 1 };
+
+            CompletionItem {
+                label: name.to_string(),
+                detail: enum_name.to_string(),
+                insert_text: snippet(name, constructor_definition.arg_tys.len()),
+                sort_text: format!("{}{:04}", group, i),
+            }
+        })
+        .collect();
+    Some(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    const PRELUDE: &str = r#"enum Boolean {
+    True,
+    False,
+}
+
+enum Digit {
+    One,
+    Two(Boolean),
+}
+
+enum Binary {
+    Zero,
+    NonZero(Boolean, Binary),
+}
+"#;
+
+    /// `$0` の位置で補完して、候補のラベルを並び順に列挙する。
+    fn complete(source_code: &str) -> Vec<String> {
+        let source_code = format!("{}{}", PRELUDE, source_code);
+        let offset = source_code.find("$0").unwrap();
+
+        let line = source_code[..offset].matches('\n').count() + 1;
+        let line_start = source_code[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let position = TextPosition::new(line, offset - line_start + 1);
+
+        let source_code = source_code.replace("$0", "");
+        let analysis = Analysis::new(Rc::new(source_code));
+
+        let mut items = completion(&analysis, position);
+        items.sort_by(|l, r| l.sort_text.cmp(&r.sort_text));
+        items.into_iter().map(|item| item.label).collect()
+    }
+
+    #[test]
+    fn test_arm_position_puts_missing_constructors_first() {
+        let labels = complete("match True {\n    True => {}\n    $0\n}\n");
+        assert_eq!(labels, vec!["False", "True"]);
+    }
+
+    #[test]
+    fn test_argument_position_uses_argument_type() {
+        let labels = complete("match One {\n    Two(True) => {}\n    Two($0)\n}\n");
+        assert_eq!(labels, vec!["False", "True"]);
+    }
+
+    #[test]
+    fn test_nested_argument_position() {
+        let labels = complete(
+            "match Zero {\n    Zero => {}\n    NonZero(True, _) => {}\n    NonZero(False, NonZero(_, _)) => {}\n    NonZero(False, $0)\n}\n",
+        );
+        assert_eq!(labels, vec!["Zero", "NonZero"]);
+    }
+
+    #[test]
+    fn test_sibling_arguments_narrow_missing_constructors() {
+        // Zero が漏れているのは NonZero(True, _) の下だけなので、NonZero(False, |) では NonZero を先に並べる。
+        let labels = complete(
+            "match Zero {\n    NonZero(False, Zero) => {}\n    NonZero(True, NonZero(_, _)) => {}\n    Zero => {}\n    NonZero(False, $0)\n}\n",
+        );
+        assert_eq!(labels, vec!["NonZero", "Zero"]);
+
+        let labels = complete(
+            "match Zero {\n    NonZero(False, Zero) => {}\n    NonZero(True, NonZero(_, _)) => {}\n    Zero => {}\n    NonZero(True, $0)\n}\n",
+        );
+        assert_eq!(labels, vec!["Zero", "NonZero"]);
+    }
+
+    #[test]
+    fn test_no_completion_in_arm_body() {
+        let labels = complete("match True {\n    True => {$0}\n}\n");
+        assert!(labels.is_empty());
+    }
+
+    #[test]
+    fn test_snippet_has_placeholders() {
+        assert_eq!(snippet("One", 0), "One");
+        assert_eq!(snippet("NonZero", 2), "NonZero(${1:_}, ${2:_})");
+    }
+}
@@ -0,0 +1,171 @@
+//! 定義への移動、参照の検索、ハイライト
+
+use super::symbols::SymbolIndex;
+use crate::syntax::{TextPosition, TextRange};
+
+/// ハイライトの種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum HighlightKind {
+    /// 名前を使っている箇所
+    Read,
+
+    /// 名前を宣言している箇所
+    Write,
+}
+
+/// 指定した位置にある名前の宣言を探す。
+pub(crate) fn definition(index: &SymbolIndex, position: TextPosition) -> Option<TextRange> {
+    let symbol_id = index.occurrence_at(position)?.symbol_opt?;
+    Some(index.symbol(symbol_id).def_range)
+}
+
+/// 指定した位置にある名前の出現箇所を列挙する。
+pub(crate) fn references(
+    index: &SymbolIndex,
+    position: TextPosition,
+    include_declaration: bool,
+) -> Vec<TextRange> {
+    let symbol_id = match index.occurrence_at(position).and_then(|o| o.symbol_opt) {
+        Some(symbol_id) => symbol_id,
+        None => return vec![],
+    };
+
+    index
+        .occurrences_of(symbol_id)
+        .filter(|occurrence| include_declaration || !occurrence.is_def)
+        .map(|occurrence| occurrence.range)
+        .collect()
+}
+
+/// 指定した位置にある名前と同じものを指している箇所を列挙する。
+pub(crate) fn document_highlights(
+    index: &SymbolIndex,
+    position: TextPosition,
+) -> Vec<(TextRange, HighlightKind)> {
+    let symbol_id = match index.occurrence_at(position).and_then(|o| o.symbol_opt) {
+        Some(symbol_id) => symbol_id,
+        None => return vec![],
+    };
+
+    index
+        .occurrences_of(symbol_id)
+        .map(|occurrence| {
+            let kind = if occurrence.is_def {
+                HighlightKind::Write
+            } else {
+                HighlightKind::Read
+            };
+            (occurrence.range, kind)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ide::Analysis;
+    use std::rc::Rc;
+
+    const SOURCE_CODE: &str = r#"enum Boolean {
+    True,
+    False,
+}
+
+enum Digit {
+    One,
+    Two(Boolean),
+}
+
+match Two(True) {
+    One => {}
+    Two(True) => {}
+    Two(False) => {}
+}
+"#;
+
+    /// `n` 番目 (0 から数える) に出現する `text` の先頭の位置を求める。
+    fn position_of(text: &str, n: usize) -> TextPosition {
+        let (line_index, column) = SOURCE_CODE
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| line.match_indices(text).map(move |(j, _)| (i, j)))
+            .nth(n)
+            .unwrap();
+        TextPosition::new(line_index + 1, column + 1)
+    }
+
+    fn analyze() -> Analysis {
+        Analysis::new(Rc::new(SOURCE_CODE.to_string()))
+    }
+
+    #[test]
+    fn test_definition_of_ctor_in_pattern() {
+        let analysis = analyze();
+
+        let range = definition(&analysis.symbol_index, position_of("True", 2)).unwrap();
+        assert_eq!(range.start(), position_of("True", 0));
+    }
+
+    #[test]
+    fn test_definition_of_enum_in_tuple_field() {
+        let analysis = analyze();
+
+        let range = definition(&analysis.symbol_index, position_of("Boolean", 1)).unwrap();
+        assert_eq!(range.start(), position_of("Boolean", 0));
+    }
+
+    #[test]
+    fn test_references_of_ctor() {
+        let analysis = analyze();
+        let position = position_of("Two", 0);
+
+        let ranges = references(&analysis.symbol_index, position, true);
+        assert_eq!(ranges.len(), 4);
+
+        let ranges = references(&analysis.symbol_index, position, false);
+        assert_eq!(ranges.len(), 3);
+        assert!(ranges.iter().all(|range| range.start() != position));
+    }
+
+    #[test]
+    fn test_document_highlights() {
+        let analysis = analyze();
+
+        let highlights = document_highlights(&analysis.symbol_index, position_of("True", 1));
+        let kinds = highlights.iter().map(|&(_, kind)| kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                HighlightKind::Write,
+                HighlightKind::Read,
+                HighlightKind::Read
+            ]
+        );
+    }
+
+    #[test]
+    fn test_use_before_declaration_is_not_resolved() {
+        // 型検査と同様に、宣言より前にある名前は定義されていないものとして扱う。
+        let source_code = "match Unit {}\n\nenum Unit {\n    Unit,\n}\n\nmatch Unit {}\n";
+        let analysis = Analysis::new(Rc::new(source_code.to_string()));
+
+        let early_use = TextPosition::new(1, 7);
+        assert!(definition(&analysis.symbol_index, early_use).is_none());
+
+        let late_use = TextPosition::new(7, 7);
+        let range = definition(&analysis.symbol_index, late_use).unwrap();
+        assert_eq!(range.start(), TextPosition::new(4, 5));
+
+        let ranges = references(&analysis.symbol_index, late_use, true);
+        assert_eq!(ranges.len(), 2);
+        assert!(ranges.iter().all(|range| range.start().line() != 1));
+
+        let diagnostics = crate::diagnostic::collect_diagnostics(Rc::new(source_code.to_string()));
+        let undefined = diagnostics
+            .iter()
+            .filter(|d| d.code == crate::diagnostic::DiagnosticCode::UndefinedName)
+            .map(|d| d.range.start())
+            .collect::<Vec<_>>();
+        assert_eq!(undefined, vec![early_use]);
+    }
+}
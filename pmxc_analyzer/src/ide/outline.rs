@@ -0,0 +1,219 @@
+//! アウトラインと折りたたみ
+//!
+//! 具象構文木の enum 宣言と match 文から、ドキュメントシンボルと折りたたみ範囲を作る。
+
+use super::Analysis;
+use crate::syntax::{Element, Node, NodeData, TextRange, Token, TokenRangeMap};
+
+/// ドキュメントシンボルの種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DocumentSymbolKind {
+    Enum,
+    Ctor,
+    Match,
+}
+
+/// アウトラインに表示する要素
+#[derive(Clone, Debug)]
+pub(crate) struct DocumentSymbol {
+    pub(crate) name: String,
+    pub(crate) kind: DocumentSymbolKind,
+
+    /// 宣言や文の全体の範囲
+    pub(crate) range: TextRange,
+
+    /// 名前など、シンボルを選択したときに強調する範囲
+    pub(crate) selection_range: TextRange,
+
+    pub(crate) children: Vec<DocumentSymbol>,
+}
+
+/// 折りたたみ範囲。(行番号は 1 から始まる。)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct FoldingRange {
+    pub(crate) start_line: usize,
+    pub(crate) end_line: usize,
+}
+
+/// 式のテキストを、トリビアを除いて組み立てる。例: `NonZero(True, Zero)`
+fn expr_text(node: &NodeData, text: &mut String) {
+    for element in node.children() {
+        match element {
+            Element::Token(token) => {
+                text.push_str(token.text());
+                if token.token() == Token::Comma {
+                    text.push(' ');
+                }
+            }
+            Element::Error(_) => {}
+            Element::Node(node) => expr_text(node, text),
+        }
+    }
+}
+
+fn ctor_symbol(node: &NodeData, map: &TokenRangeMap) -> Option<DocumentSymbol> {
+    let name = node.first_ident()?;
+
+    Some(DocumentSymbol {
+        name,
+        kind: DocumentSymbolKind::Ctor,
+        range: map.node_range(node)?,
+        selection_range: map.token_range(node, Token::Ident)?,
+        children: vec![],
+    })
+}
+
+fn enum_symbol(node: &NodeData, map: &TokenRangeMap) -> Option<DocumentSymbol> {
+    let name = node.first_ident()?;
+
+    let children = node
+        .filter_node(|child| child.node() == Node::CtorDecl)
+        .into_iter()
+        .filter_map(|child| ctor_symbol(&child, map))
+        .collect();
+
+    Some(DocumentSymbol {
+        name,
+        kind: DocumentSymbolKind::Enum,
+        range: map.node_range(node)?,
+        selection_range: map.token_range(node, Token::Ident)?,
+        children,
+    })
+}
+
+fn match_symbol(node: &NodeData, map: &TokenRangeMap) -> Option<DocumentSymbol> {
+    let mut name = "match".to_string();
+    if let Some(cond) = node.first_node(|child| child.node().is_expr()) {
+        name.push(' ');
+        expr_text(&cond, &mut name);
+    }
+
+    Some(DocumentSymbol {
+        name,
+        kind: DocumentSymbolKind::Match,
+        range: map.node_range(node)?,
+        selection_range: map.token_range(node, Token::Match)?,
+        children: vec![],
+    })
+}
+
+/// ドキュメントシンボルの木を作る。enum はコンストラクタを子要素に持つ。
+pub(crate) fn document_symbols(analysis: &Analysis) -> Vec<DocumentSymbol> {
+    let map = analysis.model.token_range_map();
+
+    analysis
+        .ast
+        .node
+        .filter_node(|child| child.node().is_stmt())
+        .into_iter()
+        .filter_map(|child| match child.node() {
+            Node::EnumDecl => enum_symbol(&child, map),
+            Node::MatchStmt => match_symbol(&child, map),
+            _ => None,
+        })
+        .collect()
+}
+
+/// enum 宣言と match 文の中括弧の中を折りたたみ範囲とする。
+///
+/// 閉じ中括弧の行は折りたたまずに残す。
+pub(crate) fn folding_ranges(analysis: &Analysis) -> Vec<FoldingRange> {
+    let map = analysis.model.token_range_map();
+
+    analysis
+        .ast
+        .node
+        .filter_node(|child| child.node() == Node::EnumDecl || child.node() == Node::MatchStmt)
+        .into_iter()
+        .filter_map(|child| {
+            let left = map.token_range(&child, Token::LeftBrace)?;
+            let right = map.token_range(&child, Token::RightBrace)?;
+
+            let start_line = left.start().line();
+            let end_line = right.start().line() - 1;
+            if start_line < end_line {
+                Some(FoldingRange {
+                    start_line,
+                    end_line,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    const SOURCE_CODE: &str = r#"enum Boolean {
+    True,
+    False,
+}
+
+enum Binary {
+    Zero,
+    NonZero(Boolean, Binary),
+}
+
+match NonZero(True, Zero) {
+    _ => {}
+}
+
+match True {}
+"#;
+
+    fn analyze() -> Analysis {
+        Analysis::new(Rc::new(SOURCE_CODE.to_string()))
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        let analysis = analyze();
+        let symbols = document_symbols(&analysis);
+
+        let names = symbols
+            .iter()
+            .map(|symbol| symbol.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "Boolean",
+                "Binary",
+                "match NonZero(True, Zero)",
+                "match True"
+            ]
+        );
+
+        let children = symbols[1]
+            .children
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            children,
+            vec![
+                ("Zero", DocumentSymbolKind::Ctor),
+                ("NonZero", DocumentSymbolKind::Ctor)
+            ]
+        );
+
+        assert_eq!(symbols[1].range.start().line(), 6);
+        assert_eq!(symbols[1].range.end().line(), 9);
+        assert_eq!(symbols[2].selection_range.start().line(), 11);
+    }
+
+    #[test]
+    fn test_folding_ranges() {
+        let analysis = analyze();
+
+        let ranges = folding_ranges(&analysis)
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(1, 3), (6, 8), (11, 12)]);
+    }
+}
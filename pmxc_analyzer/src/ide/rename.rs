@@ -0,0 +1,176 @@
+//! 名前の変更
+
+use super::symbols::{SymbolIndex, SymbolKind};
+use crate::messages::Message;
+use crate::syntax::{tokenize_rules, TextPosition, TextRange};
+
+/// テキストの置き換え
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TextEdit {
+    pub(crate) range: TextRange,
+    pub(crate) new_text: String,
+}
+
+/// 名前を変更できない理由
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum RenameError {
+    /// 指定した位置に、定義された enum やコンストラクタの名前がない。
+    NoSymbol,
+
+    /// 新しい名前が識別子として正しくない。
+    InvalidName,
+
+    /// 新しい名前が既存の名前と衝突する。
+    Conflict(SymbolKind),
+}
+
+impl RenameError {
+    pub(crate) fn message(&self) -> Message {
+        match self {
+            RenameError::NoSymbol => Message::RenameNoSymbol,
+            RenameError::InvalidName => Message::RenameInvalidName,
+            RenameError::Conflict(SymbolKind::Enum) => Message::RenameEnumConflict,
+            RenameError::Conflict(SymbolKind::Ctor) => Message::RenameCtorConflict,
+        }
+    }
+}
+
+/// 指定した位置にある名前が変更できるか調べて、その範囲と現在の名前を返す。
+pub(crate) fn prepare_rename(
+    index: &SymbolIndex,
+    position: TextPosition,
+) -> Result<(TextRange, String), RenameError> {
+    match index.occurrence_at(position) {
+        Some(occurrence) if occurrence.symbol_opt.is_some() => {
+            Ok((occurrence.range, occurrence.name.to_string()))
+        }
+        _ => Err(RenameError::NoSymbol),
+    }
+}
+
+/// 指定した位置にある enum やコンストラクタの名前を変更するための置き換えを列挙する。
+pub(crate) fn rename(
+    index: &SymbolIndex,
+    position: TextPosition,
+    new_name: &str,
+) -> Result<Vec<TextEdit>, RenameError> {
+    let symbol_id = index
+        .occurrence_at(position)
+        .and_then(|occurrence| occurrence.symbol_opt)
+        .ok_or(RenameError::NoSymbol)?;
+    let symbol = index.symbol(symbol_id);
+
+    if !tokenize_rules::text_is_ident(new_name) {
+        return Err(RenameError::InvalidName);
+    }
+
+    // コンストラクタは名前だけで (enum を問わず) 解決されるので、同じ名前があると区別できなくなる。
+    // 定義されていない名前の参照も、変更後の名前に解決されるようになってしまうので拒否する。
+    let conflicts = index.occurrences().iter().any(|occurrence| {
+        occurrence.kind == symbol.kind
+            && occurrence.name == new_name
+            && occurrence.symbol_opt != Some(symbol_id)
+    });
+    if conflicts {
+        return Err(RenameError::Conflict(symbol.kind));
+    }
+
+    Ok(index
+        .occurrences_of(symbol_id)
+        .map(|occurrence| TextEdit {
+            range: occurrence.range,
+            new_text: new_name.to_string(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ide::Analysis;
+    use crate::messages::Locale;
+    use std::rc::Rc;
+
+    const SOURCE_CODE: &str = r#"enum Boolean {
+    True,
+    False,
+}
+
+enum Digit {
+    One,
+    Two(Boolean),
+}
+
+match Two(True) {
+    One => {}
+    Two(True) => {}
+    Two(False) => {}
+}
+"#;
+
+    fn analyze() -> Analysis {
+        Analysis::new(Rc::new(SOURCE_CODE.to_string()))
+    }
+
+    #[test]
+    fn test_rename_ctor() {
+        let analysis = analyze();
+
+        // `True,` の上
+        let edits = rename(&analysis.symbol_index, TextPosition::new(2, 5), "Yes").unwrap();
+        let lines = edits
+            .iter()
+            .map(|edit| edit.range.start().line())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![2, 11, 13]);
+        assert!(edits.iter().all(|edit| edit.new_text == "Yes"));
+    }
+
+    #[test]
+    fn test_rename_enum() {
+        let analysis = analyze();
+
+        // `Two(Boolean)` の Boolean の上
+        let edits = rename(&analysis.symbol_index, TextPosition::new(8, 9), "Bool").unwrap();
+        assert_eq!(edits.len(), 2);
+    }
+
+    #[test]
+    fn test_rename_rejects_conflict() {
+        let analysis = analyze();
+
+        let result = rename(&analysis.symbol_index, TextPosition::new(2, 5), "One");
+        assert_eq!(result, Err(RenameError::Conflict(SymbolKind::Ctor)));
+
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.message().render(Locale::En),
+            "a constructor with the same name is already defined"
+        );
+    }
+
+    #[test]
+    fn test_rename_rejects_invalid_name() {
+        let analysis = analyze();
+
+        for new_name in &["", "1st", "Two-Three", "match", "_"] {
+            let result = rename(&analysis.symbol_index, TextPosition::new(2, 5), new_name);
+            assert_eq!(result, Err(RenameError::InvalidName));
+        }
+    }
+
+    #[test]
+    fn test_rename_rejects_non_symbol() {
+        let analysis = analyze();
+
+        // `enum` キーワードの上
+        let position = TextPosition::new(1, 2);
+        assert_eq!(
+            prepare_rename(&analysis.symbol_index, position),
+            Err(RenameError::NoSymbol)
+        );
+
+        let result = rename(&analysis.symbol_index, position, "Foo");
+        assert_eq!(result, Err(RenameError::NoSymbol));
+    }
+}
@@ -0,0 +1,192 @@
+//! 意味に基づく字句の分類 (セマンティックトークン)
+//!
+//! 字句解析だけでは識別子が何を指しているか分からないので、シンボル解決の結果をもとに分類する。
+
+use super::symbols::{SymbolId, SymbolIndex, SymbolKind};
+use crate::syntax::TextRange;
+
+/// トークンの種類。LSP の legend と同じ順番に並べる。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SemanticTokenType {
+    /// enum 型の名前
+    Enum = 0,
+
+    /// コンストラクタの名前 (宣言、パターン、式のいずれか)
+    EnumMember = 1,
+
+    /// 定義されていない名前
+    Undefined = 2,
+}
+
+// 凡例は wasm の semantic_tokens_legend が返す。
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+pub(crate) static TOKEN_TYPES: &[&str] = &["enum", "enumMember", "undefined"];
+
+/// 宣言の名前であることを表す修飾子
+pub(crate) const MODIFIER_DECLARATION: u32 = 1 << 0;
+
+/// どこからも参照されていない宣言であることを表す修飾子
+pub(crate) const MODIFIER_UNUSED: u32 = 1 << 1;
+
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+pub(crate) static TOKEN_MODIFIERS: &[&str] = &["declaration", "unused"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SemanticToken {
+    pub(crate) range: TextRange,
+    pub(crate) token_type: SemanticTokenType,
+
+    /// 修飾子のビットの和
+    pub(crate) modifiers: u32,
+}
+
+fn is_used(index: &SymbolIndex, symbol_id: SymbolId) -> bool {
+    if index.occurrences_of(symbol_id).any(|o| !o.is_def) {
+        return true;
+    }
+
+    // enum は型の名前が参照されていなくても、コンストラクタが使われていれば使われているとみなす。
+    index.symbols().iter().enumerate().any(|(id, symbol)| {
+        symbol.parent_opt == Some(symbol_id) && index.occurrences_of(id).any(|o| !o.is_def)
+    })
+}
+
+/// 識別子を分類する。結果はソースコード上の順番に並ぶ。
+pub(crate) fn semantic_tokens(index: &SymbolIndex) -> Vec<SemanticToken> {
+    index
+        .occurrences()
+        .iter()
+        .map(|occurrence| {
+            let symbol_id = match occurrence.symbol_opt {
+                Some(symbol_id) => symbol_id,
+                None => {
+                    return SemanticToken {
+                        range: occurrence.range,
+                        token_type: SemanticTokenType::Undefined,
+                        modifiers: 0,
+                    };
+                }
+            };
+
+            let token_type = match occurrence.kind {
+                SymbolKind::Enum => SemanticTokenType::Enum,
+                SymbolKind::Ctor => SemanticTokenType::EnumMember,
+            };
+
+            let mut modifiers = 0;
+            if occurrence.is_def {
+                modifiers |= MODIFIER_DECLARATION;
+
+                if !is_used(index, symbol_id) {
+                    modifiers |= MODIFIER_UNUSED;
+                }
+            }
+
+            SemanticToken {
+                range: occurrence.range,
+                token_type,
+                modifiers,
+            }
+        })
+        .collect()
+}
+
+/// LSP の形式 (直前のトークンからの相対位置を 5 つ組で並べたもの) に変換する。
+///
+/// 行と列は 0 から数える。識別子は複数行にまたがらないものとする。
+pub(crate) fn encode(tokens: &[SemanticToken]) -> Vec<u32> {
+    let mut data = Vec::with_capacity(tokens.len() * 5);
+    let (mut last_line, mut last_character) = (0, 0);
+
+    for token in tokens {
+        let start = token.range.start();
+        let line = (start.line() - 1) as u32;
+        let character = (start.character() - 1) as u32;
+        let length = (token.range.end().character() - start.character()) as u32;
+
+        let delta_line = line - last_line;
+        let delta_character = if delta_line == 0 {
+            character - last_character
+        } else {
+            character
+        };
+
+        data.extend(&[
+            delta_line,
+            delta_character,
+            length,
+            token.token_type as u32,
+            token.modifiers,
+        ]);
+
+        last_line = line;
+        last_character = character;
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ide::Analysis;
+    use std::rc::Rc;
+
+    const SOURCE_CODE: &str = r#"enum Boolean {
+    True,
+    False,
+}
+
+enum Unused {
+    U(Boolean),
+}
+
+match True {
+    Maybe => {}
+}
+"#;
+
+    #[test]
+    fn test_semantic_tokens() {
+        let analysis = Analysis::new(Rc::new(SOURCE_CODE.to_string()));
+
+        let tokens = semantic_tokens(&analysis.symbol_index)
+            .into_iter()
+            .map(|token| {
+                (
+                    token.range.start().line(),
+                    token.token_type,
+                    token.modifiers,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let declaration = MODIFIER_DECLARATION;
+        let unused = MODIFIER_DECLARATION | MODIFIER_UNUSED;
+        assert_eq!(
+            tokens,
+            vec![
+                (1, SemanticTokenType::Enum, declaration),
+                (2, SemanticTokenType::EnumMember, declaration),
+                (3, SemanticTokenType::EnumMember, unused),
+                (6, SemanticTokenType::Enum, unused),
+                (7, SemanticTokenType::EnumMember, unused),
+                (7, SemanticTokenType::Enum, 0),
+                (10, SemanticTokenType::EnumMember, 0),
+                (11, SemanticTokenType::Undefined, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let analysis = Analysis::new(Rc::new(SOURCE_CODE.to_string()));
+        let tokens = semantic_tokens(&analysis.symbol_index);
+        let data = encode(&tokens);
+
+        // enum Boolean
+        assert_eq!(&data[0..5], &[0, 5, 7, 0, MODIFIER_DECLARATION]);
+        // U(Boolean): 同じ行の U から 2 文字右
+        assert_eq!(&data[25..30], &[0, 2, 7, 0, 0]);
+    }
+}
@@ -0,0 +1,228 @@
+//! シンボル解決
+//!
+//! enum やコンストラクタの宣言と、それらの名前が使われている箇所を対応づける。
+//! コンストラクタの名前は型検査と同様にソースコード全体で一意とみなして解決する。
+
+use crate::syntax::{
+    CtorExpr, CtorPat, EnumDecl, Expr, MatchStmt, NodeData, Pat, Root, Stmt, TextPosition,
+    TextRange, TokenRangeMap,
+};
+
+pub(crate) type SymbolId = usize;
+
+/// シンボルの種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum SymbolKind {
+    Enum,
+    Ctor,
+}
+
+/// 宣言されたもの (enum またはコンストラクタ)
+#[derive(Debug)]
+pub(crate) struct Symbol {
+    pub(crate) kind: SymbolKind,
+    pub(crate) name: String,
+
+    /// 宣言における名前の範囲。
+    pub(crate) def_range: TextRange,
+
+    /// コンストラクタが属する enum。(enum なら None。)
+    pub(crate) parent_opt: Option<SymbolId>,
+}
+
+/// 名前の出現箇所
+#[derive(Clone, Debug)]
+pub(crate) struct Occurrence {
+    pub(crate) kind: SymbolKind,
+    pub(crate) name: String,
+    pub(crate) range: TextRange,
+
+    /// 名前が指しているシンボル。定義されていない名前なら None。
+    pub(crate) symbol_opt: Option<SymbolId>,
+
+    /// 宣言の名前か？
+    pub(crate) is_def: bool,
+}
+
+/// 宣言と参照の対応表
+#[derive(Debug, Default)]
+pub(crate) struct SymbolIndex {
+    symbols: Vec<Symbol>,
+    occurrences: Vec<Occurrence>,
+}
+
+struct Collector<'a> {
+    token_range_map: &'a TokenRangeMap,
+    index: SymbolIndex,
+}
+
+impl Collector<'_> {
+    fn add_def(
+        &mut self,
+        kind: SymbolKind,
+        name: &str,
+        node: &NodeData,
+        parent_opt: Option<SymbolId>,
+    ) -> Option<SymbolId> {
+        let range = self.token_range_map.name_range(node)?;
+
+        let symbol_id = self.index.symbols.len();
+        self.index.symbols.push(Symbol {
+            kind,
+            name: name.to_string(),
+            def_range: range,
+            parent_opt,
+        });
+        self.index.occurrences.push(Occurrence {
+            kind,
+            name: name.to_string(),
+            range,
+            symbol_opt: Some(symbol_id),
+            is_def: true,
+        });
+        Some(symbol_id)
+    }
+
+    fn add_use(&mut self, kind: SymbolKind, name: &str, node: &NodeData) {
+        let range = match self.token_range_map.name_range(node) {
+            Some(range) => range,
+            None => return,
+        };
+
+        // 型検査と同様に、ここまでに現れた宣言だけから参照先を探す。
+        // (同じ名前の宣言が複数あるときは最初のものを参照先とする。)
+        let symbol_opt = self.index.find_symbol(kind, name);
+
+        self.index.occurrences.push(Occurrence {
+            kind,
+            name: name.to_string(),
+            range,
+            symbol_opt,
+            is_def: false,
+        });
+    }
+
+    fn on_pat(&mut self, pat: &Pat) {
+        if let Pat::Ctor(CtorPat {
+            name_opt,
+            tuple_opt,
+            node,
+        }) = pat
+        {
+            if let Some(name) = name_opt {
+                self.add_use(SymbolKind::Ctor, name, node);
+            }
+
+            for field_pat in tuple_opt.iter().flatten() {
+                self.on_pat(field_pat);
+            }
+        }
+    }
+
+    fn on_expr(&mut self, expr: &Expr) {
+        let Expr::Ctor(CtorExpr {
+            name_opt,
+            tuple_opt,
+            node,
+        }) = expr;
+
+        if let Some(name) = name_opt {
+            self.add_use(SymbolKind::Ctor, name, node);
+        }
+
+        for field_expr in tuple_opt.iter().flatten() {
+            self.on_expr(field_expr);
+        }
+    }
+
+    fn on_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Match(MatchStmt { cond_opt, arms, .. }) => {
+                if let Some(cond) = cond_opt {
+                    self.on_expr(cond);
+                }
+
+                for pat in arms.iter().filter_map(|arm| arm.pat_opt.as_ref()) {
+                    self.on_pat(pat);
+                }
+            }
+            Stmt::Enum(EnumDecl {
+                name_opt,
+                ctors,
+                node,
+            }) => {
+                let enum_id_opt = name_opt
+                    .as_ref()
+                    .and_then(|name| self.add_def(SymbolKind::Enum, name, node, None));
+
+                for ctor in ctors {
+                    if let Some(name) = ctor.name_opt.as_ref() {
+                        self.add_def(SymbolKind::Ctor, name, &ctor.node, enum_id_opt);
+                    }
+
+                    let fields = ctor.tuple_decl_opt.iter().flat_map(|t| t.fields.iter());
+                    for ty in fields {
+                        if let Some(name) = ty.name_opt.as_ref() {
+                            self.add_use(SymbolKind::Enum, name, &ty.node);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl SymbolIndex {
+    pub(crate) fn new(root: &Root, token_range_map: &TokenRangeMap) -> Self {
+        let mut collector = Collector {
+            token_range_map,
+            index: SymbolIndex::default(),
+        };
+
+        for stmt in root.stmts.iter() {
+            collector.on_stmt(stmt);
+        }
+
+        let mut index = collector.index;
+        index
+            .occurrences
+            .sort_by_key(|occurrence| occurrence.range.start());
+        index
+    }
+
+    pub(crate) fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub(crate) fn symbol(&self, symbol_id: SymbolId) -> &Symbol {
+        &self.symbols[symbol_id]
+    }
+
+    /// 名前の出現箇所をすべて列挙する。(ソースコード上の順番に並ぶ。)
+    pub(crate) fn occurrences(&self) -> &[Occurrence] {
+        &self.occurrences
+    }
+
+    pub(crate) fn find_symbol(&self, kind: SymbolKind, name: &str) -> Option<SymbolId> {
+        self.symbols
+            .iter()
+            .position(|symbol| symbol.kind == kind && symbol.name == name)
+    }
+
+    /// 指定した位置にある名前を探す。
+    pub(crate) fn occurrence_at(&self, position: TextPosition) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .find(|occurrence| occurrence.range.contains(position))
+    }
+
+    /// シンボルの出現箇所 (宣言を含む) を列挙する。
+    pub(crate) fn occurrences_of(
+        &self,
+        symbol_id: SymbolId,
+    ) -> impl Iterator<Item = &Occurrence> + '_ {
+        self.occurrences
+            .iter()
+            .filter(move |occurrence| occurrence.symbol_opt == Some(symbol_id))
+    }
+}
@@ -0,0 +1,552 @@
+//! JSON で記述された網羅性検査の問題を解く機能
+//!
+//! ソースコードを持たないツールが、自前の型定義と match 式を JSON で渡して検査するためのもの。
+//!
+//! 入力の例:
+//!
+//! ```json
+//! {
+//!     "definitions": [
+//!         {
+//!             "kind": "enum",
+//!             "name": "Boolean",
+//!             "constructors": [{ "name": "False" }, { "name": "True" }]
+//!         }
+//!     ],
+//!     "expressions": [
+//!         {
+//!             "condition_ty": { "kind": "enum", "name": "Boolean" },
+//!             "arms": [{ "pattern": { "kind": "constructor", "name": "True" } }]
+//!         }
+//!     ],
+//!     "budget": { "max_steps": 100000 }
+//! }
+//! ```
+//!
+//! `budget` は計算量の上限 ([`Budget`]) で、省略したフィールドは既定値になる。
+//!
+//! 出力の例:
+//!
+//! ```json
+//! {
+//!     "results": [
+//!         {
+//!             "verdict": "non_exhaustive",
+//!             "is_exhaustive": false,
+//!             "witnesses": [{ "text": "False", "pattern": { "kind": "constructor", "name": "False", "args": [] } }],
+//!             "redundant_arms": []
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! 計算量の上限に達して判定できなかったときは、`"verdict": "undetermined"` となり、
+//! 使い切った上限の種類が `"budget_exceeded": "steps"` のように入る。
+//!
+//! 未定義の型やコンストラクタ、引数の個数や型が合わないパターンを含む依頼は検査せず、
+//! `{"error": "expressions[0].arms[1].pattern: ..."}` のように問題の場所を添えたエラーを返す。
+
+use crate::match_exhaustivity::{
+    check_match_with_budget, Budget, BudgetExceeded, MatchExpression, Pattern, Ty, TyDatabase,
+    TyDefinition, Verdict,
+};
+use serde::{Deserialize, Serialize};
+
+/// 検査の依頼
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CheckRequest {
+    #[serde(default)]
+    pub definitions: Vec<TyDefinition>,
+
+    #[serde(default)]
+    pub expressions: Vec<MatchExpression>,
+
+    #[serde(default)]
+    pub budget: Budget,
+}
+
+/// 検査の結果。(results は依頼の expressions と同じ順番に並ぶ。)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CheckResponse {
+    pub results: Vec<MatchReport>,
+}
+
+/// 1つの match 式に関する検査の結果
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MatchReport {
+    pub verdict: VerdictKind,
+
+    /// 使い切った計算量の種類 (判定できなかったときだけ)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_exceeded: Option<BudgetExceeded>,
+
+    pub is_exhaustive: bool,
+    pub witnesses: Vec<Witness>,
+    pub redundant_arms: Vec<usize>,
+}
+
+/// 網羅性の判定 ([`Verdict`] から上限の種類を除いたもの)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerdictKind {
+    Exhaustive,
+    NonExhaustive,
+    Undetermined,
+}
+
+impl From<Verdict> for VerdictKind {
+    fn from(verdict: Verdict) -> Self {
+        match verdict {
+            Verdict::Exhaustive => VerdictKind::Exhaustive,
+            Verdict::NonExhaustive => VerdictKind::NonExhaustive,
+            Verdict::Undetermined(_) => VerdictKind::Undetermined,
+        }
+    }
+}
+
+/// どのアームにもマッチしない値の例
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Witness {
+    /// パターンの表記 (例: `Some(False)`)
+    pub text: String,
+
+    pub pattern: Pattern,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// 型が定義されているか検査する。
+fn validate_ty(ty: &Ty, td: &TyDatabase) -> Result<(), String> {
+    match ty {
+        Ty::Enum { name } if td.find_enum_definition(name).is_none() => {
+            Err(format!("enum `{}` is not defined", name))
+        }
+        Ty::Constructor { name } if td.find_constructor_definition(name).is_none() => {
+            Err(format!("constructor `{}` is not defined", name))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 型 ty の値が型 expected の値でもあるか？
+fn is_subtype(ty: &Ty, expected: &Ty, td: &TyDatabase) -> bool {
+    match (ty, expected) {
+        (Ty::Constructor { name }, Ty::Enum { name: enum_name }) => td
+            .find_constructor_by_name(name)
+            .is_some_and(|(name, _)| name == enum_name),
+        _ => ty == expected,
+    }
+}
+
+/// パターンが型 expected の値にマッチしうる形か検査する。
+fn validate_pattern(
+    pattern: &Pattern,
+    expected: &Ty,
+    td: &TyDatabase,
+    path: &str,
+) -> Result<(), String> {
+    match pattern {
+        Pattern::Discard { ty } => {
+            validate_ty(ty, td).map_err(|error| format!("{}.ty: {}", path, error))?;
+            if !is_subtype(ty, expected, td) {
+                return Err(format!(
+                    "{}: expected a pattern of type `{}`, found `_: {}`",
+                    path, expected, ty
+                ));
+            }
+            Ok(())
+        }
+        Pattern::Constructor { name, args } => {
+            let constructor = match td.find_constructor_definition(name) {
+                Some(constructor) => constructor,
+                None => return Err(format!("{}: constructor `{}` is not defined", path, name)),
+            };
+
+            let ty = Ty::Constructor {
+                name: name.to_string(),
+            };
+            if !is_subtype(&ty, expected, td) {
+                return Err(format!(
+                    "{}: expected a pattern of type `{}`, found constructor `{}`",
+                    path, expected, name
+                ));
+            }
+
+            if args.len() != constructor.arg_tys.len() {
+                return Err(format!(
+                    "{}: constructor `{}` takes {} argument(s) but {} were given",
+                    path,
+                    name,
+                    constructor.arg_tys.len(),
+                    args.len()
+                ));
+            }
+
+            for (i, (arg, arg_ty)) in args.iter().zip(constructor.arg_tys.iter()).enumerate() {
+                validate_pattern(arg, arg_ty, td, &format!("{}.args[{}]", path, i))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// 依頼に含まれる型とパターンが型定義と矛盾しないか検査する。
+///
+/// 検査器は型の付いたパターンを前提にしているので、矛盾する依頼をそのまま渡してはいけない。
+/// エラーには問題の場所 (例: `expressions[0].arms[1].pattern`) を付ける。
+fn validate_request(request: &CheckRequest, td: &TyDatabase) -> Result<(), String> {
+    for (i, definition) in request.definitions.iter().enumerate() {
+        let TyDefinition::Enum { constructors, .. } = definition;
+
+        for (j, constructor) in constructors.iter().enumerate() {
+            for (k, arg_ty) in constructor.arg_tys.iter().enumerate() {
+                validate_ty(arg_ty, td).map_err(|error| {
+                    format!(
+                        "definitions[{}].constructors[{}].arg_tys[{}]: {}",
+                        i, j, k, error
+                    )
+                })?;
+            }
+        }
+    }
+
+    for (i, expression) in request.expressions.iter().enumerate() {
+        validate_ty(&expression.condition_ty, td)
+            .map_err(|error| format!("expressions[{}].condition_ty: {}", i, error))?;
+
+        for (j, arm) in expression.arms.iter().enumerate() {
+            let path = format!("expressions[{}].arms[{}].pattern", i, j);
+            validate_pattern(&arm.pattern, &expression.condition_ty, td, &path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 依頼を検査する。
+///
+/// 依頼が型定義と矛盾するときは、問題の場所と内容を表すエラーメッセージを返す。
+pub fn check_request(request: &CheckRequest) -> Result<CheckResponse, String> {
+    let mut td = TyDatabase::default();
+    for definition in request.definitions.iter().cloned() {
+        td.add_definition(definition);
+    }
+    validate_request(request, &td)?;
+
+    let results = request
+        .expressions
+        .iter()
+        .map(|expression| {
+            let result = check_match_with_budget(expression, &td, request.budget);
+            let budget_exceeded = match result.verdict {
+                Verdict::Undetermined(exceeded) => Some(exceeded),
+                _ => None,
+            };
+
+            MatchReport {
+                verdict: VerdictKind::from(result.verdict),
+                budget_exceeded,
+                is_exhaustive: result.is_exhaustive,
+                witnesses: result
+                    .witnesses
+                    .into_iter()
+                    .map(|pattern| Witness {
+                        text: pattern.to_string(),
+                        pattern,
+                    })
+                    .collect(),
+                redundant_arms: result.redundant_arms,
+            }
+        })
+        .collect();
+
+    Ok(CheckResponse { results })
+}
+
+/// JSON で書かれた依頼 ([`CheckRequest`]) を検査して、結果 ([`CheckResponse`]) を JSON で返す。
+///
+/// 入力が不正なときは `{"error": "..."}` を返す。
+pub fn check_json(input: &str) -> Result<String, String> {
+    let to_error = |error: String| serde_json::to_string(&ErrorResponse { error }).unwrap();
+
+    let request =
+        serde_json::from_str::<CheckRequest>(input).map_err(|err| to_error(err.to_string()))?;
+
+    let response = check_request(&request).map_err(to_error)?;
+    Ok(serde_json::to_string(&response).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_check_json() {
+        let input = json!({
+            "definitions": [
+                {
+                    "kind": "enum",
+                    "name": "Boolean",
+                    "constructors": [{ "name": "False" }, { "name": "True" }],
+                },
+                {
+                    "kind": "enum",
+                    "name": "Option",
+                    "constructors": [
+                        { "name": "None" },
+                        { "name": "Some", "arg_tys": [{ "kind": "enum", "name": "Boolean" }] },
+                    ],
+                },
+            ],
+            "expressions": [
+                {
+                    "condition_ty": { "kind": "enum", "name": "Option" },
+                    "arms": [
+                        {
+                            "pattern": {
+                                "kind": "constructor",
+                                "name": "Some",
+                                "args": [{ "kind": "constructor", "name": "True" }],
+                            },
+                        },
+                    ],
+                },
+                {
+                    "condition_ty": { "kind": "enum", "name": "Boolean" },
+                    "arms": [
+                        { "pattern": { "kind": "discard", "ty": { "kind": "enum", "name": "Boolean" } } },
+                        { "pattern": { "kind": "constructor", "name": "True" } },
+                    ],
+                },
+            ],
+        });
+
+        let output = check_json(&input.to_string()).unwrap();
+        let output = serde_json::from_str::<Value>(&output).unwrap();
+
+        let first = &output["results"][0];
+        assert_eq!(first["verdict"], json!("non_exhaustive"));
+        assert_eq!(first["is_exhaustive"], json!(false));
+        assert!(first.get("budget_exceeded").is_none());
+        assert_eq!(
+            first["witnesses"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|witness| witness["text"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["None", "Some(False)"]
+        );
+
+        let second = &output["results"][1];
+        assert_eq!(second["is_exhaustive"], json!(true));
+        assert_eq!(second["witnesses"], json!([]));
+        assert_eq!(second["redundant_arms"], json!([1]));
+    }
+
+    #[test]
+    fn test_check_json_budget() {
+        let input = json!({
+            "definitions": [
+                {
+                    "kind": "enum",
+                    "name": "Boolean",
+                    "constructors": [{ "name": "False" }, { "name": "True" }],
+                },
+            ],
+            "expressions": [
+                {
+                    "condition_ty": { "kind": "enum", "name": "Boolean" },
+                    "arms": [{ "pattern": { "kind": "constructor", "name": "True" } }],
+                },
+            ],
+            "budget": { "max_steps": 1 },
+        });
+
+        let output = check_json(&input.to_string()).unwrap();
+        let output = serde_json::from_str::<Value>(&output).unwrap();
+
+        let first = &output["results"][0];
+        assert_eq!(first["verdict"], json!("undetermined"));
+        assert_eq!(first["budget_exceeded"], json!("steps"));
+        assert_eq!(first["is_exhaustive"], json!(false));
+        assert_eq!(first["witnesses"], json!([]));
+    }
+
+    #[test]
+    fn test_check_json_invalid_input() {
+        let error = check_json(r#"{"definitions": 1}"#).unwrap_err();
+        let error = serde_json::from_str::<Value>(&error).unwrap();
+        assert!(error["error"].is_string());
+    }
+
+    /// Boolean と Option を定義して、与えた match 式を検査する。
+    fn check_expression(expression: Value) -> Value {
+        let input = json!({
+            "definitions": [
+                {
+                    "kind": "enum",
+                    "name": "Boolean",
+                    "constructors": [{ "name": "False" }, { "name": "True" }],
+                },
+                {
+                    "kind": "enum",
+                    "name": "Option",
+                    "constructors": [
+                        { "name": "None" },
+                        { "name": "Some", "arg_tys": [{ "kind": "enum", "name": "Boolean" }] },
+                    ],
+                },
+            ],
+            "expressions": [expression],
+        });
+
+        let output = match check_json(&input.to_string()) {
+            Ok(output) | Err(output) => output,
+        };
+        serde_json::from_str::<Value>(&output).unwrap()
+    }
+
+    fn error_of(output: &Value) -> &str {
+        output["error"]
+            .as_str()
+            .unwrap_or_else(|| panic!("エラーになるはず: {}", output))
+    }
+
+    #[test]
+    fn test_check_json_rejects_wrong_arity() {
+        let output = check_expression(json!({
+            "condition_ty": { "kind": "enum", "name": "Option" },
+            "arms": [
+                { "pattern": { "kind": "constructor", "name": "None" } },
+                {
+                    "pattern": {
+                        "kind": "constructor",
+                        "name": "Some",
+                        "args": [
+                            { "kind": "constructor", "name": "True" },
+                            { "kind": "constructor", "name": "False" },
+                        ],
+                    },
+                },
+            ],
+        }));
+        assert_eq!(
+            error_of(&output),
+            "expressions[0].arms[1].pattern: constructor `Some` takes 1 argument(s) but 2 were given"
+        );
+
+        let output = check_expression(json!({
+            "condition_ty": { "kind": "enum", "name": "Option" },
+            "arms": [{ "pattern": { "kind": "constructor", "name": "Some" } }],
+        }));
+        assert_eq!(
+            error_of(&output),
+            "expressions[0].arms[0].pattern: constructor `Some` takes 1 argument(s) but 0 were given"
+        );
+    }
+
+    #[test]
+    fn test_check_json_rejects_undefined_names() {
+        let output = check_expression(json!({
+            "condition_ty": { "kind": "enum", "name": "Color" },
+            "arms": [],
+        }));
+        assert_eq!(
+            error_of(&output),
+            "expressions[0].condition_ty: enum `Color` is not defined"
+        );
+
+        let output = check_expression(json!({
+            "condition_ty": { "kind": "enum", "name": "Option" },
+            "arms": [
+                {
+                    "pattern": {
+                        "kind": "constructor",
+                        "name": "Some",
+                        "args": [{ "kind": "constructor", "name": "Maybe" }],
+                    },
+                },
+            ],
+        }));
+        assert_eq!(
+            error_of(&output),
+            "expressions[0].arms[0].pattern.args[0]: constructor `Maybe` is not defined"
+        );
+
+        let output = check_expression(json!({
+            "condition_ty": { "kind": "enum", "name": "Boolean" },
+            "arms": [{ "pattern": { "kind": "discard", "ty": { "kind": "constructor", "name": "Maybe" } } }],
+        }));
+        assert_eq!(
+            error_of(&output),
+            "expressions[0].arms[0].pattern.ty: constructor `Maybe` is not defined"
+        );
+    }
+
+    #[test]
+    fn test_check_json_rejects_undefined_arg_ty() {
+        let input = json!({
+            "definitions": [
+                {
+                    "kind": "enum",
+                    "name": "Option",
+                    "constructors": [
+                        { "name": "None" },
+                        { "name": "Some", "arg_tys": [{ "kind": "enum", "name": "Boolean" }] },
+                    ],
+                },
+            ],
+        });
+
+        let error = check_json(&input.to_string()).unwrap_err();
+        let error = serde_json::from_str::<Value>(&error).unwrap();
+        assert_eq!(
+            error_of(&error),
+            "definitions[0].constructors[1].arg_tys[0]: enum `Boolean` is not defined"
+        );
+    }
+
+    #[test]
+    fn test_check_json_rejects_mismatched_types() {
+        let output = check_expression(json!({
+            "condition_ty": { "kind": "enum", "name": "Option" },
+            "arms": [{ "pattern": { "kind": "constructor", "name": "True" } }],
+        }));
+        assert_eq!(
+            error_of(&output),
+            "expressions[0].arms[0].pattern: expected a pattern of type `Option`, found constructor `True`"
+        );
+
+        let output = check_expression(json!({
+            "condition_ty": { "kind": "enum", "name": "Option" },
+            "arms": [
+                {
+                    "pattern": {
+                        "kind": "constructor",
+                        "name": "Some",
+                        "args": [{ "kind": "discard", "ty": { "kind": "enum", "name": "Option" } }],
+                    },
+                },
+            ],
+        }));
+        assert_eq!(
+            error_of(&output),
+            "expressions[0].arms[0].pattern.args[0]: expected a pattern of type `Boolean`, found `_: Option`"
+        );
+
+        // コンストラクタ型の値は、それを含む enum 型の値でもある。
+        let output = check_expression(json!({
+            "condition_ty": { "kind": "enum", "name": "Boolean" },
+            "arms": [
+                { "pattern": { "kind": "discard", "ty": { "kind": "constructor", "name": "True" } } },
+                { "pattern": { "kind": "constructor", "name": "False" } },
+            ],
+        }));
+        assert_eq!(output["results"][0]["verdict"], json!("exhaustive"));
+    }
+}
@@ -1,141 +1,80 @@
+//! パターンマッチ網羅性検査のライブラリ
+//!
+//! 型定義 ([`TyDatabase`]) と match 式 ([`MatchExpression`]) を組み立てて [`check_match`] に渡すと、
+//! 網羅性検査の結果が得られる。
+//! 独自の型の表現を使う場合は [`TySystem`] を実装する。
+//! ソースコードを持たないツールからは [`json`] モジュールの JSON 形式でも検査できる。
+//!
+//! ```
+//! use pmxc_analyzer::*;
+//!
+//! let mut td = TyDatabase::default();
+//! td.add_definition(TyDefinition::Enum {
+//!     name: "Boolean".to_string(),
+//!     constructors: vec![
+//!         ConstructorDefinition::new("False", vec![]),
+//!         ConstructorDefinition::new("True", vec![]),
+//!     ],
+//! });
+//!
+//! // match b { True => {} }
+//! let expression = MatchExpression {
+//!     condition_ty: Ty::Enum { name: "Boolean".to_string() },
+//!     arms: vec![MatchArm {
+//!         pattern: Pattern::Constructor { name: "True".to_string(), args: vec![] },
+//!     }],
+//! };
+//!
+//! let result = check_match(&expression, &td);
+//! assert!(!result.is_exhaustive);
+//! assert_eq!(result.witnesses[0].to_string(), "False");
+//! ```
+//!
+//! WebAssembly 向けの関数は `wasm` フィーチャー (既定で有効) を指定したときだけ含まれる。
+//! ファジングのための入口 (`fuzzing` モジュール) は `fuzzing` フィーチャーを指定したときだけ公開する。
+
+// 構文解析と網羅性検査の元からあるコードに残っている書き方への指摘。
+// (書き換えはこのライブラリの機能とは別の変更で行う。)
+#![allow(
+    clippy::int_plus_one,
+    clippy::needless_borrow,
+    clippy::redundant_closure,
+    clippy::unnecessary_lazy_evaluations,
+    clippy::unnecessary_map_or,
+    clippy::write_with_newline
+)]
+
+mod cli;
+mod crash;
+mod diagnostic;
+#[cfg(any(test, feature = "fuzzing"))]
+#[doc(hidden)]
+pub mod fuzzing;
+// エディタ向けの機能は wasm と LSP のサーバーから使う。
+#[cfg(any(test, feature = "wasm", not(target_arch = "wasm32")))]
+mod ide;
+pub mod json;
+#[cfg(not(target_arch = "wasm32"))]
+mod lsp;
 mod match_exhaustivity;
+mod messages;
+mod render;
+mod report;
+#[cfg(test)]
+mod snapshot;
 mod syntax;
-
-use monaco::*;
-use std::rc::Rc;
-use syntax::*;
-use wasm_bindgen::prelude::*;
-
-#[wasm_bindgen]
-extern "C" {
-    fn alert(s: &str);
-}
-
-pub(crate) mod monaco {
-    use serde::Serialize;
-
-    pub mod editor {
-        use super::*;
-
-        #[derive(Serialize)]
-        #[allow(unused)]
-        pub enum MarkerSeverity {
-            Hint = 1,
-            Info = 2,
-            Warning = 4,
-            Error = 8,
-        }
-
-        #[derive(Serialize)]
-        #[serde(rename_all = "camelCase")]
-        pub struct MarkerData {
-            // code?: string;
-            pub severity: MarkerSeverity,
-            pub message: String,
-            // source?: string;
-            /// 1-based index
-            pub start_line_number: usize,
-            pub start_column: usize,
-            pub end_line_number: usize,
-            pub end_column: usize,
-            // relatedInformation?: IRelatedInformation[];
-            // tags?: MarkerTag[];
-        }
-    }
-
-    pub mod languages {
-        use super::*;
-
-        #[derive(Serialize)]
-        pub struct State;
-
-        #[derive(Serialize)]
-        #[serde(rename_all = "camelCase")]
-        pub struct Token {
-            pub start_index: usize,
-            pub scopes: String,
-        }
-
-        #[derive(Serialize)]
-        #[serde(rename_all = "camelCase")]
-        pub struct LineTokens {
-            pub tokens: Vec<Token>,
-            pub end_state: State,
-        }
-    }
-}
-
-#[wasm_bindgen]
-pub fn tokenize(source_code: String) -> JsValue {
-    let token_indices = syntax::tokenize::tokenize_with_utf16_indices(Rc::new(source_code));
-
-    let tokens = token_indices
-        .iter()
-        .map(|&(token, start_index)| {
-            let scopes = match token {
-                Token::Comment => "comment",
-                _ if token.is_control_keyword() => "keyword.control",
-                _ if token.is_keyword() => "keyword",
-                Token::Other => "invalid",
-                _ => "none",
-            };
-
-            languages::Token {
-                start_index,
-                scopes: scopes.to_string(),
-            }
-        })
-        .collect();
-
-    let line_tokens = languages::LineTokens {
-        tokens,
-        end_state: languages::State,
-    };
-
-    JsValue::from_serde(&line_tokens).unwrap()
-}
-
-#[wasm_bindgen]
-pub fn validate(source_code: String) -> JsValue {
-    let root = syntax::parse::parse(Rc::new(source_code));
-
-    let mut cursor = syntax::TextCursor::default();
-    let mut errors = vec![];
-    syntax::parse::collect_errors(&root, &mut cursor, &mut errors);
-
-    let markers = errors
-        .into_iter()
-        .map(|(range, message)| editor::MarkerData {
-            severity: editor::MarkerSeverity::Error,
-            message,
-            start_line_number: range.start().line(),
-            start_column: range.start().character(),
-            end_line_number: range.end().line(),
-            end_column: range.end().character(),
-        })
-        .collect::<Vec<editor::MarkerData>>();
-    if !markers.is_empty() {
-        return JsValue::from_serde(&markers).unwrap();
-    }
-
-    let root = Rc::new(root);
-    let token_range_map = syntax::TokenRangeMap::new(&root);
-    let ast = syntax::ast_gen::gen_root(root);
-    let mut model = match_exhaustivity::lower::from_ast(&ast, token_range_map);
-    match_exhaustivity::lower::check(&mut model);
-
-    let markers = model
-        .errors
-        .into_iter()
-        .map(|(range, message)| editor::MarkerData {
-            severity: editor::MarkerSeverity::Error,
-            message,
-            start_line_number: range.start().line(),
-            start_column: range.start().character(),
-            end_line_number: range.end().line(),
-            end_column: range.end().character(),
-        })
-        .collect::<Vec<editor::MarkerData>>();
-
-    JsValue::from_serde(&markers).unwrap()
-}
+#[cfg(test)]
+mod ui_test;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+/// pmxc コマンド (src/bin/pmxc.rs) の入口。ライブラリの利用者向けではない。
+#[doc(hidden)]
+pub use cli::run as run_cli;
+pub use json::check_json;
+pub use match_exhaustivity::{
+    check_match, check_match_with_budget, trace_match, Budget, BudgetExceeded, CheckResult,
+    CheckStats, Checker, ConstructorDefinition, Decomposition, Derivation, MatchArm,
+    MatchExpression, MatchTrace, MemoStats, Pattern, SpaceChecker, SpaceOperation, Ty, TyDatabase,
+    TyDefinition, TySystem, UsefulnessChecker, Verdict,
+};
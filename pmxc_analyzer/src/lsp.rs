@@ -0,0 +1,360 @@
+//! Language Server Protocol のサーバー (pmxc lsp)
+//!
+//! 標準入出力で LSP クライアント (エディタ) と通信する。
+//! 今のところ、開いているドキュメントのドキュメントシンボルと折りたたみ範囲を返す。
+//! (中身は wasm の document_symbols, folding_ranges と同じく ide::outline で計算する。)
+
+use crate::crash;
+use crate::ide::{self, outline};
+use crate::syntax::{TextPosition, TextRange};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+};
+use lsp_types::request::{DocumentSymbolRequest, FoldingRangeRequest, Request as _};
+use lsp_types::{
+    DocumentSymbolResponse, FoldingRangeProviderCapability, OneOf, Position, Range,
+    ServerCapabilities, SymbolKind, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+
+type LspResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// 開いているドキュメントの内容
+type Documents = HashMap<Url, Rc<String>>;
+
+fn to_lsp_position(position: TextPosition) -> Position {
+    // TextPosition は 1 から数える。(列は UTF-16 単位なので、LSP の既定と同じ。)
+    Position::new(
+        position.line().saturating_sub(1) as u32,
+        position.character().saturating_sub(1) as u32,
+    )
+}
+
+fn to_lsp_range(range: TextRange) -> Range {
+    Range::new(to_lsp_position(range.start()), to_lsp_position(range.end()))
+}
+
+#[allow(deprecated)]
+fn to_lsp_document_symbol(symbol: outline::DocumentSymbol) -> lsp_types::DocumentSymbol {
+    lsp_types::DocumentSymbol {
+        name: symbol.name,
+        detail: None,
+        kind: match symbol.kind {
+            outline::DocumentSymbolKind::Enum => SymbolKind::ENUM,
+            outline::DocumentSymbolKind::Ctor => SymbolKind::ENUM_MEMBER,
+            outline::DocumentSymbolKind::Match => SymbolKind::OPERATOR,
+        },
+        tags: None,
+        deprecated: None,
+        range: to_lsp_range(symbol.range),
+        selection_range: to_lsp_range(symbol.selection_range),
+        children: Some(
+            symbol
+                .children
+                .into_iter()
+                .map(to_lsp_document_symbol)
+                .collect(),
+        ),
+    }
+}
+
+fn document_symbols(source_code: Rc<String>) -> DocumentSymbolResponse {
+    let analysis = ide::Analysis::new(source_code);
+
+    DocumentSymbolResponse::Nested(
+        outline::document_symbols(&analysis)
+            .into_iter()
+            .map(to_lsp_document_symbol)
+            .collect(),
+    )
+}
+
+fn folding_ranges(source_code: Rc<String>) -> Vec<lsp_types::FoldingRange> {
+    let analysis = ide::Analysis::new(source_code);
+
+    outline::folding_ranges(&analysis)
+        .into_iter()
+        .map(|range| lsp_types::FoldingRange {
+            start_line: range.start_line as u32 - 1,
+            end_line: range.end_line as u32 - 1,
+            ..lsp_types::FoldingRange::default()
+        })
+        .collect()
+}
+
+/// リクエストの引数を解釈して f を呼び、その結果を応答にする。
+///
+/// f の中でパニックが起きたときは、内部エラーの応答を返す。(サーバーは止めない。)
+fn respond<R: lsp_types::request::Request>(
+    request: Request,
+    f: impl FnOnce(R::Params) -> R::Result,
+) -> Response {
+    let params = match serde_json::from_value::<R::Params>(request.params) {
+        Ok(params) => params,
+        Err(error) => {
+            return Response::new_err(
+                request.id,
+                ErrorCode::InvalidParams as i32,
+                error.to_string(),
+            );
+        }
+    };
+
+    match crash::catch_panic(|| f(params)) {
+        Ok(result) => Response::new_ok(request.id, result),
+        Err(message) => Response::new_err(request.id, ErrorCode::InternalError as i32, message),
+    }
+}
+
+fn on_request(request: Request, documents: &Documents) -> Response {
+    match request.method.as_str() {
+        DocumentSymbolRequest::METHOD => respond::<DocumentSymbolRequest>(request, |params| {
+            let source_code = documents.get(&params.text_document.uri)?;
+            Some(document_symbols(Rc::clone(source_code)))
+        }),
+        FoldingRangeRequest::METHOD => respond::<FoldingRangeRequest>(request, |params| {
+            let source_code = documents.get(&params.text_document.uri)?;
+            Some(folding_ranges(Rc::clone(source_code)))
+        }),
+        _ => Response::new_err(
+            request.id,
+            ErrorCode::MethodNotFound as i32,
+            format!("unknown request: {}", request.method),
+        ),
+    }
+}
+
+fn on_notification(notification: Notification, documents: &mut Documents) {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            if let Ok(params) = notification
+                .extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+            {
+                let document = params.text_document;
+                documents.insert(document.uri, Rc::new(document.text));
+            }
+        }
+        DidChangeTextDocument::METHOD => {
+            if let Ok(params) = notification
+                .extract::<lsp_types::DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD)
+            {
+                // 同期の方法は FULL なので、最後の変更が文書全体になる。
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    documents.insert(params.text_document.uri, Rc::new(change.text));
+                }
+            }
+        }
+        DidCloseTextDocument::METHOD => {
+            if let Ok(params) = notification
+                .extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+            {
+                documents.remove(&params.text_document.uri);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn server_capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// 初期化から終了 (shutdown, exit) までクライアントとやりとりする。
+fn serve(connection: Connection) -> LspResult<()> {
+    connection.initialize(serde_json::to_value(server_capabilities())?)?;
+
+    let mut documents = Documents::new();
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+
+                let response = on_request(request, &documents);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => on_notification(notification, &mut documents),
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// 標準入出力でサーバーを動かす。
+pub(crate) fn run_stdio() -> LspResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_server::RequestId;
+    use serde_json::{json, Value};
+    use std::thread;
+
+    const SOURCE_CODE: &str =
+        "enum Boolean {\n    True,\n    False,\n}\n\nmatch True {\n    True => {}\n}\n";
+
+    /// メモリ上の接続でサーバーを動かすクライアント
+    struct Client {
+        connection: Connection,
+        server: Option<thread::JoinHandle<()>>,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let (server_connection, connection) = Connection::memory();
+            let server = thread::spawn(move || serve(server_connection).unwrap());
+
+            let mut client = Client {
+                connection,
+                server: Some(server),
+                next_id: 0,
+            };
+            client.request("initialize", json!({ "capabilities": {} }));
+            client.notify("initialized", json!({}));
+            client
+        }
+
+        fn notify(&self, method: &str, params: Value) {
+            let notification = Notification::new(method.to_string(), params);
+            self.connection
+                .sender
+                .send(Message::Notification(notification))
+                .unwrap();
+        }
+
+        fn request(&mut self, method: &str, params: Value) -> Response {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+
+            let request = Request::new(id.clone(), method.to_string(), params);
+            self.connection
+                .sender
+                .send(Message::Request(request))
+                .unwrap();
+
+            match self.connection.receiver.recv().unwrap() {
+                Message::Response(response) => {
+                    assert_eq!(response.id, id);
+                    response
+                }
+                message => panic!("unexpected message: {:?}", message),
+            }
+        }
+
+        fn stop(mut self) {
+            self.request("shutdown", Value::Null);
+            self.notify("exit", Value::Null);
+            self.server.take().unwrap().join().unwrap();
+        }
+    }
+
+    fn open(client: &Client, uri: &str, text: &str) {
+        client.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": uri, "languageId": "pmxclang", "version": 1, "text": text }
+            }),
+        );
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        let mut client = Client::start();
+        open(&client, "file:///a.pmxclang", SOURCE_CODE);
+
+        let response = client.request(
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": "file:///a.pmxclang" } }),
+        );
+        let result = response.result.unwrap();
+
+        let names = result
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|symbol| symbol["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Boolean", "match True"]);
+
+        // 位置は 0 から数える。
+        assert_eq!(
+            result[0]["selectionRange"],
+            json!({ "start": { "line": 0, "character": 5 }, "end": { "line": 0, "character": 12 } })
+        );
+        assert_eq!(result[0]["children"][1]["name"], json!("False"));
+
+        client.stop();
+    }
+
+    #[test]
+    fn test_folding_ranges_follow_changes() {
+        let mut client = Client::start();
+        open(&client, "file:///a.pmxclang", "enum Unit { Unit }\n");
+
+        let params = json!({ "textDocument": { "uri": "file:///a.pmxclang" } });
+        let response = client.request("textDocument/foldingRange", params.clone());
+        assert_eq!(response.result, Some(json!([])));
+
+        client.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": "file:///a.pmxclang", "version": 2 },
+                "contentChanges": [{ "text": SOURCE_CODE }]
+            }),
+        );
+
+        let response = client.request("textDocument/foldingRange", params);
+        let lines = response
+            .result
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|range| {
+                (
+                    range["startLine"].as_u64().unwrap(),
+                    range["endLine"].as_u64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![(0, 2), (5, 6)]);
+
+        client.stop();
+    }
+
+    #[test]
+    fn test_unknown_document_and_method() {
+        let mut client = Client::start();
+
+        let response = client.request(
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": "file:///unknown.pmxclang" } }),
+        );
+        assert_eq!(response.result, Some(Value::Null));
+
+        let response = client.request("textDocument/hover", json!({}));
+        assert_eq!(
+            response.error.unwrap().code,
+            ErrorCode::MethodNotFound as i32
+        );
+
+        client.stop();
+    }
+}
@@ -1,24 +1,50 @@
+pub(crate) mod budget;
 pub(crate) mod expressions;
+pub(crate) mod memo;
 pub(crate) mod patterns;
+#[cfg(test)]
+mod property_tests;
 pub(crate) mod space;
 pub(crate) mod space_from_pattern;
 pub(crate) mod space_from_ty;
 pub(crate) mod space_intersection;
+pub(crate) mod space_normalize;
 pub(crate) mod space_subtraction;
 pub(crate) mod space_to_pattern;
+pub(crate) mod symbol;
+pub(crate) mod trace;
 pub(crate) mod ty_system;
 pub(crate) mod use_cases;
+pub(crate) mod usefulness;
 
-pub(crate) use expressions::{MatchArm, MatchExpression};
-pub(crate) use patterns::Pattern;
+pub use budget::{Budget, BudgetExceeded};
+pub use expressions::{MatchArm, MatchExpression};
+pub use memo::{CheckStats, MemoStats};
+pub use patterns::Pattern;
 pub(crate) use space::Space;
-pub(crate) use ty_system::{ConstructorDefinition, Ty, TyDatabase, TyDefinition};
+pub use space_from_ty::{Decomposition, TySystem};
+pub use trace::{trace_match, Derivation, MatchTrace, SpaceOperation};
+pub use ty_system::{ConstructorDefinition, Ty, TyDatabase, TyDefinition};
+pub use use_cases::{
+    check_match, check_match_with_budget, CheckResult, Checker, SpaceChecker, Verdict,
+};
+pub use usefulness::UsefulnessChecker;
 
 pub(crate) mod display {
     use super::*;
     use std::fmt::{self, Display, Formatter};
 
-    impl Display for Pattern {
+    /// 型を表示する。コンストラクタ型は、同名の enum 型と区別できるように `typeof K` と書く。
+    impl Display for Ty {
+        fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+            match self {
+                Ty::Constructor { name } => write!(f, "typeof {}", name),
+                Ty::Enum { name } => write!(f, "{}", name),
+            }
+        }
+    }
+
+    impl<T> Display for Pattern<T> {
         fn fmt(&self, f: &mut Formatter) -> fmt::Result {
             match self {
                 Pattern::Discard { .. } => write!(f, "_"),
@@ -42,33 +68,58 @@ pub(crate) mod display {
 /// 抽象構文木から網羅性検査用の中間表現を生成する。
 /// (網羅性検査アルゴリズムとは無関係。)
 pub(crate) mod lower {
+    use super::space::SpaceCx;
     use super::*;
+    use crate::diagnostic::{Diagnostic, DiagnosticCode, Fix};
+    use crate::messages::Message;
     use crate::syntax::{
-        self, CtorExpr, CtorPat, EnumDecl, Expr, MatchStmt, Node, Pat, Root, Stmt, TextRange,
-        Token, TokenRangeMap,
+        self, CtorExpr, CtorPat, EnumDecl, Expr, MatchStmt, Pat, Root, Stmt, TextRange, Token,
+        TokenRangeMap,
     };
+    use std::collections::HashMap;
 
     pub(crate) struct MatchExhaustivityModel {
-        ty_database: TyDatabase,
-        match_expressions: Vec<(MatchExpression, TextRange)>,
+        pub(crate) ty_database: TyDatabase,
+        pub(crate) match_expressions: Vec<LoweredMatch>,
         token_range_map: TokenRangeMap,
-        pub(crate) errors: Vec<(TextRange, String)>,
+        pub(crate) diagnostics: Vec<Diagnostic>,
+
+        /// enum の名前から、その宣言における名前の範囲への対応。(関連箇所として示すのに使う。)
+        enum_name_ranges: HashMap<String, TextRange>,
+    }
+
+    /// 中間表現に変換された match 式と、その位置情報
+    pub(crate) struct LoweredMatch {
+        pub(crate) expression: MatchExpression,
+
+        /// match キーワードの範囲
+        pub(crate) range: TextRange,
+
+        /// 各アームの範囲。(expression.arms と同じ順番に並ぶ。)
+        pub(crate) arm_ranges: Vec<TextRange>,
+
+        /// 閉じ波カッコの範囲。(構文エラーで欠けているときは None。)
+        pub(crate) r_brace_range: Option<TextRange>,
+    }
+
+    impl MatchExhaustivityModel {
+        pub(crate) fn token_range_map(&self) -> &TokenRangeMap {
+            &self.token_range_map
+        }
     }
 
     fn resolve_ty(ty: &syntax::Ty, m: &mut MatchExhaustivityModel) -> Option<Ty> {
         let ty_name = ty.name_opt.as_ref()?;
 
         if m.ty_database.find_enum_definition(&ty_name).is_none() {
-            let range = ty
-                .node
-                .first_node(|node| node.node() == Node::Name)
-                .and_then(|name| {
-                    name.first_token(|token| token.token() == Token::Ident)
-                        .and_then(|token| m.token_range_map.get(&token))
-                })
-                .cloned()
-                .unwrap_or_default();
-            m.errors.push((range, "定義されていません".to_string()));
+            let range = m.token_range_map.name_range(&ty.node).unwrap_or_default();
+            m.diagnostics.push(Diagnostic::new(
+                DiagnosticCode::UndefinedName,
+                range,
+                Message::UndefinedTy {
+                    name: ty_name.to_string(),
+                },
+            ));
         }
 
         Some(Ty::Enum {
@@ -92,26 +143,43 @@ pub(crate) mod lower {
                             constructor_definition
                         }
                         (constructor_opt, _) => {
-                            let message = if constructor_opt.is_some() {
-                                "型が異なります"
-                            } else {
-                                "定義されていません"
-                            };
-                            // FIXME: パターンが K のときと K(..) のときで字句の見つけ方が異なる。
-                            let range = node
-                                .first_token(|token| token.token() == Token::Ident)
-                                .and_then(|token| m.token_range_map.get(&token))
-                                .or_else(|| {
-                                    node.first_node(|node| node.node() == Node::Name).and_then(
-                                        |name| {
-                                            name.first_token(|token| token.token() == Token::Ident)
-                                                .and_then(|token| m.token_range_map.get(&token))
-                                        },
+                            let range = m.token_range_map.name_range(node).unwrap_or_default();
+                            let diagnostic = match (constructor_opt, ty) {
+                                (Some((enum_name, _)), Ty::Enum { name: ref ty_name }) => {
+                                    let mut diagnostic = Diagnostic::new(
+                                        DiagnosticCode::TypeMismatch,
+                                        range,
+                                        Message::TypeMismatch,
                                     )
-                                })
-                                .cloned()
-                                .unwrap_or_default();
-                            m.errors.push((range, message.to_string()));
+                                    .with_note(Message::CtorOfOtherEnum {
+                                        ctor_name: name.to_string(),
+                                        enum_name: enum_name.to_string(),
+                                        expected_ty_name: ty_name.to_string(),
+                                    });
+                                    if let Some(&enum_range) = m.enum_name_ranges.get(enum_name) {
+                                        diagnostic = diagnostic.with_related(
+                                            enum_range,
+                                            Message::EnumDefinedHere {
+                                                name: enum_name.to_string(),
+                                            },
+                                        );
+                                    }
+                                    diagnostic
+                                }
+                                (Some(_), _) => Diagnostic::new(
+                                    DiagnosticCode::TypeMismatch,
+                                    range,
+                                    Message::TypeMismatch,
+                                ),
+                                (None, _) => Diagnostic::new(
+                                    DiagnosticCode::UndefinedName,
+                                    range,
+                                    Message::UndefinedCtor {
+                                        name: name.to_string(),
+                                    },
+                                ),
+                            };
+                            m.diagnostics.push(diagnostic);
                             return None;
                         }
                     };
@@ -119,22 +187,21 @@ pub(crate) mod lower {
                 let arity = constructor_definition.arg_tys.len();
                 let given_arity = tuple_opt.as_ref().map_or(0, |t| t.len());
                 if arity != given_arity {
-                    let range = node
-                        .first_token(|token| token.token() == Token::Ident)
-                        .and_then(|token| m.token_range_map.get(&token))
-                        .or_else(|| {
-                            node.first_node(|node| node.node() == Node::Name)
-                                .and_then(|name| {
-                                    name.first_token(|token| token.token() == Token::Ident)
-                                        .and_then(|token| m.token_range_map.get(&token))
-                                })
-                        })
-                        .cloned()
-                        .unwrap_or_default();
-                    m.errors.push((
-                        range,
-                        format!("引数の数が異なります ({} → {})", given_arity, arity,),
-                    ));
+                    let range = m.token_range_map.name_range(node).unwrap_or_default();
+                    m.diagnostics.push(
+                        Diagnostic::new(
+                            DiagnosticCode::ArityMismatch,
+                            range,
+                            Message::ArityMismatch {
+                                given: given_arity,
+                                expected: arity,
+                            },
+                        )
+                        .with_note(Message::ArityNote {
+                            ctor_name: name.to_string(),
+                            arity,
+                        }),
+                    );
                     return None;
                 }
 
@@ -167,12 +234,14 @@ pub(crate) mod lower {
             }) => {
                 let (enum_name, _) = match m.ty_database.find_constructor_by_name(name) {
                     None => {
-                        let range = node
-                            .first_token(|token| token.token() == Token::Ident)
-                            .and_then(|token| m.token_range_map.get(&token))
-                            .cloned()
-                            .unwrap_or_default();
-                        m.errors.push((range, "定義されていません".to_string()));
+                        let range = m.token_range_map.name_range(node).unwrap_or_default();
+                        m.diagnostics.push(Diagnostic::new(
+                            DiagnosticCode::UndefinedName,
+                            range,
+                            Message::UndefinedCtor {
+                                name: name.to_string(),
+                            },
+                        ));
                         return None;
                     }
                     Some(t) => t,
@@ -210,33 +279,43 @@ pub(crate) mod lower {
                     None => return,
                 };
 
-                let arms = arms
+                let (arms, arm_ranges) = arms
                     .iter()
-                    .filter_map(|arm| analyze_match_arm(arm, &cond_ty, m))
-                    .collect();
+                    .filter_map(|arm| {
+                        let match_arm = analyze_match_arm(arm, &cond_ty, m)?;
+                        let range = m.token_range_map.node_range(&arm.node).unwrap_or_default();
+                        Some((match_arm, range))
+                    })
+                    .unzip();
 
-                let range = node
-                    .first_token(|token| token.token() == Token::Match)
-                    .and_then(|token| m.token_range_map.get(token))
-                    .cloned()
+                let range = m
+                    .token_range_map
+                    .token_range(node, Token::Match)
                     .unwrap_or_default();
+                let r_brace_range = m.token_range_map.token_range(node, Token::RightBrace);
 
-                m.match_expressions.push((
-                    MatchExpression {
+                m.match_expressions.push(LoweredMatch {
+                    expression: MatchExpression {
                         condition_ty: cond_ty,
                         arms,
                     },
                     range,
-                ));
+                    arm_ranges,
+                    r_brace_range,
+                });
             }
             Stmt::Enum(EnumDecl {
                 name_opt: Some(ref name),
                 ctors,
-                ..
+                node,
             }) => {
+                if let Some(range) = m.token_range_map.name_range(node) {
+                    m.enum_name_ranges.entry(name.to_string()).or_insert(range);
+                }
+
                 // 自己参照のために型だけ定義する。
-                let i = m.ty_database.definitions.len();
-                m.ty_database.definitions.push(TyDefinition::Enum {
+                let i = m.ty_database.definitions().len();
+                m.ty_database.add_definition(TyDefinition::Enum {
                     name: name.to_string(),
                     constructors: vec![],
                 });
@@ -264,10 +343,7 @@ pub(crate) mod lower {
                     })
                     .collect();
 
-                m.ty_database.definitions[i] = TyDefinition::Enum {
-                    name: name.to_string(),
-                    constructors,
-                };
+                m.ty_database.set_constructors(i, constructors);
             }
             _ => {}
         }
@@ -275,12 +351,11 @@ pub(crate) mod lower {
 
     pub(crate) fn from_ast(root: &Root, token_range_map: TokenRangeMap) -> MatchExhaustivityModel {
         let mut m = MatchExhaustivityModel {
-            ty_database: TyDatabase {
-                definitions: vec![],
-            },
+            ty_database: TyDatabase::default(),
             match_expressions: vec![],
             token_range_map,
-            errors: vec![],
+            diagnostics: vec![],
+            enum_name_ranges: HashMap::new(),
         };
 
         for stmt in root.stmts.iter() {
@@ -290,19 +365,123 @@ pub(crate) mod lower {
         m
     }
 
+    /// 足りないアームを閉じ波カッコの直前に追加する修正案を作る。
+    fn add_missing_arms_fix(r_brace_range: TextRange, witnesses: &[Pattern]) -> Fix {
+        let position = r_brace_range.start();
+
+        // 閉じ波カッコが行頭にあるなら、その前に行を挿入する。
+        let text = if position.character() == 1 {
+            witnesses
+                .iter()
+                .map(|pattern| format!("    {} => {{}}\n", pattern))
+                .collect::<String>()
+        } else {
+            witnesses
+                .iter()
+                .map(|pattern| format!("{} => {{}} ", pattern))
+                .collect::<String>()
+        };
+
+        Fix {
+            title: Message::AddMissingArms,
+            edits: vec![(TextRange::new(position, position), text)],
+        }
+    }
+
+    /// 計算量の上限に達したことを示す注記を作る。
+    fn budget_exceeded_note(budget: Budget, exceeded: BudgetExceeded) -> Message {
+        let limit = match exceeded {
+            BudgetExceeded::Steps => budget.max_steps,
+            BudgetExceeded::Depth => budget.max_depth,
+            BudgetExceeded::UnionWidth => budget.max_union_width,
+        };
+        Message::BudgetExceeded { exceeded, limit }
+    }
+
     pub(crate) fn check(model: &mut MatchExhaustivityModel) {
-        for i in 0..model.match_expressions.len() {
-            let (match_expression, range) = &model.match_expressions[i];
-            let (ok, pattern) = use_cases::check_exhaustivity(match_expression, &model.ty_database);
-
-            if !ok {
-                let range = range.clone();
-                let message = match pattern {
-                    Some(pattern) => format!("網羅的ではありません (例: {})", pattern),
-                    None => "網羅的ではありません".to_string(),
+        let budget = Budget::default();
+        for lowered in model.match_expressions.iter() {
+            let LoweredMatch {
+                expression,
+                range,
+                arm_ranges,
+                r_brace_range,
+            } = lowered;
+            let result = use_cases::check_match_with_budget(expression, &model.ty_database, budget);
+
+            if let Verdict::Undetermined(exceeded) = result.verdict {
+                model.diagnostics.push(
+                    Diagnostic::new(DiagnosticCode::Undetermined, *range, Message::Undetermined)
+                        .with_note(budget_exceeded_note(budget, exceeded)),
+                );
+            }
+
+            if result.verdict == Verdict::NonExhaustive {
+                let message = Message::NonExhaustive {
+                    witness_opt: result.witnesses.first().map(|pattern| pattern.to_string()),
                 };
 
-                model.errors.push((range, message));
+                let mut diagnostic =
+                    Diagnostic::new(DiagnosticCode::NonExhaustive, *range, message);
+                if let Ty::Enum { ref name } = expression.condition_ty {
+                    if let Some(&enum_range) = model.enum_name_ranges.get(name) {
+                        diagnostic = diagnostic.with_related(
+                            enum_range,
+                            Message::EnumDefinedHere {
+                                name: name.to_string(),
+                            },
+                        );
+                    }
+                }
+                for pattern in result.witnesses.iter().skip(1) {
+                    diagnostic = diagnostic.with_note(Message::AlsoNotMatched {
+                        pattern: pattern.to_string(),
+                    });
+                }
+                if let (Some(r_brace_range), false) = (r_brace_range, result.witnesses.is_empty()) {
+                    diagnostic = diagnostic
+                        .with_fix(add_missing_arms_fix(*r_brace_range, &result.witnesses));
+                }
+
+                model.diagnostics.push(diagnostic);
+            }
+
+            // 覆っているアームを探す計算量も、match 式ごとに1つの上限の中で数える。
+            let mut cx = SpaceCx::with_budget(&model.ty_database, budget);
+
+            for &i in result.redundant_arms.iter() {
+                let arm_range = arm_ranges[i];
+                let mut diagnostic = Diagnostic::new(
+                    DiagnosticCode::UnreachableArm,
+                    arm_range,
+                    Message::UnreachableArm,
+                )
+                .unnecessary()
+                .with_fix(Fix {
+                    title: Message::RemoveArm,
+                    edits: vec![(arm_range, String::new())],
+                });
+
+                // 1つのアームだけで覆っているなら、それを示す。(上限に達したら探すのをやめる。)
+                let mut covering_arm_opt = None;
+                for j in 0..i {
+                    if cx.budget.exceeded().is_some() {
+                        break;
+                    }
+                    if use_cases::covers(
+                        &expression.arms[j].pattern,
+                        &expression.arms[i].pattern,
+                        &mut cx,
+                    ) {
+                        covering_arm_opt = Some(j);
+                        break;
+                    }
+                }
+                if let Some(j) = covering_arm_opt {
+                    diagnostic = diagnostic.with_related(arm_ranges[j], Message::CoveredByArm);
+                }
+
+                model.diagnostics.push(diagnostic);
             }
         }
     }
@@ -311,29 +490,106 @@ pub(crate) mod lower {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::messages::Locale;
+    use crate::snapshot::{self, assert_snapshots};
     use crate::syntax::{self, ast_gen, parse};
+    use std::fmt::Write;
     use std::fs;
-    use std::io::Write;
-    use std::path::PathBuf;
     use std::rc::Rc;
 
-    #[test]
-    pub(crate) fn test_snapshot() {
-        let root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let tests_dir = root_dir.join("./tests");
-
-        let source_code = fs::read_to_string(&tests_dir.join("check.pmxclang")).unwrap();
-
+    fn lower_source(source_code: String) -> lower::MatchExhaustivityModel {
         let root = Rc::new(parse::parse(Rc::new(source_code)));
         let token_range_map = syntax::TokenRangeMap::new(&root);
 
         let ast = ast_gen::gen_root(root);
-        let mut model = lower::from_ast(&ast, token_range_map);
-        lower::check(&mut model);
+        lower::from_ast(&ast, token_range_map)
+    }
 
-        let mut snapshot = vec![];
-        write!(snapshot, "{:#?}\n", model.errors).unwrap();
+    #[test]
+    pub(crate) fn test_snapshot() {
+        assert_snapshots("check", |source_code| {
+            let mut model = lower_source(source_code);
+            lower::check(&mut model);
+
+            let mut snapshot = String::new();
+            for diagnostic in model.diagnostics.iter() {
+                writeln!(snapshot, "{}", diagnostic).unwrap();
+                for related in diagnostic.related.iter() {
+                    writeln!(
+                        snapshot,
+                        "    related {}: {}",
+                        related.range,
+                        related.message.render(Locale::default())
+                    )
+                    .unwrap();
+                }
+                for note in diagnostic.notes.iter() {
+                    writeln!(snapshot, "    note: {}", note.render(Locale::default())).unwrap();
+                }
+                for fix in diagnostic.fixes.iter() {
+                    writeln!(snapshot, "    fix: {}", fix.title.render(Locale::default())).unwrap();
+                    for (range, text) in fix.edits.iter() {
+                        writeln!(snapshot, "        {} {:?}", range, text).unwrap();
+                    }
+                }
+            }
+            snapshot
+        });
+    }
+
+    #[test]
+    fn test_trace_snapshot() {
+        assert_snapshots("trace", |source_code| {
+            let model = lower_source(source_code);
+
+            let mut snapshot = String::new();
+            for lowered in model.match_expressions.iter() {
+                let trace = trace_match(&lowered.expression, &model.ty_database, Budget::default());
+                writeln!(snapshot, "{} {:?}", lowered.range, trace.verdict).unwrap();
+                write!(snapshot, "{}", trace.derivation).unwrap();
+            }
+            snapshot
+        });
+    }
+
+    /// すべてのテスト用のプログラムについて、2つのアルゴリズムの網羅性と冗長なアームの判定が一致する。
+    #[test]
+    fn test_checkers_agree_on_test_programs() {
+        let mut failures = vec![];
+
+        for kind in ["tokenize", "parse", "ast", "check", "trace", "ui"] {
+            for path in snapshot::source_paths(kind) {
+                let model = lower_source(fs::read_to_string(&path).unwrap());
+
+                for lowered in model.match_expressions.iter() {
+                    let td = &model.ty_database;
+                    let space = SpaceChecker::default().check(&lowered.expression, td);
+                    let usefulness = UsefulnessChecker::default().check(&lowered.expression, td);
+
+                    // 計算量の上限に達したものは比べない。(上限の数え方がアルゴリズムごとに異なる。)
+                    if matches!(space.verdict, Verdict::Undetermined(_))
+                        || matches!(usefulness.verdict, Verdict::Undetermined(_))
+                    {
+                        continue;
+                    }
+
+                    if space.verdict != usefulness.verdict
+                        || space.redundant_arms != usefulness.redundant_arms
+                    {
+                        failures.push(format!(
+                            "{}:{}: space {:?} {:?}, usefulness {:?} {:?}",
+                            path.display(),
+                            lowered.range,
+                            space.verdict,
+                            space.redundant_arms,
+                            usefulness.verdict,
+                            usefulness.redundant_arms
+                        ));
+                    }
+                }
+            }
+        }
 
-        fs::write(&tests_dir.join("check_snapshot.txt"), snapshot).unwrap();
+        assert!(failures.is_empty(), "\n{}", failures.join("\n"));
     }
 }
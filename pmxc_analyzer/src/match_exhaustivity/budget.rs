@@ -0,0 +1,100 @@
+//! 網羅性検査の計算量の上限
+//!
+//! スペースの差は再帰的に計算され、入力によっては手順数や再帰の深さ、ユニオンの大きさが爆発する。
+//! (悪意のあるプログラムや生成されたプログラムで、ブラウザのタブが固まったりスタックが溢れたりしないようにする。)
+//! 上限に達したら、それ以降の差は引かずに左辺をそのまま返す。
+//! その結果は本来の差を含むので、空と判定されたスペースは本当に空だが、空でないと判定されたスペースは
+//! 本当に空でないとは限らない。そのため、網羅的でないという判定は「判定できない」に置き換える。
+
+use serde::{Deserialize, Serialize};
+
+/// 網羅性検査1回で使える計算量の上限
+///
+/// JSON で一部のフィールドだけを指定したときは、残りは既定値になる。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Budget {
+    /// スペースの演算を行う回数の上限 (計算済みの結果を引いた回数は含まない)
+    pub max_steps: usize,
+
+    /// スペースの演算の再帰の深さの上限
+    pub max_depth: usize,
+
+    /// ユニオンスペースの要素数の上限
+    pub max_union_width: usize,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget {
+            max_steps: 200_000,
+            max_depth: 256,
+            max_union_width: 4096,
+        }
+    }
+}
+
+/// 使い切った計算量の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetExceeded {
+    Steps,
+    Depth,
+    UnionWidth,
+}
+
+/// 計算量を数えるもの
+pub(crate) struct BudgetMeter {
+    budget: Budget,
+    steps: usize,
+    depth: usize,
+    exceeded: Option<BudgetExceeded>,
+}
+
+impl BudgetMeter {
+    pub(crate) fn new(budget: Budget) -> Self {
+        BudgetMeter {
+            budget,
+            steps: 0,
+            depth: 0,
+            exceeded: None,
+        }
+    }
+
+    /// 演算を1回始める。上限に達していたら false を返す。(そのときは `leave` を呼ばない。)
+    pub(crate) fn enter(&mut self) -> bool {
+        if self.exceeded.is_some() {
+            return false;
+        }
+
+        if self.steps >= self.budget.max_steps {
+            self.exceeded = Some(BudgetExceeded::Steps);
+            return false;
+        }
+        if self.depth >= self.budget.max_depth {
+            self.exceeded = Some(BudgetExceeded::Depth);
+            return false;
+        }
+
+        self.steps += 1;
+        self.depth += 1;
+        true
+    }
+
+    pub(crate) fn leave(&mut self) {
+        debug_assert!(self.depth > 0);
+        self.depth -= 1;
+    }
+
+    /// ユニオンスペースの要素数を報告する。
+    pub(crate) fn observe_union_width(&mut self, width: usize) {
+        if width > self.budget.max_union_width && self.exceeded.is_none() {
+            self.exceeded = Some(BudgetExceeded::UnionWidth);
+        }
+    }
+
+    /// 上限に達したか？ (達したなら、最初に達した上限の種類)
+    pub(crate) fn exceeded(&self) -> Option<BudgetExceeded> {
+        self.exceeded
+    }
+}
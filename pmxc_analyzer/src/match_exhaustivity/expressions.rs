@@ -1,6 +1,7 @@
 //! サンプル言語の式
 
 use super::*;
+use serde::{Deserialize, Serialize};
 
 /// match 式のアーム (=> の部分)。
 ///
@@ -8,14 +9,16 @@ use super::*;
 ///
 /// この網羅性検査はガード (if 節) があるアームを無視するため、
 /// ガードの条件式も省略している。
-pub(crate) struct MatchArm {
-    pub(crate) pattern: Pattern,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MatchArm<T = Ty> {
+    pub pattern: Pattern<T>,
 }
 
-pub(crate) struct MatchExpression {
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MatchExpression<T = Ty> {
     /// パターンマッチの対象となる式の型。
     /// この網羅性検査では型しか利用しないので、式は持たない。
-    pub(crate) condition_ty: Ty,
+    pub condition_ty: T,
 
-    pub(crate) arms: Vec<MatchArm>,
+    pub arms: Vec<MatchArm<T>>,
 }
@@ -0,0 +1,70 @@
+//! スペースの演算のメモ化
+//!
+//! 網羅性検査では、同じスペースの組に対する差や交差が何度も計算される。
+//! (例えば、コンストラクタどうしの差では `all_are_covered` の判定と積の展開で同じ引数の差を計算する。)
+//! スペースは hash-cons されているので、[`SpaceId`](super::space::SpaceId) の組をそのままキーにできる。
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// 1つのメモ表のヒット数とミス数
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl MemoStats {
+    /// ヒット率。(まだ一度も引いていないときは 0。)
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// 網羅性検査1回分のメモ化の統計
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CheckStats {
+    pub subtraction: MemoStats,
+    pub intersection: MemoStats,
+    pub normalization: MemoStats,
+    pub emptiness: MemoStats,
+}
+
+pub(crate) struct MemoTable<K, V> {
+    map: HashMap<K, V>,
+    stats: MemoStats,
+}
+
+impl<K: Eq + Hash, V: Copy> MemoTable<K, V> {
+    pub(crate) fn new() -> Self {
+        MemoTable {
+            map: HashMap::new(),
+            stats: MemoStats::default(),
+        }
+    }
+
+    /// 計算済みの結果を引く。(ヒットかミスかを数える。)
+    pub(crate) fn get(&mut self, key: &K) -> Option<V> {
+        let value_opt = self.map.get(key).copied();
+        if value_opt.is_some() {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+        value_opt
+    }
+
+    pub(crate) fn insert(&mut self, key: K, value: V) {
+        self.map.insert(key, value);
+    }
+
+    pub(crate) fn stats(&self) -> MemoStats {
+        self.stats
+    }
+}
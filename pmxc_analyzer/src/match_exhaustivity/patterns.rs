@@ -1,16 +1,25 @@
 //! サンプル言語のパターン
 
 use super::*;
+use serde::{Deserialize, Serialize};
 
 /// パターン
-#[derive(Clone, Debug)]
-pub(crate) enum Pattern {
+///
+/// JSON では `{"kind": "discard", "ty": ..}` や `{"kind": "constructor", "name": "True", "args": []}` のように表す。
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Pattern<T = Ty> {
     /// `_`
-    Discard { ty: Ty },
+    Discard { ty: T },
 
     /// コンストラクタパターン
     ///
     /// enum の特定のコンストラクタにだけマッチするようなパターン。
     /// 例: `Boolean::True` や `Pair::Pair(_, _)` など。
-    Constructor { name: String, args: Vec<Pattern> },
+    Constructor {
+        name: String,
+
+        #[serde(default = "Vec::new")]
+        args: Vec<Pattern<T>>,
+    },
 }
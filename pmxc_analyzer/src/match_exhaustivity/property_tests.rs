@@ -0,0 +1,359 @@
+//! 網羅性検査のプロパティテスト
+//!
+//! ランダムな型定義 (再帰的な enum を含む) と match 式を生成して、網羅性検査の結果を
+//! 値を深さの上限まで列挙して総当たりで調べた結果 (オラクル) と比較する。
+
+use super::space::SpaceCx;
+use super::space_normalize::space_normalize;
+use super::space_subtraction::space_subtraction;
+use super::use_cases::{check_exhaustivity, check_match, leaked_space, Checker, SpaceChecker};
+use super::usefulness::UsefulnessChecker;
+use super::*;
+use proptest::prelude::*;
+use proptest::strategy::Union;
+use std::rc::Rc;
+
+/// 型定義の簡潔な表現。`enums[i][j]` は i 番目の enum の j 番目のコンストラクタの引数の型 (enum の番号) のリスト。
+type Enums = Vec<Vec<Vec<usize>>>;
+
+/// 列挙する値の個数の上限。(これを超えるケースは捨てる。)
+const MAX_VALUES: usize = 20_000;
+
+/// 生成するパターンの深さの上限
+const MAX_PATTERN_DEPTH: usize = 3;
+
+fn enum_name(i: usize) -> String {
+    format!("E{}", i)
+}
+
+fn ctor_name(i: usize, j: usize) -> String {
+    format!("E{}C{}", i, j)
+}
+
+fn enum_ty(i: usize) -> Ty {
+    Ty::Enum { name: enum_name(i) }
+}
+
+fn new_ty_database(enums: &Enums) -> TyDatabase {
+    let mut td = TyDatabase::default();
+    for (i, ctors) in enums.iter().enumerate() {
+        td.add_definition(TyDefinition::Enum {
+            name: enum_name(i),
+            constructors: ctors
+                .iter()
+                .enumerate()
+                .map(|(j, args)| {
+                    ConstructorDefinition::new(
+                        ctor_name(i, j),
+                        args.iter().map(|&arg| enum_ty(arg)).collect(),
+                    )
+                })
+                .collect(),
+        });
+    }
+    td
+}
+
+// -----------------------------------------------
+// オラクル
+// -----------------------------------------------
+
+/// 値。(コンストラクタの名前と引数)
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Value {
+    name: String,
+    args: Vec<Value>,
+}
+
+impl Value {
+    fn depth(&self) -> usize {
+        1 + self.args.iter().map(Value::depth).max().unwrap_or(0)
+    }
+}
+
+fn matches(pattern: &Pattern, value: &Value) -> bool {
+    match pattern {
+        Pattern::Discard { .. } => true,
+        Pattern::Constructor { name, args } => {
+            *name == value.name
+                && args.len() == value.args.len()
+                && args.iter().zip(&value.args).all(|(p, v)| matches(p, v))
+        }
+    }
+}
+
+fn pattern_depth(pattern: &Pattern) -> usize {
+    match pattern {
+        Pattern::Discard { .. } => 0,
+        Pattern::Constructor { args, .. } => 1 + args.iter().map(pattern_depth).max().unwrap_or(0),
+    }
+}
+
+/// 各型の値のうち最も浅いものを求める。(値を持たない型は None。)
+fn smallest_values(enums: &Enums) -> Vec<Option<Value>> {
+    let mut smallest: Vec<Option<Value>> = vec![None; enums.len()];
+
+    // 深さ d 以下の値が見つかった型を順に確定させていく。
+    loop {
+        let mut changed = false;
+        for (i, ctors) in enums.iter().enumerate() {
+            for (j, args) in ctors.iter().enumerate() {
+                let args_opt = args
+                    .iter()
+                    .map(|&arg| smallest[arg].clone())
+                    .collect::<Option<Vec<_>>>();
+                if let Some(args) = args_opt {
+                    let value = Value {
+                        name: ctor_name(i, j),
+                        args,
+                    };
+                    if smallest[i]
+                        .as_ref()
+                        .is_none_or(|current| value.depth() < current.depth())
+                    {
+                        smallest[i] = Some(value);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        if !changed {
+            return smallest;
+        }
+    }
+}
+
+/// 型 ty の深さ depth 以下の値をすべて列挙する。(多すぎるときは None。)
+fn enumerate_values(enums: &Enums, ty: usize, depth: usize) -> Option<Vec<Value>> {
+    // values[i] = i 番目の型の、いま見ている深さ以下の値
+    let mut values: Vec<Vec<Value>> = vec![vec![]; enums.len()];
+
+    for _ in 0..depth {
+        let mut next = vec![];
+        for (i, ctors) in enums.iter().enumerate() {
+            let mut ty_values = vec![];
+            for (j, args) in ctors.iter().enumerate() {
+                let mut tuples = vec![vec![]];
+                for &arg in args {
+                    if tuples.len() * values[arg].len() > MAX_VALUES {
+                        return None;
+                    }
+                    tuples = tuples
+                        .into_iter()
+                        .flat_map(|tuple: Vec<Value>| {
+                            values[arg].iter().map(move |value| {
+                                let mut tuple = tuple.clone();
+                                tuple.push(value.clone());
+                                tuple
+                            })
+                        })
+                        .collect();
+                }
+
+                ty_values.extend(tuples.into_iter().map(|args| Value {
+                    name: ctor_name(i, j),
+                    args,
+                }));
+                if ty_values.len() > MAX_VALUES {
+                    return None;
+                }
+            }
+            next.push(ty_values);
+        }
+        values = next;
+    }
+
+    Some(values.swap_remove(ty))
+}
+
+/// パターンにマッチする値を1つ作る。(破棄パターンは最も浅い値で埋める。)
+fn instantiate(pattern: &Pattern, smallest: &[Option<Value>]) -> Option<Value> {
+    match pattern {
+        Pattern::Discard {
+            ty: Ty::Enum { name },
+        } => {
+            let i = name[1..].parse::<usize>().unwrap();
+            smallest[i].clone()
+        }
+        Pattern::Discard { ty } => panic!("unexpected type {:?}", ty),
+        Pattern::Constructor { name, args } => Some(Value {
+            name: name.clone(),
+            args: args
+                .iter()
+                .map(|arg| instantiate(arg, smallest))
+                .collect::<Option<Vec<_>>>()?,
+        }),
+    }
+}
+
+// -----------------------------------------------
+// 生成器
+// -----------------------------------------------
+
+/// 1〜3 個の enum からなる型定義。コンストラクタの引数には任意の enum (自分自身を含む) が現れうる。
+fn enums_strategy() -> impl Strategy<Value = Enums> {
+    (1..=3_usize).prop_flat_map(|n| {
+        prop::collection::vec(
+            prop::collection::vec(prop::collection::vec(0..n, 0..=2), 1..=3),
+            n,
+        )
+    })
+}
+
+/// 型 ty の値にマッチしうるパターン
+fn pattern_strategy(enums: Rc<Enums>, ty: usize, depth: usize) -> BoxedStrategy<Pattern> {
+    let discard = Just(Pattern::Discard { ty: enum_ty(ty) });
+    if depth == 0 {
+        return discard.boxed();
+    }
+
+    let ctors = enums[ty]
+        .iter()
+        .enumerate()
+        .map(|(j, args)| {
+            let name = ctor_name(ty, j);
+            args.iter()
+                .map(|&arg| pattern_strategy(enums.clone(), arg, depth - 1))
+                .collect::<Vec<_>>()
+                .prop_map(move |args| Pattern::Constructor {
+                    name: name.clone(),
+                    args,
+                })
+                .boxed()
+        })
+        .collect::<Vec<_>>();
+
+    prop_oneof![1 => discard, 4 => Union::new(ctors)].boxed()
+}
+
+fn case_strategy() -> impl Strategy<Value = (Enums, MatchExpression)> {
+    enums_strategy().prop_flat_map(|enums| {
+        let n = enums.len();
+        let enums = Rc::new(enums);
+        (0..n).prop_flat_map(move |ty| {
+            let arms = prop::collection::vec(
+                pattern_strategy(enums.clone(), ty, MAX_PATTERN_DEPTH),
+                0..=5,
+            );
+            (Just(enums.as_ref().clone()), arms).prop_map(move |(enums, patterns)| {
+                let expression = MatchExpression {
+                    condition_ty: enum_ty(ty),
+                    arms: patterns
+                        .into_iter()
+                        .map(|pattern| MatchArm { pattern })
+                        .collect(),
+                };
+                (enums, expression)
+            })
+        })
+    })
+}
+
+fn condition_index(expression: &MatchExpression) -> usize {
+    match &expression.condition_ty {
+        Ty::Enum { name } => name[1..].parse().unwrap(),
+        ty => panic!("unexpected type {:?}", ty),
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(500))]
+
+    /// 報告された例はどのアームにもマッチしない。
+    #[test]
+    fn prop_witnesses_escape_all_arms((enums, expression) in case_strategy()) {
+        let td = new_ty_database(&enums);
+        let smallest = smallest_values(&enums);
+        let result = check_match(&expression, &td);
+
+        prop_assert_eq!(result.is_exhaustive, result.witnesses.is_empty());
+
+        for witness in result.witnesses.iter() {
+            let value = instantiate(witness, &smallest);
+            prop_assert!(value.is_some(), "witness {} has no value", witness);
+
+            let value = value.unwrap();
+            for arm in expression.arms.iter() {
+                prop_assert!(
+                    !matches(&arm.pattern, &value),
+                    "witness {} is matched by {}",
+                    witness,
+                    arm.pattern
+                );
+            }
+        }
+
+        let (ok, pattern_opt) = check_exhaustivity(&expression, &td);
+        prop_assert_eq!(ok, result.is_exhaustive);
+        if !ok {
+            prop_assert!(pattern_opt.is_some());
+        }
+    }
+
+    /// 網羅的という判定は、総当たりで列挙した結果と一致する。
+    #[test]
+    fn prop_exhaustive_agrees_with_enumeration((enums, expression) in case_strategy()) {
+        let td = new_ty_database(&enums);
+        let ty = condition_index(&expression);
+
+        // どのアームにもマッチしない値があれば、パターンが見ている深さより下の部分を
+        // 最も浅い値で置き換えても、やはりどのアームにもマッチしない。
+        // そのため、この深さまで調べれば十分。
+        let smallest = smallest_values(&enums);
+        let max_smallest_depth = smallest.iter().flatten().map(Value::depth).max().unwrap_or(0);
+        let max_pattern_depth = expression.arms.iter().map(|arm| pattern_depth(&arm.pattern)).max().unwrap_or(0);
+        let values = enumerate_values(&enums, ty, max_pattern_depth + max_smallest_depth);
+        prop_assume!(values.is_some());
+
+        let escaped = values
+            .unwrap()
+            .into_iter()
+            .find(|value| expression.arms.iter().all(|arm| !matches(&arm.pattern, value)));
+
+        let result = check_match(&expression, &td);
+        prop_assert_eq!(
+            result.is_exhaustive,
+            escaped.is_none(),
+            "escaped value: {:?}, witnesses: {:?}",
+            escaped,
+            result.witnesses.iter().map(|p| p.to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    /// 正規化してもスペースが表す値の集合は変わらない。
+    #[test]
+    fn prop_normalization_preserves_space((enums, expression) in case_strategy()) {
+        let td = new_ty_database(&enums);
+        let mut cx = SpaceCx::new(&td);
+        let space = leaked_space(&expression, &mut cx);
+        let normalized = space_normalize(space, &expression.condition_ty, &mut cx);
+
+        let leak = space_subtraction(space, normalized, &mut cx);
+        prop_assert!(cx.is_empty(leak));
+        let leak = space_subtraction(normalized, space, &mut cx);
+        prop_assert!(cx.is_empty(leak));
+    }
+
+    /// スペースに基づくアルゴリズムと有用性に基づくアルゴリズムの判定が一致する。
+    #[test]
+    fn prop_checkers_agree((enums, expression) in case_strategy()) {
+        let td = new_ty_database(&enums);
+        let space = SpaceChecker::default().check(&expression, &td);
+        let usefulness = UsefulnessChecker::default().check(&expression, &td);
+
+        prop_assert_eq!(space.verdict, usefulness.verdict);
+        prop_assert_eq!(space.redundant_arms, usefulness.redundant_arms);
+
+        // 有用性に基づくアルゴリズムが示す例も、どのアームにもマッチしない。
+        let smallest = smallest_values(&enums);
+        for witness in usefulness.witnesses.iter() {
+            let value = instantiate(witness, &smallest);
+            prop_assert!(value.is_some(), "witness {} has no value", witness);
+
+            let value = value.unwrap();
+            for arm in expression.arms.iter() {
+                prop_assert!(!matches(&arm.pattern, &value), "witness {} is matched by {}", witness, arm.pattern);
+            }
+        }
+    }
+}
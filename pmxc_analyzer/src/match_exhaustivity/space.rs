@@ -1,52 +1,334 @@
-use super::*;
+//! スペース (値の集合) の表現
+//!
+//! スペースは [`SpaceCx`] の中で hash-cons される。構造が等しいスペースは同じ [`SpaceId`] で表されるので、
+//! スペースの比較やハッシュ、複製は整数の操作で済む。
+//! 同様に、コンストラクタの名前は [`Symbol`] に、型は [`TyId`] に置き換えて持つ。
+//!
+//! SpaceCx は網羅性検査を1回行う間だけ使う。計算量の上限 ([`Budget`]) もここで数える。
 
-#[derive(Clone, Debug)]
+use super::budget::{Budget, BudgetMeter};
+use super::memo::{CheckStats, MemoTable};
+use super::space_from_ty::{Decomposition, TySystem};
+use super::symbol::{Interner, Symbol};
+use super::trace::Tracer;
+use std::collections::HashMap;
+
+/// hash-cons されたスペース
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct SpaceId(u32);
+
+/// intern された型
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct TyId(u32);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) enum Space {
-    Constructor { name: String, args: Vec<Space> },
+    Constructor { name: Symbol, args: Vec<SpaceId> },
+
+    Union(Vec<SpaceId>),
+
+    Ty(TyId),
+}
+
+/// スペースや名前、型を保持して、型システムへの問い合わせの結果を覚えておくもの
+pub(crate) struct SpaceCx<'a, S: TySystem> {
+    ts: &'a S,
+    symbols: Interner,
+
+    tys: Vec<S::Ty>,
+    ty_ids: HashMap<S::Ty, TyId>,
+
+    spaces: Vec<Space>,
+    space_ids: HashMap<Space, SpaceId>,
+
+    // 型システムへの問い合わせの結果
+    decompositions: HashMap<TyId, SpaceId>,
+    inhabited: HashMap<TyId, bool>,
+    subtypes: HashMap<(TyId, TyId), bool>,
+
+    // スペースの演算の結果
+    pub(crate) subtractions: MemoTable<(SpaceId, SpaceId), SpaceId>,
+    pub(crate) intersections: MemoTable<(SpaceId, SpaceId), SpaceId>,
+    pub(crate) normalizations: MemoTable<(SpaceId, Option<TyId>), SpaceId>,
+    emptiness: MemoTable<SpaceId, bool>,
 
-    Union(Vec<Space>),
+    pub(crate) budget: BudgetMeter,
 
-    Ty(Ty),
+    /// 演算の導出を記録するもの。(記録しないときは None。)
+    pub(crate) trace: Option<Tracer<S::Ty>>,
 }
 
-impl Space {
-    pub(crate) fn new_empty() -> Space {
-        Space::Union(vec![])
+impl<'a, S: TySystem> SpaceCx<'a, S> {
+    pub(crate) fn new(ts: &'a S) -> Self {
+        SpaceCx::with_budget(ts, Budget::default())
     }
 
-    pub(crate) fn new_union(spaces: impl IntoIterator<Item = Space>) -> Space {
-        fn flatten(space: Space, spaces: &mut Vec<Space>) {
-            match space {
-                Space::Constructor { .. } => spaces.push(space),
-                Space::Ty(..) => spaces.push(space),
-                Space::Union(union) => {
-                    for space in union {
-                        flatten(space, spaces);
-                    }
-                }
-            }
+    pub(crate) fn with_budget(ts: &'a S, budget: Budget) -> Self {
+        let mut cx = SpaceCx {
+            ts,
+            symbols: Interner::default(),
+            tys: vec![],
+            ty_ids: HashMap::new(),
+            spaces: vec![],
+            space_ids: HashMap::new(),
+            decompositions: HashMap::new(),
+            inhabited: HashMap::new(),
+            subtypes: HashMap::new(),
+            subtractions: MemoTable::new(),
+            intersections: MemoTable::new(),
+            normalizations: MemoTable::new(),
+            emptiness: MemoTable::new(),
+            budget: BudgetMeter::new(budget),
+            trace: None,
+        };
+
+        // 空のスペースを最初に作っておく。(new_empty を参照だけで済ませるため。)
+        let empty = cx.intern_space(Space::Union(vec![]));
+        debug_assert_eq!(empty, SpaceId(0));
+        cx
+    }
+
+    // -----------------------------------------------
+    // intern
+    // -----------------------------------------------
+
+    pub(crate) fn intern_symbol(&mut self, name: &str) -> Symbol {
+        self.symbols.intern(name)
+    }
+
+    pub(crate) fn symbol_str(&self, symbol: Symbol) -> &str {
+        self.symbols.resolve(symbol)
+    }
+
+    pub(crate) fn intern_ty(&mut self, ty: &S::Ty) -> TyId {
+        if let Some(&id) = self.ty_ids.get(ty) {
+            return id;
+        }
+
+        let id = TyId(self.tys.len() as u32);
+        self.tys.push(ty.clone());
+        self.ty_ids.insert(ty.clone(), id);
+        id
+    }
+
+    pub(crate) fn ty(&self, id: TyId) -> &S::Ty {
+        &self.tys[id.0 as usize]
+    }
+
+    fn intern_space(&mut self, space: Space) -> SpaceId {
+        if let Some(&id) = self.space_ids.get(&space) {
+            return id;
         }
 
+        let id = SpaceId(self.spaces.len() as u32);
+        self.spaces.push(space.clone());
+        self.space_ids.insert(space, id);
+        id
+    }
+
+    pub(crate) fn space(&self, id: SpaceId) -> &Space {
+        &self.spaces[id.0 as usize]
+    }
+
+    // -----------------------------------------------
+    // スペースの構築
+    // -----------------------------------------------
+
+    pub(crate) fn new_empty(&self) -> SpaceId {
+        SpaceId(0)
+    }
+
+    pub(crate) fn new_ty(&mut self, ty: TyId) -> SpaceId {
+        self.intern_space(Space::Ty(ty))
+    }
+
+    pub(crate) fn new_constructor(&mut self, name: Symbol, args: Vec<SpaceId>) -> SpaceId {
+        self.intern_space(Space::Constructor { name, args })
+    }
+
+    /// ユニオンスペースを作る。入れ子のユニオンは平らにして、重複する要素は1つにまとめる。
+    pub(crate) fn new_union(&mut self, spaces: impl IntoIterator<Item = SpaceId>) -> SpaceId {
         let mut union = vec![];
         for space in spaces {
-            flatten(space, &mut union);
+            match self.space(space) {
+                Space::Union(members) => {
+                    for &member in members.iter() {
+                        if !union.contains(&member) {
+                            union.push(member);
+                        }
+                    }
+                }
+                _ => {
+                    if !union.contains(&space) {
+                        union.push(space);
+                    }
+                }
+            }
         }
 
+        self.budget.observe_union_width(union.len());
+
         if union.len() == 1 {
-            union.into_iter().next().unwrap()
+            union[0]
         } else {
-            Space::Union(union)
+            self.intern_space(Space::Union(union))
+        }
+    }
+
+    /// スペースが空か？ (値を持たない型のスペースも空とみなす。)
+    pub(crate) fn is_empty(&mut self, space: SpaceId) -> bool {
+        if let Some(result) = self.emptiness.get(&space) {
+            return result;
+        }
+
+        let result = match self.space(space).clone() {
+            // 値を持たない引数があれば、コンストラクタを適用した値もない。
+            Space::Constructor { args, .. } => args.into_iter().any(|arg| self.is_empty(arg)),
+            Space::Ty(ty) => !self.is_inhabited(ty),
+            Space::Union(union) => union.into_iter().all(|space| self.is_empty(space)),
+        };
+        self.emptiness.insert(space, result);
+        result
+    }
+
+    /// これまでのメモ化の統計
+    pub(crate) fn stats(&self) -> CheckStats {
+        CheckStats {
+            subtraction: self.subtractions.stats(),
+            intersection: self.intersections.stats(),
+            normalization: self.normalizations.stats(),
+            emptiness: self.emptiness.stats(),
         }
     }
 
-    pub(crate) fn is_empty(&self) -> bool {
-        match self {
-            Space::Constructor { .. } => false,
-            Space::Ty(..) => {
-                // FIXME: コンストラクタを持たない enum 型なら true
-                false
+    // -----------------------------------------------
+    // 型システムへの問い合わせ
+    // -----------------------------------------------
+
+    pub(crate) fn can_decompose(&self, ty: TyId) -> bool {
+        self.ts.can_decompose(self.ty(ty))
+    }
+
+    /// 型を分解したスペースを作る。`can_decompose` が true を返した型に対してだけ呼ぶ。
+    pub(crate) fn decompose(&mut self, ty: TyId) -> SpaceId {
+        if let Some(&space) = self.decompositions.get(&ty) {
+            return space;
+        }
+
+        let space = match self.ts.decompose(self.ty(ty)) {
+            Decomposition::Union(tys) => {
+                let spaces = tys
+                    .iter()
+                    .map(|ty| {
+                        let ty = self.intern_ty(ty);
+                        self.new_ty(ty)
+                    })
+                    .collect::<Vec<_>>();
+                self.new_union(spaces)
+            }
+            Decomposition::Constructor { name, arg_tys } => {
+                let name = self.intern_symbol(&name);
+                let args = arg_tys
+                    .iter()
+                    .map(|ty| {
+                        let ty = self.intern_ty(ty);
+                        self.new_ty(ty)
+                    })
+                    .collect();
+                self.new_constructor(name, args)
             }
-            Space::Union(union) => union.iter().all(|space| space.is_empty()),
+        };
+        self.decompositions.insert(ty, space);
+        space
+    }
+
+    pub(crate) fn is_subtype_of(&mut self, subty: TyId, super_ty: TyId) -> bool {
+        if let Some(&result) = self.subtypes.get(&(subty, super_ty)) {
+            return result;
+        }
+
+        let result = self.ts.is_subtype_of(self.ty(subty), self.ty(super_ty));
+        self.subtypes.insert((subty, super_ty), result);
+        result
+    }
+
+    pub(crate) fn is_constructor_ty(&self, ty: TyId, constructor_name: Symbol) -> bool {
+        self.ts
+            .is_constructor_ty(self.ty(ty), self.symbol_str(constructor_name))
+    }
+
+    pub(crate) fn is_inhabited(&mut self, ty: TyId) -> bool {
+        if let Some(&result) = self.inhabited.get(&ty) {
+            return result;
         }
+
+        let result = self.ts.is_inhabited(self.ty(ty));
+        self.inhabited.insert(ty, result);
+        result
+    }
+
+    pub(crate) fn constructor_arity(&self, constructor_name: Symbol) -> Option<usize> {
+        self.ts.constructor_arity(self.symbol_str(constructor_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::match_exhaustivity::{Ty, TyDatabase, TyDefinition};
+
+    #[test]
+    fn test_hash_consing() {
+        let td = TyDatabase::default();
+        let mut cx = SpaceCx::new(&td);
+
+        let ty = cx.intern_ty(&Ty::Enum {
+            name: "Boolean".to_string(),
+        });
+        let name = cx.intern_symbol("Some");
+        let first = cx.new_ty(ty);
+        let first = cx.new_constructor(name, vec![first]);
+        let second = cx.new_ty(ty);
+        let second = cx.new_constructor(name, vec![second]);
+        assert_eq!(first, second);
+
+        // 入れ子のユニオンは平らになり、重複は取り除かれる。
+        let inner = cx.new_union(vec![first, cx.new_empty()]);
+        assert_eq!(inner, first);
+        let union = cx.new_union(vec![first, second]);
+        assert_eq!(union, first);
+        assert_eq!(cx.new_union(vec![]), cx.new_empty());
+    }
+
+    #[test]
+    fn test_constructor_with_empty_argument_is_empty() {
+        let mut td = TyDatabase::default();
+        td.add_definition(TyDefinition::Enum {
+            name: "Never".to_string(),
+            constructors: vec![],
+        });
+        let mut cx = SpaceCx::new(&td);
+
+        let never = cx.intern_ty(&Ty::Enum {
+            name: "Never".to_string(),
+        });
+        let boolean = cx.intern_ty(&Ty::Enum {
+            name: "Boolean".to_string(),
+        });
+        let name = cx.intern_symbol("Pair");
+
+        // 値を持たない型の引数があれば、コンストラクタを適用した値もない。
+        let never_space = cx.new_ty(never);
+        let boolean_space = cx.new_ty(boolean);
+        let space = cx.new_constructor(name, vec![boolean_space, never_space]);
+        assert!(cx.is_empty(space));
+
+        let empty = cx.new_empty();
+        let space = cx.new_constructor(name, vec![empty, boolean_space]);
+        assert!(cx.is_empty(space));
+
+        // (未定義の型は値を持つものとみなす。)
+        let space = cx.new_constructor(name, vec![boolean_space, boolean_space]);
+        assert!(!cx.is_empty(space));
     }
 }
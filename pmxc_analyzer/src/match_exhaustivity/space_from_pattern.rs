@@ -1,11 +1,20 @@
+use super::space::{SpaceCx, SpaceId};
+use super::space_from_ty::TySystem;
 use super::*;
 
-pub(crate) fn space_from_pattern(pattern: Pattern) -> Space {
+pub(crate) fn space_from_pattern<S: TySystem>(
+    pattern: &Pattern<S::Ty>,
+    cx: &mut SpaceCx<S>,
+) -> SpaceId {
     match pattern {
-        Pattern::Discard { ty } => Space::Ty(ty),
-        Pattern::Constructor { name, args, .. } => Space::Constructor {
-            name,
-            args: args.into_iter().map(space_from_pattern).collect(),
-        },
+        Pattern::Discard { ty } => {
+            let ty = cx.intern_ty(ty);
+            cx.new_ty(ty)
+        }
+        Pattern::Constructor { name, args, .. } => {
+            let name = cx.intern_symbol(name);
+            let args = args.iter().map(|arg| space_from_pattern(arg, cx)).collect();
+            cx.new_constructor(name, args)
+        }
     }
 }
@@ -1,55 +1,79 @@
 //! 型からスペースを作る機能
 //!
 //! これは型システムに強く依存する。
-//! ここでは通常の関数として定義しているが、トレイトやインターフェイスやモジュールを使って、API として渡す方がいい。
+//! 型に関する知識は [`TySystem`] トレイトを通して受け取る。
+//! 網羅性検査のアルゴリズムは型の表現を知らないので、利用者は自前の型をそのまま使える。
 
+use super::space::{SpaceCx, SpaceId};
+use super::trace::{self, SpaceOperation};
 use super::*;
+use std::fmt::Debug;
+use std::hash::Hash;
 
-pub(crate) fn space_from_ty(ty: Ty) -> Space {
-    Space::Ty(ty)
+/// 型を分解した結果
+#[derive(Clone, Debug)]
+pub enum Decomposition<T> {
+    /// いくつかの型の和になる。(例: enum 型は、各コンストラクタのコンストラクタ型の和。)
+    Union(Vec<T>),
+
+    /// 1つのコンストラクタからなる。(例: コンストラクタ型は、そのコンストラクタの引数の型の組。)
+    Constructor { name: String, arg_tys: Vec<T> },
+}
+
+/// 網羅性検査が必要とする型システムの機能
+///
+/// 型は検査の途中で intern されるので、`Eq` と `Hash` を実装している必要がある。
+pub trait TySystem {
+    type Ty: Clone + Debug + Eq + Hash;
+
+    /// 型を分解できるか？
+    fn can_decompose(&self, ty: &Self::Ty) -> bool;
+
+    /// 型を分解する。`can_decompose` が true を返した型に対してだけ呼ばれる。
+    fn decompose(&self, ty: &Self::Ty) -> Decomposition<Self::Ty>;
+
+    /// subty が super_ty の部分型であるか？
+    fn is_subtype_of(&self, subty: &Self::Ty, super_ty: &Self::Ty) -> bool;
+
+    /// ty が名前が `constructor_name` のコンストラクタ型か？
+    fn is_constructor_ty(&self, ty: &Self::Ty, constructor_name: &str) -> bool;
+
+    /// 型に値が存在するか？ (例えばコンストラクタを持たない enum 型には値がない。)
+    fn is_inhabited(&self, ty: &Self::Ty) -> bool;
+
+    /// コンストラクタの引数の個数。(未定義なら None。)
+    fn constructor_arity(&self, constructor_name: &str) -> Option<usize>;
+}
+
+pub(crate) fn space_from_ty<S: TySystem>(ty: &S::Ty, cx: &mut SpaceCx<S>) -> SpaceId {
+    let ty = cx.intern_ty(ty);
+    cx.new_ty(ty)
 }
 
-pub(crate) fn space_can_decompose(space: &Space, td: &TyDatabase) -> bool {
-    match space {
-        Space::Ty(Ty::Enum { ref name }) => td.find_enum_definition(name).is_some(),
-        Space::Ty(Ty::Constructor { ref name }) => td.find_constructor_definition(name).is_some(),
+pub(crate) fn space_can_decompose<S: TySystem>(space: SpaceId, cx: &SpaceCx<S>) -> bool {
+    match *cx.space(space) {
+        Space::Ty(ty) => cx.can_decompose(ty),
         _ => false,
     }
 }
 
-pub(crate) fn space_decompose(space: Space, td: &TyDatabase) -> Space {
-    assert!(space_can_decompose(&space, td));
-
-    match space {
-        Space::Ty(Ty::Enum { ref name }) => {
-            let constructor_definitions = match td.find_enum_definition(name) {
-                Some(x) => x,
-                None => unreachable!(),
-            };
-
-            Space::new_union(constructor_definitions.iter().map(|kd| {
-                space_from_ty(Ty::Constructor {
-                    name: kd.name.to_string(),
-                })
-            }))
-        }
-        Space::Ty(Ty::Constructor { ref name }) => {
-            let constructor_definition = match td.find_constructor_definition(name) {
-                Some(x) => x,
-                None => unreachable!(),
-            };
-
-            Space::Constructor {
-                name: constructor_definition.name.to_string(),
-                args: constructor_definition
-                    .arg_tys
-                    .iter()
-                    .map(|arg_ty| space_from_ty(arg_ty.clone()))
-                    .collect(),
-            }
-        }
+pub(crate) fn space_decompose<S: TySystem>(space: SpaceId, cx: &mut SpaceCx<S>) -> SpaceId {
+    assert!(space_can_decompose(space, cx));
+
+    let ty = match *cx.space(space) {
+        Space::Ty(ty) => ty,
         _ => unreachable!(),
-    }
+    };
+
+    let result = cx.decompose(ty);
+    trace::leaf(
+        cx,
+        SpaceOperation::Decomposition,
+        "type definition",
+        &[space],
+        result,
+    );
+    result
 }
 
 #[cfg(test)]
@@ -59,7 +83,7 @@ mod tests {
     fn new_ty_database() -> TyDatabase {
         let mut td = TyDatabase::default();
 
-        td.definitions.push(TyDefinition::Enum {
+        td.add_definition(TyDefinition::Enum {
             name: "Boolean".to_string(),
             constructors: vec![
                 ConstructorDefinition {
@@ -80,21 +104,26 @@ mod tests {
     fn test_decompose_boolean() {
         let td = new_ty_database();
 
+        let mut cx = SpaceCx::new(&td);
+
         let boolean_ty = Ty::Enum {
             name: "Boolean".to_string(),
         };
-        let ty_space = space_from_ty(boolean_ty);
-        let decomposed_space = space_decompose(ty_space, &td);
+        let ty_space = space_from_ty(&boolean_ty, &mut cx);
+        let decomposed_space = space_decompose(ty_space, &mut cx);
 
-        let spaces = match decomposed_space {
+        let spaces = match cx.space(decomposed_space) {
             Space::Union(spaces) => spaces,
             _ => unreachable!("ユニオンスペースのはず"),
         };
 
         let mut constructor_names = spaces
             .iter()
-            .map(|s| match s {
-                Space::Ty(Ty::Constructor { name }) => name.as_str(),
+            .map(|&s| match *cx.space(s) {
+                Space::Ty(ty) => match cx.ty(ty) {
+                    Ty::Constructor { name } => name.as_str(),
+                    _ => unreachable!("コンストラクタ型スペースのはず"),
+                },
                 _ => unreachable!("コンストラクタ型スペースのはず"),
             })
             .collect::<Vec<_>>();
@@ -107,14 +136,16 @@ mod tests {
     fn test_decompose_true() {
         let td = new_ty_database();
 
+        let mut cx = SpaceCx::new(&td);
+
         let true_ty = Ty::Constructor {
             name: "True".to_string(),
         };
-        let ty_space = space_from_ty(true_ty);
-        let decomposed_space = space_decompose(ty_space, &td);
+        let ty_space = space_from_ty(&true_ty, &mut cx);
+        let decomposed_space = space_decompose(ty_space, &mut cx);
 
-        let name = match decomposed_space {
-            Space::Constructor { ref name, .. } => name.as_str(),
+        let name = match *cx.space(decomposed_space) {
+            Space::Constructor { name, .. } => cx.symbol_str(name),
             _ => unreachable!("コンストラクタスペースのはず"),
         };
         assert_eq!(name, "True");
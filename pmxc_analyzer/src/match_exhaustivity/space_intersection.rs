@@ -1,71 +1,216 @@
-use super::space_from_ty::{space_can_decompose, space_decompose};
+use super::space::{SpaceCx, SpaceId};
+use super::space_from_ty::{space_can_decompose, space_decompose, TySystem};
+use super::trace::{self, SpaceOperation};
 use super::*;
 
-/// スペースの交差 (共通部分) を求める。
-pub(crate) fn space_intersection(mut first: Space, mut second: Space, td: &TyDatabase) -> Space {
+/// スペースの交差 (共通部分) を求める。(同じ組に対する結果は覚えておく。)
+///
+/// 計算量の上限に達したら、first を返す。(本来の交差を含むスペースになる。)
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+pub(crate) fn space_intersection<S: TySystem>(
+    first: SpaceId,
+    second: SpaceId,
+    cx: &mut SpaceCx<S>,
+) -> SpaceId {
+    // 結果のスペースの形は引数の順番によって異なることがあるので、組の順番はそのままキーにする。
+    let key = (first, second);
+    if let Some(result) = cx.intersections.get(&key) {
+        trace::leaf(
+            cx,
+            SpaceOperation::Intersection,
+            "memoized",
+            &[first, second],
+            result,
+        );
+        return result;
+    }
+
+    if !cx.budget.enter() {
+        trace::leaf(
+            cx,
+            SpaceOperation::Intersection,
+            "budget exceeded",
+            &[first, second],
+            first,
+        );
+        return first;
+    }
+    trace::enter(cx, SpaceOperation::Intersection, &[first, second]);
+    let result = intersect(first, second, cx);
+    trace::leave(cx, result);
+    cx.budget.leave();
+    cx.intersections.insert(key, result);
+    result
+}
+
+fn intersect<S: TySystem>(first: SpaceId, second: SpaceId, cx: &mut SpaceCx<S>) -> SpaceId {
     // 空のスペースの交差は常に空になる。
-    if first.is_empty() || second.is_empty() {
-        return Space::new_empty();
+    if cx.is_empty(first) || cx.is_empty(second) {
+        trace::rule(cx, "empty operand");
+        return cx.new_empty();
     }
 
-    match (&mut first, &mut second) {
+    match (cx.space(first).clone(), cx.space(second).clone()) {
         // 型スペース同士の交差は、2つの型の間に部分型関係があれば、
         // 部分型の方のスペースになる。
         // (S⊂T → S∩T = S)
-        (Space::Ty(ref subty), Space::Ty(ref super_ty)) if td.is_subtype_of(subty, super_ty) => {
+        (Space::Ty(subty), Space::Ty(super_ty)) if cx.is_subtype_of(subty, super_ty) => {
+            trace::rule(cx, "subtype");
             first
         }
         // 左右対称
-        (Space::Ty(ref super_ty), Space::Ty(ref subty)) if td.is_subtype_of(subty, super_ty) => {
+        (Space::Ty(super_ty), Space::Ty(subty)) if cx.is_subtype_of(subty, super_ty) => {
+            trace::rule(cx, "subtype");
             second
         }
 
         // 型 T のスペースと、それに含まれるコンストラクタ K のスペースの交差は、
         // コンストラクタ K に絞られる。
         // (K ⊂ T → K∩T = K)
-        (Space::Ty(ref ty), Space::Constructor { ref name, .. })
-            if td.is_subtype_of_constructor(ty, name) =>
-        {
+        (Space::Ty(ty), Space::Constructor { name, .. }) if cx.is_constructor_ty(ty, name) => {
+            trace::rule(cx, "constructor of type");
             second
         }
         // 左右対称
-        (Space::Constructor { ref name, .. }, Space::Ty(ty))
-            if td.is_subtype_of_constructor(ty, name) =>
-        {
+        (Space::Constructor { name, .. }, Space::Ty(ty)) if cx.is_constructor_ty(ty, name) => {
+            trace::rule(cx, "constructor of type");
             first
         }
 
         // ユニオンを分配する。
-        (Space::Union(ref mut union), _) => {
-            let union = std::mem::replace(union, vec![]);
-
-            Space::new_union(
-                union
-                    .into_iter()
-                    .map(|subspace| space_intersection(subspace, second.clone(), td)),
-            )
+        (Space::Union(union), _) => {
+            trace::rule(cx, "distribute union");
+            let spaces = union
+                .into_iter()
+                .map(|subspace| space_intersection(subspace, second, cx))
+                .collect::<Vec<_>>();
+            cx.new_union(spaces)
         }
         // 左右対称
-        (_, Space::Union(..)) => space_intersection(second, first, td),
+        (_, Space::Union(..)) => {
+            trace::rule(cx, "swap operands");
+            space_intersection(second, first, cx)
+        }
 
         // 同じコンストラクタ同士の交差は、各フィールドの交差をとる。
-        // FIXME: 実装
+        // (いずれかのフィールドの交差が空なら、コンストラクタスペースも空とみなされる。)
         (
-            Space::Constructor { ref name, .. },
             Space::Constructor {
-                name: ref second_name,
-                ..
+                name,
+                args: first_args,
+            },
+            Space::Constructor {
+                name: second_name,
+                args: second_args,
             },
-        ) if name == second_name => first,
+        ) if name == second_name => {
+            debug_assert_eq!(
+                first_args.len(),
+                second_args.len(),
+                "同じコンストラクタの引数の個数は一致するはず"
+            );
+
+            trace::rule(cx, "same constructor");
+            let args = first_args
+                .into_iter()
+                .zip(second_args)
+                .map(|(first, second)| space_intersection(first, second, cx))
+                .collect();
+            cx.new_constructor(name, args)
+        }
 
         // 型スペースを分解して交差を取る。
-        (&mut ref s, _) if space_can_decompose(&s, td) => {
-            let first = space_decompose(first, td);
-            space_intersection(first, second, td)
+        _ if space_can_decompose(first, cx) => {
+            trace::rule(cx, "decompose type");
+            let first = space_decompose(first, cx);
+            space_intersection(first, second, cx)
         }
         // 左右対称
-        (_, &mut ref s) if space_can_decompose(&s, td) => space_intersection(second, first, td),
+        _ if space_can_decompose(second, cx) => {
+            trace::rule(cx, "swap operands");
+            space_intersection(second, first, cx)
+        }
+
+        _ => {
+            trace::rule(cx, "disjoint");
+            cx.new_empty()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::match_exhaustivity::space_from_pattern::space_from_pattern;
+
+    fn new_ty_database() -> TyDatabase {
+        let mut td = TyDatabase::default();
+        let boolean_ty = Ty::Enum {
+            name: "Boolean".to_string(),
+        };
+
+        td.add_definition(TyDefinition::Enum {
+            name: "Boolean".to_string(),
+            constructors: vec![
+                ConstructorDefinition::new("False", vec![]),
+                ConstructorDefinition::new("True", vec![]),
+            ],
+        });
+        td.add_definition(TyDefinition::Enum {
+            name: "Pair".to_string(),
+            constructors: vec![ConstructorDefinition::new(
+                "Pair",
+                vec![boolean_ty.clone(), boolean_ty],
+            )],
+        });
+
+        td
+    }
+
+    fn constructor(name: &str, args: Vec<Pattern>) -> Pattern {
+        Pattern::Constructor {
+            name: name.to_string(),
+            args,
+        }
+    }
+
+    fn discard() -> Pattern {
+        Pattern::Discard {
+            ty: Ty::Enum {
+                name: "Boolean".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_same_constructor_intersects_fields() {
+        let td = new_ty_database();
+        let mut cx = SpaceCx::new(&td);
+        let true_pattern = constructor("True", vec![]);
+        let false_pattern = constructor("False", vec![]);
+
+        // Pair(True, _) ∩ Pair(_, False) = Pair(True, False)
+        let first = space_from_pattern(
+            &constructor("Pair", vec![true_pattern.clone(), discard()]),
+            &mut cx,
+        );
+        let second = space_from_pattern(
+            &constructor("Pair", vec![discard(), false_pattern.clone()]),
+            &mut cx,
+        );
+        let expected = space_from_pattern(
+            &constructor("Pair", vec![true_pattern.clone(), false_pattern.clone()]),
+            &mut cx,
+        );
+        assert_eq!(space_intersection(first, second, &mut cx), expected);
+        assert_eq!(space_intersection(second, first, &mut cx), expected);
 
-        _ => Space::new_empty(),
+        // Pair(True, _) ∩ Pair(False, _) は空
+        let third = space_from_pattern(
+            &constructor("Pair", vec![false_pattern, discard()]),
+            &mut cx,
+        );
+        let disjoint = space_intersection(first, third, &mut cx);
+        assert!(cx.is_empty(disjoint));
     }
 }
@@ -0,0 +1,348 @@
+//! スペースの正規化
+//!
+//! 差を取った結果のスペースは、空の要素や、他の要素に含まれる要素、
+//! `K(A, X) | K(B, X)` のように1つにまとめられる要素を含んだ大きなユニオンになりやすい。
+//! 漏れているパターンの例を作ったり表示したりする前に、次の規則で簡単にする。
+//!
+//! - 空の要素を取り除く。
+//! - 他の要素に含まれる要素を取り除く。(含まれるかどうかは差を取って調べる。)
+//! - 1か所の引数だけが異なる同じコンストラクタのスペースをまとめる。(`K(A, X) | K(B, X)` → `K(A | B, X)`)
+//! - 型のすべての値を覆っているスペースを、型のスペースに戻す。(`true | false` → `Boolean`)
+//!
+//! 正規化してもスペースが表す値の集合は変わらない。
+
+use super::space::{SpaceCx, SpaceId, TyId};
+use super::space_from_ty::TySystem;
+use super::space_subtraction::space_subtraction;
+use super::symbol::Symbol;
+use super::*;
+
+/// 型 `ty` の値からなるスペースを正規化する。
+pub(crate) fn space_normalize<S: TySystem>(
+    space: SpaceId,
+    ty: &S::Ty,
+    cx: &mut SpaceCx<S>,
+) -> SpaceId {
+    let ty = cx.intern_ty(ty);
+    normalize(space, Some(ty), cx)
+}
+
+/// スペースを正規化する。型が分からない部分 (`ty` が None) は型のスペースに戻さない。
+/// (計算量の上限に達したら、そのまま返す。)
+fn normalize<S: TySystem>(space: SpaceId, ty: Option<TyId>, cx: &mut SpaceCx<S>) -> SpaceId {
+    if let Some(result) = cx.normalizations.get(&(space, ty)) {
+        return result;
+    }
+
+    if !cx.budget.enter() {
+        return space;
+    }
+    let result = normalize_uncached(space, ty, cx);
+    cx.budget.leave();
+    cx.normalizations.insert((space, ty), result);
+    result
+}
+
+fn normalize_uncached<S: TySystem>(
+    space: SpaceId,
+    ty: Option<TyId>,
+    cx: &mut SpaceCx<S>,
+) -> SpaceId {
+    if cx.is_empty(space) {
+        return cx.new_empty();
+    }
+
+    // 型のすべての値を覆っているなら、型のスペースに戻す。
+    if let Some(ty) = ty {
+        let ty_space = cx.new_ty(ty);
+        let leak = space_subtraction(ty_space, space, cx);
+        if cx.is_empty(leak) {
+            return ty_space;
+        }
+    }
+
+    match cx.space(space).clone() {
+        Space::Ty(_) => space,
+        Space::Constructor { name, args } => {
+            let arg_tys = ty.and_then(|ty| constructor_arg_tys(ty, name, cx));
+            let args = args
+                .iter()
+                .enumerate()
+                .map(|(i, &arg)| {
+                    let arg_ty = arg_tys.as_ref().and_then(|tys| tys.get(i).copied());
+                    normalize(arg, arg_ty, cx)
+                })
+                .collect();
+            cx.new_constructor(name, args)
+        }
+        Space::Union(members) => {
+            let members = members
+                .into_iter()
+                .map(|member| normalize(member, ty, cx))
+                .collect::<Vec<_>>();
+            let union = cx.new_union(members);
+            let mut members = match cx.space(union) {
+                Space::Union(members) => members.clone(),
+                _ => vec![union],
+            };
+            members.retain(|&member| !cx.is_empty(member));
+
+            loop {
+                let removed = remove_subsumed(&mut members, cx);
+                let factored = factor_constructors(&mut members, ty, cx);
+                if !removed && !factored {
+                    break;
+                }
+            }
+
+            cx.new_union(members)
+        }
+    }
+}
+
+/// 他の要素に含まれる要素を取り除く。取り除いたものがあれば true を返す。
+fn remove_subsumed<S: TySystem>(members: &mut Vec<SpaceId>, cx: &mut SpaceCx<S>) -> bool {
+    let mut removed = false;
+    let mut i = 0;
+    while i < members.len() {
+        let subsumed = (0..members.len()).any(|j| {
+            j != i && {
+                let leak = space_subtraction(members[i], members[j], cx);
+                cx.is_empty(leak)
+            }
+        });
+
+        if subsumed {
+            members.remove(i);
+            removed = true;
+        } else {
+            i += 1;
+        }
+    }
+    removed
+}
+
+/// 1か所の引数だけが異なる同じコンストラクタのスペースを1組まとめる。まとめたら true を返す。
+fn factor_constructors<S: TySystem>(
+    members: &mut Vec<SpaceId>,
+    ty: Option<TyId>,
+    cx: &mut SpaceCx<S>,
+) -> bool {
+    for i in 0..members.len() {
+        for j in i + 1..members.len() {
+            let (name, first_args, second_args) = match (cx.space(members[i]), cx.space(members[j]))
+            {
+                (
+                    Space::Constructor { name, args },
+                    Space::Constructor {
+                        name: second_name,
+                        args: second_args,
+                    },
+                ) if name == second_name && args.len() == second_args.len() => {
+                    (*name, args.clone(), second_args.clone())
+                }
+                _ => continue,
+            };
+
+            let mut differences =
+                (0..first_args.len()).filter(|&t| first_args[t] != second_args[t]);
+            let t = match (differences.next(), differences.next()) {
+                (Some(t), None) => t,
+                _ => continue,
+            };
+
+            let arg_ty = ty
+                .and_then(|ty| constructor_arg_tys(ty, name, cx))
+                .and_then(|tys| tys.get(t).copied());
+            let arg = cx.new_union(vec![first_args[t], second_args[t]]);
+            let mut args = first_args;
+            args[t] = normalize(arg, arg_ty, cx);
+
+            members[i] = cx.new_constructor(name, args);
+            members.remove(j);
+            return true;
+        }
+    }
+    false
+}
+
+/// 型 `ty` に含まれるコンストラクタ `name` の引数の型を求める。
+fn constructor_arg_tys<S: TySystem>(
+    ty: TyId,
+    name: Symbol,
+    cx: &mut SpaceCx<S>,
+) -> Option<Vec<TyId>> {
+    if !cx.can_decompose(ty) {
+        return None;
+    }
+
+    let space = cx.decompose(ty);
+    match cx.space(space).clone() {
+        Space::Constructor {
+            name: ty_name,
+            args,
+        } if ty_name == name => args
+            .into_iter()
+            .map(|arg| match *cx.space(arg) {
+                Space::Ty(arg_ty) => Some(arg_ty),
+                _ => None,
+            })
+            .collect(),
+        Space::Constructor { .. } => None,
+        Space::Union(members) => members
+            .into_iter()
+            .find_map(|member| match *cx.space(member) {
+                Space::Ty(member_ty) if cx.is_constructor_ty(member_ty, name) => {
+                    constructor_arg_tys(member_ty, name, cx)
+                }
+                _ => None,
+            }),
+        // (コンストラクタが1つだけの enum 型)
+        Space::Ty(ty) => constructor_arg_tys(ty, name, cx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::match_exhaustivity::space_from_pattern::space_from_pattern;
+    use crate::match_exhaustivity::space_from_ty::space_from_ty;
+
+    fn new_ty_database() -> TyDatabase {
+        let mut td = TyDatabase::default();
+        let boolean_ty = Ty::Enum {
+            name: "Boolean".to_string(),
+        };
+
+        td.add_definition(TyDefinition::Enum {
+            name: "Boolean".to_string(),
+            constructors: vec![
+                ConstructorDefinition::new("False", vec![]),
+                ConstructorDefinition::new("True", vec![]),
+            ],
+        });
+        td.add_definition(TyDefinition::Enum {
+            name: "Pair".to_string(),
+            constructors: vec![ConstructorDefinition::new(
+                "Pair",
+                vec![boolean_ty.clone(), boolean_ty],
+            )],
+        });
+
+        td
+    }
+
+    fn constructor(name: &str, args: Vec<Pattern>) -> Pattern {
+        Pattern::Constructor {
+            name: name.to_string(),
+            args,
+        }
+    }
+
+    fn spaces_are_equal(first: SpaceId, second: SpaceId, cx: &mut SpaceCx<TyDatabase>) -> bool {
+        let leak = space_subtraction(first, second, cx);
+        let other_leak = space_subtraction(second, first, cx);
+        cx.is_empty(leak) && cx.is_empty(other_leak)
+    }
+
+    #[test]
+    fn test_factor_and_fold() {
+        let td = new_ty_database();
+        let mut cx = SpaceCx::new(&td);
+        let pair_ty = Ty::Enum {
+            name: "Pair".to_string(),
+        };
+        let true_pattern = constructor("True", vec![]);
+        let false_pattern = constructor("False", vec![]);
+
+        // Pair(True, False) | Pair(False, False) は Pair(Boolean, False) になる。
+        let first = space_from_pattern(
+            &constructor("Pair", vec![true_pattern.clone(), false_pattern.clone()]),
+            &mut cx,
+        );
+        let second = space_from_pattern(
+            &constructor("Pair", vec![false_pattern.clone(), false_pattern.clone()]),
+            &mut cx,
+        );
+        let space = cx.new_union(vec![first, second]);
+        let normalized = space_normalize(space, &pair_ty, &mut cx);
+
+        let expected = space_from_pattern(
+            &constructor(
+                "Pair",
+                vec![
+                    Pattern::Discard {
+                        ty: Ty::Enum {
+                            name: "Boolean".to_string(),
+                        },
+                    },
+                    false_pattern,
+                ],
+            ),
+            &mut cx,
+        );
+        assert_eq!(normalized, expected);
+        assert!(spaces_are_equal(space, normalized, &mut cx));
+
+        // さらに Pair(_, True) を足すと型全体を覆うので、型のスペースに戻る。
+        let third = space_from_pattern(
+            &constructor(
+                "Pair",
+                vec![
+                    Pattern::Discard {
+                        ty: Ty::Enum {
+                            name: "Boolean".to_string(),
+                        },
+                    },
+                    true_pattern,
+                ],
+            ),
+            &mut cx,
+        );
+        let space = cx.new_union(vec![first, second, third]);
+        let normalized = space_normalize(space, &pair_ty, &mut cx);
+        assert_eq!(normalized, space_from_ty(&pair_ty, &mut cx));
+    }
+
+    #[test]
+    fn test_drop_empty_and_subsumed_members() {
+        let mut td = new_ty_database();
+        td.add_definition(TyDefinition::Enum {
+            name: "Never".to_string(),
+            constructors: vec![],
+        });
+        let mut cx = SpaceCx::new(&td);
+        let pair_ty = Ty::Enum {
+            name: "Pair".to_string(),
+        };
+        let true_pattern = constructor("True", vec![]);
+
+        // Pair(True, True) は Pair(True, _) に含まれ、Never は空なので、どちらも消える。
+        let first = space_from_pattern(
+            &constructor("Pair", vec![true_pattern.clone(), true_pattern.clone()]),
+            &mut cx,
+        );
+        let second = space_from_pattern(
+            &constructor(
+                "Pair",
+                vec![
+                    true_pattern,
+                    Pattern::Discard {
+                        ty: Ty::Enum {
+                            name: "Boolean".to_string(),
+                        },
+                    },
+                ],
+            ),
+            &mut cx,
+        );
+        let never = space_from_ty(
+            &Ty::Enum {
+                name: "Never".to_string(),
+            },
+            &mut cx,
+        );
+        let space = cx.new_union(vec![first, never, second]);
+        assert_eq!(space_normalize(space, &pair_ty, &mut cx), second);
+    }
+}
@@ -1,79 +1,116 @@
-use super::space_from_ty::{space_can_decompose, space_decompose};
+use super::space::{SpaceCx, SpaceId};
+use super::space_from_ty::{space_can_decompose, space_decompose, TySystem};
+use super::trace::{self, SpaceOperation};
 use super::*;
 
-/// スペースからスペースを引く。
-pub(crate) fn space_subtraction(mut first: Space, mut second: Space, td: &TyDatabase) -> Space {
+/// スペースからスペースを引く。(同じ組に対する結果は覚えておく。)
+///
+/// 計算量の上限に達したら、引かずに first を返す。(本来の差を含むスペースになる。)
+pub(crate) fn space_subtraction<S: TySystem>(
+    first: SpaceId,
+    second: SpaceId,
+    cx: &mut SpaceCx<S>,
+) -> SpaceId {
+    if let Some(result) = cx.subtractions.get(&(first, second)) {
+        trace::leaf(
+            cx,
+            SpaceOperation::Subtraction,
+            "memoized",
+            &[first, second],
+            result,
+        );
+        return result;
+    }
+
+    if !cx.budget.enter() {
+        trace::leaf(
+            cx,
+            SpaceOperation::Subtraction,
+            "budget exceeded",
+            &[first, second],
+            first,
+        );
+        return first;
+    }
+    trace::enter(cx, SpaceOperation::Subtraction, &[first, second]);
+    let result = subtract(first, second, cx);
+    trace::leave(cx, result);
+    cx.budget.leave();
+    cx.subtractions.insert((first, second), result);
+    result
+}
+
+fn subtract<S: TySystem>(first: SpaceId, second: SpaceId, cx: &mut SpaceCx<S>) -> SpaceId {
     // 空のスペースからは何を引いても空。
-    if first.is_empty() {
-        return Space::new_empty();
+    if cx.is_empty(first) {
+        trace::rule(cx, "empty minuend");
+        return cx.new_empty();
     }
 
     // 空のスペースを引いても変化しない。
-    if second.is_empty() {
+    if cx.is_empty(second) {
+        trace::rule(cx, "empty subtrahend");
         return first;
     }
 
-    match (&mut first, &mut second) {
+    match (cx.space(first).clone(), cx.space(second).clone()) {
         // 部分型スペースから上位型スペースを引くと空になる。
-        (Space::Ty(ref subty), Space::Ty(ref super_ty)) if td.is_subtype_of(subty, super_ty) => {
-            Space::new_empty()
+        (Space::Ty(subty), Space::Ty(super_ty)) if cx.is_subtype_of(subty, super_ty) => {
+            trace::rule(cx, "subtype");
+            cx.new_empty()
         }
 
         // コンストラクタ型スペースからコンストラクタスペースを引く。
         // 左辺をコンストラクタスペースにばらすだけ。
-        (Space::Ty(ref mut ty), Space::Constructor { ref name, .. })
-            if ty.is_constructor_of_name(name) =>
-        {
-            let ty = std::mem::replace(ty, Ty::default());
-            let first = space_decompose(Space::Ty(ty), td);
-            space_subtraction(first, second, td)
+        (Space::Ty(ty), Space::Constructor { name, .. }) if cx.is_constructor_ty(ty, name) => {
+            trace::rule(cx, "decompose constructor type");
+            let first = space_decompose(first, cx);
+            space_subtraction(first, second, cx)
         }
 
         // ユニオンを分配する。
         // (x | y) \ z = x \ z | y \ z
-        (Space::Union(ref mut union), _) => {
-            let union = std::mem::replace(union, vec![]);
-            Space::new_union(
-                union
-                    .into_iter()
-                    .map(|subspace| space_subtraction(subspace, second.clone(), td)),
-            )
+        (Space::Union(union), _) => {
+            trace::rule(cx, "distribute union");
+            let spaces = union
+                .into_iter()
+                .map(|subspace| space_subtraction(subspace, second, cx))
+                .collect::<Vec<_>>();
+            cx.new_union(spaces)
         }
         // x \ (y | z) = x \ y \ z
-        (_, Space::Union(ref mut union)) => {
-            let union = std::mem::replace(union, vec![]);
+        (_, Space::Union(union)) => {
+            trace::rule(cx, "subtract each member");
             union
                 .into_iter()
-                .fold(first, |first, second| space_subtraction(first, second, td))
+                .fold(first, |first, second| space_subtraction(first, second, cx))
         }
 
         // コンストラクタスペースから、そのコンストラクタを含む型のスペースを引くと、空になる。
-        (Space::Constructor { ref name, .. }, Space::Ty(ref ty))
-            if ty.is_constructor_of_name(name) =>
-        {
-            Space::new_empty()
+        (Space::Constructor { name, .. }, Space::Ty(ty)) if cx.is_constructor_ty(ty, name) => {
+            trace::rule(cx, "constructor of type");
+            cx.new_empty()
         }
 
         // コンストラクタが等しいコンストラクタスペースを引く。
         (
             Space::Constructor {
-                ref mut name,
-                args: ref mut first_args,
+                name,
+                args: first_args,
             },
             Space::Constructor {
-                name: ref second_name,
-                args: ref mut second_args,
+                name: second_name,
+                args: second_args,
             },
         ) if name == second_name => {
-            let name = std::mem::replace(name, String::default());
-            let first_args = std::mem::replace(first_args, vec![]);
-            let second_args = std::mem::replace(second_args, vec![]);
-
             debug_assert_eq!(
                 first_args.len(),
                 second_args.len(),
                 "同じコンストラクタの引数の個数は一致するはず"
             );
+            debug_assert!(cx
+                .constructor_arity(name)
+                .is_none_or(|arity| arity == first_args.len()));
 
             // すべての引数がカバーされているなら空になる。
             // (これは最後のケースの特別な場合を効率よく処理するもの、だと思う。)
@@ -81,57 +118,54 @@ pub(crate) fn space_subtraction(mut first: Space, mut second: Space, td: &TyData
                 first_args
                     .iter()
                     .zip(second_args.iter())
-                    .all(|(first, second)| {
-                        let leak = space_subtraction(first.clone(), second.clone(), td);
-                        leak.is_empty()
+                    .all(|(&first, &second)| {
+                        let leak = space_subtraction(first, second, cx);
+                        cx.is_empty(leak)
                     });
             if all_are_covered {
-                return Space::new_empty();
+                trace::rule(cx, "all arguments covered");
+                return cx.new_empty();
             }
 
             // いずれかの引数のスペースが直交していたら何もしない。
             // (いずれかの引数のスペースが空だったら、コンストラクタパターンも空。)
             // (それ以外のケースに関しては、これも最後のケースの特別な場合を効率よく処理するもの、だと思う。)
             // FIXME: 実装
-            let any_is_empty = first_args.iter().any(|arg| arg.is_empty());
+            let any_is_empty = first_args.iter().any(|&arg| cx.is_empty(arg));
             if any_is_empty {
-                return Space::new_empty();
+                trace::rule(cx, "empty argument");
+                return cx.new_empty();
             }
 
             // いずれかの引数スペースの差を取って、残りはそのまま、というスペースの和を作る。
             // 例えば型 (bool, bool) のパターンマッチで (true, false) というケースがあるとき、
             // 残りのケースとして考えられるのは「.0 が true でない」または「.1 が false でない」。
             // この「～でない」を引き算で、「または」をユニオンで表している。
+            trace::rule(cx, "product difference");
             let mut spaces = vec![];
             for t in 0..first_args.len() {
-                let mut args = vec![];
-
-                for i in 0..first_args.len() {
-                    args.push(if i == t {
-                        space_subtraction(first_args[i].clone(), second_args[i].clone(), td)
-                    } else {
-                        first_args[i].clone()
-                    });
-                }
-
-                spaces.push(Space::Constructor {
-                    name: name.to_string(),
-                    args,
-                });
+                let mut args = first_args.clone();
+                args[t] = space_subtraction(first_args[t], second_args[t], cx);
+                spaces.push(cx.new_constructor(name, args));
             }
-            Space::new_union(spaces)
+            cx.new_union(spaces)
         }
 
         // 型スペースを分解して差をとる。
-        (&mut ref s, _) if space_can_decompose(s, td) => {
-            let first = space_decompose(first, td);
-            space_subtraction(first, second, td)
+        _ if space_can_decompose(first, cx) => {
+            trace::rule(cx, "decompose type");
+            let first = space_decompose(first, cx);
+            space_subtraction(first, second, cx)
         }
-        (_, &mut ref s) if space_can_decompose(s, td) => {
-            let second = space_decompose(second, td);
-            space_subtraction(first, second, td)
+        _ if space_can_decompose(second, cx) => {
+            trace::rule(cx, "decompose type");
+            let second = space_decompose(second, cx);
+            space_subtraction(first, second, cx)
         }
 
-        _ => first,
+        _ => {
+            trace::rule(cx, "disjoint");
+            first
+        }
     }
 }
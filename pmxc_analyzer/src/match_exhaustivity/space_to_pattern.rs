@@ -4,49 +4,82 @@
 
 use super::*;
 
-fn constructor_to_pattern(name: String, constructor_definition: &ConstructorDefinition) -> Pattern {
-    let args = constructor_definition
-        .arg_tys
-        .iter()
-        .map(|arg_ty| Pattern::Discard { ty: arg_ty.clone() })
-        .collect::<Vec<_>>();
-    Pattern::Constructor { name, args }
-}
+use super::space::{SpaceCx, SpaceId, TyId};
+use super::space_from_ty::TySystem;
 
-fn ty_to_pattern(ty: &Ty, td: &TyDatabase) -> Option<Pattern> {
-    match ty {
-        Ty::Constructor { name } => {
-            let (_, constructor_definition) = td.find_constructor_by_name(name)?;
-            Some(constructor_to_pattern(
-                name.to_string(),
-                constructor_definition,
-            ))
-        }
-        Ty::Enum { name } => {
-            let constructor_definitions = td.find_enum_definition(&name)?;
-            let constructor_definition = constructor_definitions.iter().next()?;
-            Some(constructor_to_pattern(
-                constructor_definition.name.to_string(),
-                constructor_definition,
-            ))
-        }
+fn ty_to_pattern<S: TySystem>(ty: TyId, cx: &mut SpaceCx<S>) -> Option<Pattern<S::Ty>> {
+    if !cx.can_decompose(ty) {
+        return None;
+    }
+
+    let space = cx.decompose(ty);
+    match cx.space(space).clone() {
+        Space::Constructor { name, args } => Some(Pattern::Constructor {
+            name: cx.symbol_str(name).to_string(),
+            args: args
+                .into_iter()
+                .map(|arg| match *cx.space(arg) {
+                    Space::Ty(ty) => Pattern::Discard {
+                        ty: cx.ty(ty).clone(),
+                    },
+                    _ => unreachable!("コンストラクタの引数は型スペースのはず"),
+                })
+                .collect(),
+        }),
+        // 値を持たないコンストラクタは例にならない。
+        Space::Union(spaces) => spaces.into_iter().find_map(|space| {
+            let ty = match *cx.space(space) {
+                Space::Ty(ty) => ty,
+                _ => return None,
+            };
+            if cx.is_inhabited(ty) {
+                ty_to_pattern(ty, cx)
+            } else {
+                None
+            }
+        }),
+        // (コンストラクタが1つだけの enum 型)
+        Space::Ty(ty) if cx.is_inhabited(ty) => ty_to_pattern(ty, cx),
+        Space::Ty(_) => None,
     }
 }
 
-pub(crate) fn space_to_pattern(space: Space, td: &TyDatabase) -> Option<Pattern> {
-    match space {
+pub(crate) fn space_to_pattern<S: TySystem>(
+    space: SpaceId,
+    cx: &mut SpaceCx<S>,
+) -> Option<Pattern<S::Ty>> {
+    match cx.space(space).clone() {
         Space::Constructor { name, args } => {
+            // 引数の型スペースはその型の値すべてを表すので、ワイルドカードにする。
+            // (正規化したスペースでは、値をすべて覆う引数は型スペースにまとめられている。)
             let args = args
                 .into_iter()
-                .map(|arg_space| space_to_pattern(arg_space, td))
+                .map(|arg_space| {
+                    let ty_opt = match *cx.space(arg_space) {
+                        Space::Ty(ty) => Some(ty),
+                        _ => None,
+                    };
+                    match ty_opt {
+                        Some(ty) if cx.is_inhabited(ty) => Some(Pattern::Discard {
+                            ty: cx.ty(ty).clone(),
+                        }),
+                        _ => space_to_pattern(arg_space, cx),
+                    }
+                })
                 .collect::<Option<Vec<_>>>()?;
-            Some(Pattern::Constructor { name, args })
+            Some(Pattern::Constructor {
+                name: cx.symbol_str(name).to_string(),
+                args,
+            })
         }
-        Space::Union(spaces) => spaces
-            .into_iter()
-            .filter_map(|space| space_to_pattern(space, td))
-            .next(),
+        Space::Union(spaces) => spaces.into_iter().find_map(|space| {
+            if cx.is_empty(space) {
+                None
+            } else {
+                space_to_pattern(space, cx)
+            }
+        }),
 
-        Space::Ty(ty) => ty_to_pattern(&ty, td),
+        Space::Ty(ty) => ty_to_pattern(ty, cx),
     }
 }
@@ -0,0 +1,51 @@
+//! 名前の intern
+//!
+//! 網羅性検査の途中ではコンストラクタの名前を何度も比較・複製するので、
+//! 名前を整数 ([`Symbol`]) に置き換えて扱う。
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// intern された名前。同じ [`Interner`] から得たものどうしは、名前が等しいときに限り等しい。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Symbol(u32);
+
+#[derive(Debug, Default)]
+pub(crate) struct Interner {
+    names: Vec<Rc<str>>,
+    symbols: HashMap<Rc<str>, Symbol>,
+}
+
+impl Interner {
+    pub(crate) fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+
+        let symbol = Symbol(self.names.len() as u32);
+        let name: Rc<str> = Rc::from(name);
+        self.names.push(name.clone());
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub(crate) fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::default();
+        let t = interner.intern("True");
+        let f = interner.intern("False");
+
+        assert_ne!(t, f);
+        assert_eq!(interner.intern("True"), t);
+        assert_eq!(interner.resolve(f), "False");
+    }
+}
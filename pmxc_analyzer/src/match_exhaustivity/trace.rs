@@ -0,0 +1,366 @@
+//! スペースの演算の導出木
+//!
+//! match 式がなぜ網羅的 (あるいは網羅的でない) のかを説明するために、
+//! スペースの差・交差・分解でどの規則を使ったか、その入力と出力を木の形で記録する。
+//! (教材としてプレイグラウンドで表示するためのもの。)
+//!
+//! 記録は [`SpaceCx`] の `trace` が Some のときだけ行う。通常の検査では何もしない。
+
+use super::budget::Budget;
+use super::space::{SpaceCx, SpaceId};
+use super::space_from_ty::TySystem;
+use super::use_cases::{leaked_space, verdict_of};
+use super::*;
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+
+/// 記録する導出の個数の上限。(超えた分は捨てて、`truncated` を立てる。)
+const MAX_NODES: usize = 2000;
+
+/// スペースの表記の長さの上限 (バイト数)
+const MAX_TEXT_LEN: usize = 200;
+
+/// スペースの演算の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpaceOperation {
+    Subtraction,
+    Intersection,
+    Decomposition,
+}
+
+/// 1回の演算の導出
+#[derive(Clone, Debug, Serialize)]
+pub struct Derivation {
+    pub operation: SpaceOperation,
+
+    /// 使った規則の名前 (例: `"distribute union"`, `"product difference"`)
+    pub rule: &'static str,
+
+    /// 演算の入力となったスペースの表記
+    pub inputs: Vec<String>,
+
+    /// 演算の結果のスペースの表記
+    pub output: String,
+
+    /// この演算の中で行った演算
+    pub children: Vec<Derivation>,
+}
+
+/// match 式の網羅性検査の導出
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct MatchTrace {
+    pub verdict: Verdict,
+
+    /// 条件式の型のスペースからアームのスペースを引く演算の導出
+    pub derivation: Derivation,
+
+    /// 導出が多すぎて、一部を省略したか？
+    pub truncated: bool,
+}
+
+/// 導出木をインデントして1行に1つずつ表示する。(`[規則] 入力 \ 入力 = 出力` の形式。)
+impl Display for Derivation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        fn go(derivation: &Derivation, depth: usize, f: &mut Formatter) -> fmt::Result {
+            // ユニオンの入力は演算子の優先順位が分かるようにカッコで囲む。
+            let inputs = derivation
+                .inputs
+                .iter()
+                .map(|input| {
+                    if input.contains(" | ") {
+                        format!("({})", input)
+                    } else {
+                        input.to_string()
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            write!(f, "{}[{}] ", "  ".repeat(depth), derivation.rule)?;
+            match derivation.operation {
+                SpaceOperation::Subtraction => write!(f, "{}", inputs.join(" \\ "))?,
+                SpaceOperation::Intersection => write!(f, "{}", inputs.join(" ∩ "))?,
+                SpaceOperation::Decomposition => write!(f, "decompose {}", inputs.join(", "))?,
+            }
+            writeln!(f, " = {}", derivation.output)?;
+
+            for child in derivation.children.iter() {
+                go(child, depth + 1, f)?;
+            }
+            Ok(())
+        }
+
+        go(self, 0, f)
+    }
+}
+
+/// 記録中の演算
+struct Frame {
+    operation: SpaceOperation,
+    rule: &'static str,
+    inputs: Vec<SpaceId>,
+    children: Vec<Derivation>,
+}
+
+/// 導出を記録するもの
+pub(crate) struct Tracer<T> {
+    render_ty: fn(&T) -> String,
+    stack: Vec<Frame>,
+    root: Option<Derivation>,
+    nodes: usize,
+    truncated: bool,
+}
+
+impl<T> Tracer<T> {
+    pub(crate) fn new(render_ty: fn(&T) -> String) -> Self {
+        Tracer {
+            render_ty,
+            stack: vec![],
+            root: None,
+            nodes: 0,
+            truncated: false,
+        }
+    }
+}
+
+/// 演算を始める。(記録していなければ何もしない。)
+pub(crate) fn enter<S: TySystem>(
+    cx: &mut SpaceCx<S>,
+    operation: SpaceOperation,
+    inputs: &[SpaceId],
+) {
+    if let Some(tracer) = cx.trace.as_mut() {
+        tracer.stack.push(Frame {
+            operation,
+            rule: "",
+            inputs: inputs.to_vec(),
+            children: vec![],
+        });
+    }
+}
+
+/// 実行中の演算で使った規則を記録する。
+pub(crate) fn rule<S: TySystem>(cx: &mut SpaceCx<S>, rule: &'static str) {
+    if let Some(frame) = cx.trace.as_mut().and_then(|tracer| tracer.stack.last_mut()) {
+        frame.rule = rule;
+    }
+}
+
+/// 演算を終える。
+pub(crate) fn leave<S: TySystem>(cx: &mut SpaceCx<S>, output: SpaceId) {
+    let mut tracer = match cx.trace.take() {
+        Some(tracer) => tracer,
+        None => return,
+    };
+
+    if let Some(frame) = tracer.stack.pop() {
+        // 一番外側の演算は必ず残す。
+        let is_root = tracer.stack.is_empty();
+        if is_root || tracer.nodes < MAX_NODES {
+            let render = |space| render_space(space, tracer.render_ty, cx);
+            let derivation = Derivation {
+                operation: frame.operation,
+                rule: frame.rule,
+                inputs: frame.inputs.iter().map(|&space| render(space)).collect(),
+                output: render(output),
+                children: frame.children,
+            };
+
+            match tracer.stack.last_mut() {
+                Some(parent) => {
+                    parent.children.push(derivation);
+                    tracer.nodes += 1;
+                }
+                None => tracer.root = Some(derivation),
+            }
+        } else {
+            tracer.truncated = true;
+        }
+    }
+
+    cx.trace = Some(tracer);
+}
+
+/// 中で他の演算を行わない演算を記録する。
+pub(crate) fn leaf<S: TySystem>(
+    cx: &mut SpaceCx<S>,
+    operation: SpaceOperation,
+    rule_name: &'static str,
+    inputs: &[SpaceId],
+    output: SpaceId,
+) {
+    if cx.trace.is_some() {
+        enter(cx, operation, inputs);
+        rule(cx, rule_name);
+        leave(cx, output);
+    }
+}
+
+/// スペースを表記する。型のスペースは `_: T`、空のスペースは `∅` と書く。
+/// (長すぎるときは途中で打ち切って `…` を付ける。)
+fn render_space<S: TySystem>(
+    space: SpaceId,
+    render_ty: fn(&S::Ty) -> String,
+    cx: &SpaceCx<S>,
+) -> String {
+    fn go<S: TySystem>(
+        space: SpaceId,
+        render_ty: fn(&S::Ty) -> String,
+        cx: &SpaceCx<S>,
+        out: &mut String,
+    ) {
+        if out.len() > MAX_TEXT_LEN {
+            return;
+        }
+
+        match cx.space(space) {
+            Space::Union(members) if members.is_empty() => out.push('∅'),
+            Space::Union(members) => {
+                for (i, &member) in members.iter().enumerate() {
+                    if i != 0 {
+                        out.push_str(" | ");
+                    }
+                    go(member, render_ty, cx, out);
+                }
+            }
+            Space::Constructor { name, args } => {
+                out.push_str(cx.symbol_str(*name));
+                if !args.is_empty() {
+                    out.push('(');
+                    for (i, &arg) in args.iter().enumerate() {
+                        if i != 0 {
+                            out.push_str(", ");
+                        }
+                        go(arg, render_ty, cx, out);
+                    }
+                    out.push(')');
+                }
+            }
+            Space::Ty(ty) => {
+                out.push_str("_: ");
+                out.push_str(&render_ty(cx.ty(*ty)));
+            }
+        }
+    }
+
+    let mut out = String::new();
+    go(space, render_ty, cx, &mut out);
+
+    if out.len() > MAX_TEXT_LEN {
+        let mut end = MAX_TEXT_LEN;
+        while !out.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.truncate(end);
+        out.push('…');
+    }
+    out
+}
+
+/// match 式の網羅性を検査して、その導出を記録する。
+///
+/// 導出の根は、条件式の型のスペースからアーム全体のスペースを引く演算になる。
+pub fn trace_match<S: TySystem>(
+    expression: &MatchExpression<S::Ty>,
+    td: &S,
+    budget: Budget,
+) -> MatchTrace
+where
+    S::Ty: Display,
+{
+    let mut cx = SpaceCx::with_budget(td, budget);
+    cx.trace = Some(Tracer::new(|ty| ty.to_string()));
+
+    let leaked_space = leaked_space(expression, &mut cx);
+    let verdict = verdict_of(leaked_space, &mut cx);
+
+    let tracer = cx.trace.take().unwrap();
+    MatchTrace {
+        verdict,
+        derivation: tracer.root.expect("差を1回は取るはず"),
+        truncated: tracer.truncated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_ty_database() -> TyDatabase {
+        let mut td = TyDatabase::default();
+        let boolean_ty = Ty::Enum {
+            name: "Boolean".to_string(),
+        };
+
+        td.add_definition(TyDefinition::Enum {
+            name: "Boolean".to_string(),
+            constructors: vec![
+                ConstructorDefinition::new("False", vec![]),
+                ConstructorDefinition::new("True", vec![]),
+            ],
+        });
+        td.add_definition(TyDefinition::Enum {
+            name: "Pair".to_string(),
+            constructors: vec![ConstructorDefinition::new(
+                "Pair",
+                vec![boolean_ty.clone(), boolean_ty],
+            )],
+        });
+
+        td
+    }
+
+    fn constructor(name: &str, args: Vec<Pattern>) -> Pattern {
+        Pattern::Constructor {
+            name: name.to_string(),
+            args,
+        }
+    }
+
+    fn rules(derivation: &Derivation, out: &mut Vec<&'static str>) {
+        out.push(derivation.rule);
+        for child in derivation.children.iter() {
+            rules(child, out);
+        }
+    }
+
+    #[test]
+    fn test_trace_product_difference() {
+        let td = new_ty_database();
+
+        // match pair { Pair(True, _) => {} }
+        let expression = MatchExpression {
+            condition_ty: Ty::Enum {
+                name: "Pair".to_string(),
+            },
+            arms: vec![MatchArm {
+                pattern: constructor(
+                    "Pair",
+                    vec![
+                        constructor("True", vec![]),
+                        Pattern::Discard {
+                            ty: Ty::Enum {
+                                name: "Boolean".to_string(),
+                            },
+                        },
+                    ],
+                ),
+            }],
+        };
+
+        let trace = trace_match(&expression, &td, Budget::default());
+        assert_eq!(trace.verdict, Verdict::NonExhaustive);
+        assert!(!trace.truncated);
+
+        let root = &trace.derivation;
+        assert_eq!(root.operation, SpaceOperation::Subtraction);
+        assert_eq!(root.inputs, vec!["_: Pair", "Pair(True, _: Boolean)"]);
+        assert_eq!(root.rule, "decompose type");
+
+        let mut names = vec![];
+        rules(root, &mut names);
+        assert!(names.contains(&"product difference"), "{:?}", names);
+        assert!(!names.contains(&""), "{:?}", names);
+    }
+}
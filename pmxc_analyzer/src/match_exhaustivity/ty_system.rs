@@ -1,10 +1,18 @@
 //! サンプル言語の型システム
 
+use super::space_from_ty::{Decomposition, TySystem};
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+
 // NOTE: type は Rust の予約語なので ty と略す。
 
 /// 式の型
-#[derive(Clone, Debug)]
-pub(crate) enum Ty {
+///
+/// JSON では `{"kind": "enum", "name": "Boolean"}` のように表す。
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Ty {
     /// enum の1つのコンストラクタが表す型。
     /// 例えば true 型など。
     Constructor {
@@ -15,26 +23,10 @@ pub(crate) enum Ty {
     },
 }
 
-impl Ty {
-    /// この型は名前が `constructor_name` のコンストラクタ型か？
-    pub(crate) fn is_constructor_of_name(&self, constructor_name: &str) -> bool {
-        match self {
-            Ty::Constructor { ref name } => name == constructor_name,
-            _ => false,
-        }
-    }
-}
-
-impl Default for Ty {
-    fn default() -> Ty {
-        Ty::Constructor {
-            name: String::new(),
-        }
-    }
-}
-
 /// 型定義
-pub(crate) enum TyDefinition {
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TyDefinition {
     Enum {
         name: String,
         constructors: Vec<ConstructorDefinition>,
@@ -42,76 +34,169 @@ pub(crate) enum TyDefinition {
 }
 
 /// コンストラクタ定義
-pub(crate) struct ConstructorDefinition {
-    pub(crate) name: String,
-    pub(crate) arg_tys: Vec<Ty>,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConstructorDefinition {
+    pub name: String,
+
+    #[serde(default)]
+    pub arg_tys: Vec<Ty>,
+}
+
+impl ConstructorDefinition {
+    pub fn new(name: impl Into<String>, arg_tys: Vec<Ty>) -> Self {
+        ConstructorDefinition {
+            name: name.into(),
+            arg_tys,
+        }
+    }
 }
 
 /// 型に関する知識を提供する。
-#[derive(Default)]
-pub(crate) struct TyDatabase {
-    pub(crate) definitions: Vec<TyDefinition>,
+///
+/// 名前から定義を引くための索引を持つ。索引は定義を追加するたびに更新する。
+/// 値を持つ型の集合は最初に問い合わせたときに求めて、定義が変わるまで覚えておく。
+#[derive(Clone, Debug, Default)]
+pub struct TyDatabase {
+    definitions: Vec<TyDefinition>,
+
+    /// enum の名前から、definitions における位置への対応
+    enum_indices: HashMap<String, usize>,
+
+    /// コンストラクタの名前から、definitions における位置と、その中でのコンストラクタの位置への対応
+    constructor_indices: HashMap<String, (usize, usize)>,
+
+    /// 値を持つ型の集合 (`inhabited_tys` の結果)
+    inhabited: OnceCell<HashSet<Ty>>,
 }
 
 impl TyDatabase {
-    pub(crate) fn find_enum_definition(&self, enum_name: &str) -> Option<&[ConstructorDefinition]> {
-        self.definitions
-            .iter()
-            .filter_map(|d| match d {
-                TyDefinition::Enum {
-                    ref name,
-                    ref constructors,
-                } if name == enum_name => Some(constructors.as_slice()),
-                _ => None,
-            })
-            .next()
-    }
-
-    pub(crate) fn find_constructor_definition(
+    /// 型定義を追加する。
+    ///
+    /// 同じ名前の型やコンストラクタが複数あるときは、先に追加されたものが使われる。
+    pub fn add_definition(&mut self, definition: TyDefinition) {
+        let i = self.definitions.len();
+        let TyDefinition::Enum { ref name, .. } = definition;
+        self.enum_indices.entry(name.to_string()).or_insert(i);
+
+        self.definitions.push(definition);
+        self.index_constructors(i);
+        self.inhabited.take();
+    }
+
+    /// 追加済みの enum 型の定義にコンストラクタを設定する。
+    ///
+    /// 自己参照する enum を定義するときに、先に型だけを (コンストラクタなしで) 追加しておくのに使う。
+    pub(crate) fn set_constructors(&mut self, i: usize, constructors: Vec<ConstructorDefinition>) {
+        let TyDefinition::Enum {
+            constructors: ref mut old_constructors,
+            ..
+        } = self.definitions[i];
+        debug_assert!(old_constructors.is_empty());
+        *old_constructors = constructors;
+
+        self.index_constructors(i);
+        self.inhabited.take();
+    }
+
+    fn index_constructors(&mut self, i: usize) {
+        let TyDefinition::Enum {
+            ref constructors, ..
+        } = self.definitions[i];
+
+        for (j, constructor) in constructors.iter().enumerate() {
+            // 先に追加された定義にある方を優先する。
+            let index = self
+                .constructor_indices
+                .entry(constructor.name.to_string())
+                .or_insert((i, j));
+            if *index > (i, j) {
+                *index = (i, j);
+            }
+        }
+    }
+
+    pub fn definitions(&self) -> &[TyDefinition] {
+        &self.definitions
+    }
+
+    pub fn find_enum_definition(&self, enum_name: &str) -> Option<&[ConstructorDefinition]> {
+        let &i = self.enum_indices.get(enum_name)?;
+        let TyDefinition::Enum {
+            ref constructors, ..
+        } = self.definitions[i];
+        Some(constructors.as_slice())
+    }
+
+    pub fn find_constructor_definition(
         &self,
         constructor_name: &str,
     ) -> Option<&ConstructorDefinition> {
-        self.definitions
-            .iter()
-            .filter_map(|d| match d {
-                TyDefinition::Enum {
-                    ref constructors, ..
-                } => constructors
-                    .iter()
-                    .filter_map(|k| {
-                        if k.name == constructor_name {
-                            Some(k)
-                        } else {
-                            None
-                        }
-                    })
-                    .next(),
-            })
-            .next()
+        self.find_constructor_by_name(constructor_name)
+            .map(|(_, constructor_definition)| constructor_definition)
     }
 
     pub(crate) fn find_constructor_by_name(
         &self,
         constructor_name: &str,
     ) -> Option<(&str, &ConstructorDefinition)> {
-        self.definitions
-            .iter()
-            .filter_map(|d| match d {
-                TyDefinition::Enum {
-                    ref name,
-                    ref constructors,
-                } => constructors
-                    .iter()
-                    .filter_map(|k| {
-                        if k.name == constructor_name {
-                            Some((name.as_str(), k))
-                        } else {
-                            None
-                        }
+        let &(i, j) = self.constructor_indices.get(constructor_name)?;
+        let TyDefinition::Enum {
+            ref name,
+            ref constructors,
+        } = self.definitions[i];
+        Some((name.as_str(), &constructors[j]))
+    }
+
+    /// 値を持つ型を列挙する。
+    ///
+    /// コンストラクタ型はすべての引数の型が値を持つとき、enum 型はいずれかのコンストラクタ型が値を持つときに値を持つ。
+    /// 再帰的な型があるので、何も増えなくなるまで繰り返す。
+    /// (未定義の型は、誤った診断を出さないように値があるものとみなす。)
+    fn inhabited_tys(&self) -> HashSet<Ty> {
+        let mut inhabited: HashSet<Ty> = HashSet::new();
+        let is_inhabited =
+            |inhabited: &HashSet<Ty>, ty: &Ty| inhabited.contains(ty) || !self.can_decompose(ty);
+
+        loop {
+            let mut changed = false;
+
+            for definition in self.definitions.iter() {
+                let TyDefinition::Enum { name, constructors } = definition;
+
+                for constructor in constructors.iter() {
+                    let ty = Ty::Constructor {
+                        name: constructor.name.to_string(),
+                    };
+                    if !inhabited.contains(&ty)
+                        && constructor
+                            .arg_tys
+                            .iter()
+                            .all(|arg_ty| is_inhabited(&inhabited, arg_ty))
+                    {
+                        inhabited.insert(ty);
+                        changed = true;
+                    }
+                }
+
+                let ty = Ty::Enum {
+                    name: name.to_string(),
+                };
+                if !inhabited.contains(&ty)
+                    && constructors.iter().any(|constructor| {
+                        inhabited.contains(&Ty::Constructor {
+                            name: constructor.name.to_string(),
+                        })
                     })
-                    .next(),
-            })
-            .next()
+                {
+                    inhabited.insert(ty);
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return inhabited;
+            }
+        }
     }
 
     /// subty が super_ty の部分型であるか？
@@ -154,3 +239,152 @@ impl TyDatabase {
         }
     }
 }
+
+impl TySystem for TyDatabase {
+    type Ty = Ty;
+
+    fn can_decompose(&self, ty: &Ty) -> bool {
+        match ty {
+            Ty::Enum { ref name } => self.find_enum_definition(name).is_some(),
+            Ty::Constructor { ref name } => self.find_constructor_definition(name).is_some(),
+        }
+    }
+
+    fn decompose(&self, ty: &Ty) -> Decomposition<Ty> {
+        match ty {
+            Ty::Enum { ref name } => {
+                let constructor_definitions = self.find_enum_definition(name).unwrap_or(&[]);
+
+                Decomposition::Union(
+                    constructor_definitions
+                        .iter()
+                        .map(|kd| Ty::Constructor {
+                            name: kd.name.to_string(),
+                        })
+                        .collect(),
+                )
+            }
+            Ty::Constructor { ref name } => {
+                let arg_tys = self
+                    .find_constructor_definition(name)
+                    .map_or(vec![], |kd| kd.arg_tys.clone());
+
+                Decomposition::Constructor {
+                    name: name.to_string(),
+                    arg_tys,
+                }
+            }
+        }
+    }
+
+    fn is_subtype_of(&self, subty: &Ty, super_ty: &Ty) -> bool {
+        TyDatabase::is_subtype_of(self, subty, super_ty)
+    }
+
+    fn is_constructor_ty(&self, ty: &Ty, constructor_name: &str) -> bool {
+        self.is_subtype_of_constructor(ty, constructor_name)
+    }
+
+    fn is_inhabited(&self, ty: &Ty) -> bool {
+        self.inhabited
+            .get_or_init(|| self.inhabited_tys())
+            .contains(ty)
+            || !self.can_decompose(ty)
+    }
+
+    fn constructor_arity(&self, constructor_name: &str) -> Option<usize> {
+        self.find_constructor_definition(constructor_name)
+            .map(|kd| kd.arg_tys.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indices_prefer_first_definition() {
+        let mut td = TyDatabase::default();
+        td.add_definition(TyDefinition::Enum {
+            name: "List".to_string(),
+            constructors: vec![],
+        });
+        td.add_definition(TyDefinition::Enum {
+            name: "Boolean".to_string(),
+            constructors: vec![
+                ConstructorDefinition::new("true", vec![]),
+                ConstructorDefinition::new("false", vec![]),
+            ],
+        });
+        td.add_definition(TyDefinition::Enum {
+            name: "Boolean".to_string(),
+            constructors: vec![ConstructorDefinition::new("Nil", vec![])],
+        });
+
+        // 後からコンストラクタを設定した定義は、後で追加された定義より優先される。
+        let list = Ty::Enum {
+            name: "List".to_string(),
+        };
+        td.set_constructors(
+            0,
+            vec![
+                ConstructorDefinition::new("Nil", vec![]),
+                ConstructorDefinition::new("Cons", vec![list.clone()]),
+            ],
+        );
+
+        assert_eq!(
+            td.find_enum_definition("Boolean").map(|ks| ks.len()),
+            Some(2)
+        );
+        assert_eq!(
+            td.find_constructor_by_name("Nil").map(|(name, _)| name),
+            Some("List")
+        );
+        assert_eq!(
+            td.find_constructor_definition("Cons")
+                .map(|kd| kd.arg_tys.clone()),
+            Some(vec![list])
+        );
+        assert!(td.find_enum_definition("Option").is_none());
+        assert!(td.find_constructor_definition("Some").is_none());
+    }
+
+    #[test]
+    fn test_inhabited_cache_follows_definitions() {
+        let mut td = TyDatabase::default();
+        let never = Ty::Enum {
+            name: "Never".to_string(),
+        };
+        let wrapper = Ty::Enum {
+            name: "Wrapper".to_string(),
+        };
+
+        td.add_definition(TyDefinition::Enum {
+            name: "Never".to_string(),
+            constructors: vec![],
+        });
+        td.add_definition(TyDefinition::Enum {
+            name: "Wrapper".to_string(),
+            constructors: vec![ConstructorDefinition::new("Wrap", vec![never.clone()])],
+        });
+        assert!(!td.is_inhabited(&never));
+        assert!(!td.is_inhabited(&wrapper));
+
+        // コンストラクタを設定したら、覚えていた結果は捨てて求め直す。
+        td.set_constructors(0, vec![ConstructorDefinition::new("Just", vec![])]);
+        assert!(td.is_inhabited(&never));
+        assert!(td.is_inhabited(&wrapper));
+
+        // 定義を追加したときも同様。
+        let empty = Ty::Enum {
+            name: "Empty".to_string(),
+        };
+        assert!(td.is_inhabited(&empty));
+        td.add_definition(TyDefinition::Enum {
+            name: "Empty".to_string(),
+            constructors: vec![],
+        });
+        assert!(!td.is_inhabited(&empty));
+    }
+}
@@ -1,37 +1,227 @@
+use super::budget::{Budget, BudgetExceeded};
+use super::memo::CheckStats;
+use super::space::{SpaceCx, SpaceId};
 use super::space_from_pattern::space_from_pattern;
-use super::space_from_ty::space_from_ty;
+use super::space_from_ty::{space_from_ty, TySystem};
+use super::space_normalize::space_normalize;
 use super::space_subtraction::space_subtraction;
 use super::space_to_pattern::space_to_pattern;
 use super::*;
 
-pub(crate) fn check_exhaustivity(
-    expression: &MatchExpression,
-    td: &TyDatabase,
-) -> (bool, Option<Pattern>) {
+/// match 式のどのアームにもマッチしない値からなるスペースを求める。
+pub(crate) fn leaked_space<S: TySystem>(
+    expression: &MatchExpression<S::Ty>,
+    cx: &mut SpaceCx<S>,
+) -> SpaceId {
     // 条件式が作るスペース。
-    let ty_space = space_from_ty(expression.condition_ty.clone());
+    let ty_space = space_from_ty(&expression.condition_ty, cx);
 
     // アームのパターンを | でつないだパターンのスペース。
-    let pat_space = Space::new_union(
-        expression
-            .arms
-            .iter()
-            .map(|arm| space_from_pattern(arm.pattern.clone())),
-    );
+    let arm_spaces = expression
+        .arms
+        .iter()
+        .map(|arm| space_from_pattern(&arm.pattern, cx))
+        .collect::<Vec<_>>();
+    let pat_space = cx.new_union(arm_spaces);
 
     // 条件式のスペースからアーム全体のスペースを引く。
-    let leaked_space = space_subtraction(ty_space, pat_space, td);
+    space_subtraction(ty_space, pat_space, cx)
+}
+
+pub(crate) fn check_exhaustivity<S: TySystem>(
+    expression: &MatchExpression<S::Ty>,
+    td: &S,
+) -> (bool, Option<Pattern<S::Ty>>) {
+    let mut cx = SpaceCx::new(td);
+    let leaked_space = leaked_space(expression, &mut cx);
 
     // スペースが残らなければ網羅的といえる。
-    let ok = leaked_space.is_empty();
+    let ok = cx.is_empty(leaked_space);
 
     // マッチしないケースの例を1つ構築する。
-    let leaked_pattern = space_to_pattern(leaked_space, td);
+    let leaked_space = space_normalize(leaked_space, &expression.condition_ty, &mut cx);
+    let leaked_pattern = space_to_pattern(leaked_space, &mut cx);
 
     (ok, leaked_pattern)
 }
 
-pub(crate) fn is_exhaustive(expression: &MatchExpression, td: &TyDatabase) -> bool {
+/// どのアームにもマッチしない値の例を、スペースのユニオンの要素ごとに1つずつ構築する。
+fn leaked_patterns<S: TySystem>(leaked_space: SpaceId, cx: &mut SpaceCx<S>) -> Vec<Pattern<S::Ty>> {
+    let spaces = match cx.space(leaked_space) {
+        Space::Union(spaces) => spaces.clone(),
+        _ => vec![leaked_space],
+    };
+
+    let mut patterns = vec![];
+    for space in spaces {
+        if cx.is_empty(space) {
+            continue;
+        }
+
+        if let Some(pattern) = space_to_pattern(space, cx) {
+            if !patterns.contains(&pattern) {
+                patterns.push(pattern);
+            }
+        }
+    }
+    patterns
+}
+
+/// 漏れたスペースから網羅性を判定する。
+pub(crate) fn verdict_of<S: TySystem>(leaked_space: SpaceId, cx: &mut SpaceCx<S>) -> Verdict {
+    // 上限に達した後の差は本来の差を含むので、空なら網羅的といえるが、空でなくても網羅的でないとは限らない。
+    if cx.is_empty(leaked_space) {
+        Verdict::Exhaustive
+    } else if let Some(exceeded) = cx.budget.exceeded() {
+        Verdict::Undetermined(exceeded)
+    } else {
+        Verdict::NonExhaustive
+    }
+}
+
+/// 手前のアームがすべてカバーしているため、決してマッチしないアームの番号を列挙する。
+pub(crate) fn redundant_arms<S: TySystem>(
+    expression: &MatchExpression<S::Ty>,
+    cx: &mut SpaceCx<S>,
+) -> Vec<usize> {
+    let mut covered = cx.new_empty();
+    let mut redundant_arms = vec![];
+
+    for (i, arm) in expression.arms.iter().enumerate() {
+        let arm_space = space_from_pattern(&arm.pattern, cx);
+
+        // アームのスペースから手前のアームのスペースを引いて、何も残らなければ冗長。
+        let leak = space_subtraction(arm_space, covered, cx);
+        if cx.is_empty(leak) {
+            redundant_arms.push(i);
+        }
+
+        covered = cx.new_union(vec![covered, arm_space]);
+    }
+
+    redundant_arms
+}
+
+/// パターン first がパターン second にマッチするすべての値にマッチするか？
+///
+/// 計算量は cx の上限の中で数える。上限に達した後は、覆っていても false を返すことがある。
+pub(crate) fn covers<S: TySystem>(
+    first: &Pattern<S::Ty>,
+    second: &Pattern<S::Ty>,
+    cx: &mut SpaceCx<S>,
+) -> bool {
+    let first = space_from_pattern(first, cx);
+    let second = space_from_pattern(second, cx);
+    let leak = space_subtraction(second, first, cx);
+    cx.is_empty(leak)
+}
+
+/// 網羅性の判定
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Exhaustive,
+    NonExhaustive,
+
+    /// 計算量の上限に達したため、網羅的かどうか判定できなかった。
+    Undetermined(BudgetExceeded),
+}
+
+/// 網羅性検査の結果
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct CheckResult<T = Ty> {
+    pub verdict: Verdict,
+
+    /// どの値もいずれかのアームにマッチするか？ (`verdict` が `Exhaustive` のときだけ true。)
+    pub is_exhaustive: bool,
+
+    /// どのアームにもマッチしない値の例。(網羅的でないと判定したときだけ、空でない。)
+    pub witnesses: Vec<Pattern<T>>,
+
+    /// 決してマッチしないアームの番号 (0 から数える)
+    ///
+    /// 計算量の上限に達したときは、冗長なアームの一部しか含まないことがある。
+    pub redundant_arms: Vec<usize>,
+
+    /// 検査中のメモ化の統計
+    pub stats: CheckStats,
+}
+
+/// 網羅性検査のアルゴリズム
+///
+/// どのアルゴリズムも同じ入力を受け取り、同じ形の結果を返す。
+/// 既定のアルゴリズムは [`SpaceChecker`] で、比較のために
+/// [`UsefulnessChecker`](super::usefulness::UsefulnessChecker) もある。
+pub trait Checker {
+    fn check<S: TySystem>(&self, expression: &MatchExpression<S::Ty>, td: &S)
+        -> CheckResult<S::Ty>;
+}
+
+/// match 式の網羅性を検査する。
+///
+/// 型の表現は [`TySystem`] を実装すれば自由に選べる。
+/// 計算量の上限は既定値 ([`Budget::default`]) を使う。
+pub fn check_match<S: TySystem>(expression: &MatchExpression<S::Ty>, td: &S) -> CheckResult<S::Ty> {
+    check_match_with_budget(expression, td, Budget::default())
+}
+
+/// 計算量の上限を指定して、match 式の網羅性を検査する。
+///
+/// 上限に達したときは、網羅的であると分かった場合を除いて [`Verdict::Undetermined`] を返す。
+pub fn check_match_with_budget<S: TySystem>(
+    expression: &MatchExpression<S::Ty>,
+    td: &S,
+    budget: Budget,
+) -> CheckResult<S::Ty> {
+    SpaceChecker { budget }.check(expression, td)
+}
+
+/// スペースの差に基づくアルゴリズム
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SpaceChecker {
+    pub budget: Budget,
+}
+
+impl Checker for SpaceChecker {
+    fn check<S: TySystem>(
+        &self,
+        expression: &MatchExpression<S::Ty>,
+        td: &S,
+    ) -> CheckResult<S::Ty> {
+        check_with_spaces(expression, td, self.budget)
+    }
+}
+
+fn check_with_spaces<S: TySystem>(
+    expression: &MatchExpression<S::Ty>,
+    td: &S,
+    budget: Budget,
+) -> CheckResult<S::Ty> {
+    let mut cx = SpaceCx::with_budget(td, budget);
+    let leaked_space = leaked_space(expression, &mut cx);
+    let verdict = verdict_of(leaked_space, &mut cx);
+
+    let witnesses = if verdict == Verdict::NonExhaustive {
+        let leaked_space = space_normalize(leaked_space, &expression.condition_ty, &mut cx);
+        leaked_patterns(leaked_space, &mut cx)
+    } else {
+        vec![]
+    };
+
+    // (同じ理由で、冗長と判定したアームは本当に冗長。)
+    let redundant_arms = redundant_arms(expression, &mut cx);
+
+    CheckResult {
+        verdict,
+        is_exhaustive: verdict == Verdict::Exhaustive,
+        witnesses,
+        redundant_arms,
+        stats: cx.stats(),
+    }
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+pub(crate) fn is_exhaustive<S: TySystem>(expression: &MatchExpression<S::Ty>, td: &S) -> bool {
     let (ok, _) = check_exhaustivity(expression, td);
     ok
 }
@@ -47,7 +237,7 @@ mod tests {
     fn new_ty_database() -> TyDatabase {
         let mut td = TyDatabase::default();
 
-        td.definitions.push(TyDefinition::Enum {
+        td.add_definition(TyDefinition::Enum {
             name: "Boolean".to_string(),
             constructors: vec![
                 ConstructorDefinition {
@@ -141,4 +331,311 @@ mod tests {
 
         assert!(!is_exhaustive(&match_expression, &td));
     }
+
+    #[test]
+    fn test_uninhabited_recursive_enum_is_exhaustive_without_arms() {
+        // enum Never { Never(Never) } の値は作れないので、アームがなくても網羅的。
+        let mut td = TyDatabase::default();
+        let never_ty = Ty::Enum {
+            name: "Never".to_string(),
+        };
+        td.add_definition(TyDefinition::Enum {
+            name: "Never".to_string(),
+            constructors: vec![ConstructorDefinition::new("Never", vec![never_ty.clone()])],
+        });
+
+        let match_expression = MatchExpression {
+            condition_ty: never_ty,
+            arms: vec![],
+        };
+
+        let result = check_match(&match_expression, &td);
+        assert!(result.is_exhaustive);
+        assert!(result.witnesses.is_empty());
+    }
+
+    #[test]
+    fn test_witness_skips_uninhabited_constructors() {
+        // enum Never {} と enum Maybe { Nothing(Never), Just } があるとき、
+        // 最初のコンストラクタ Nothing(_) は値を持たないので、例は Just になる。
+        let mut td = TyDatabase::default();
+        let never_ty = Ty::Enum {
+            name: "Never".to_string(),
+        };
+        td.add_definition(TyDefinition::Enum {
+            name: "Never".to_string(),
+            constructors: vec![],
+        });
+        td.add_definition(TyDefinition::Enum {
+            name: "Maybe".to_string(),
+            constructors: vec![
+                ConstructorDefinition::new("Nothing", vec![never_ty]),
+                ConstructorDefinition::new("Just", vec![]),
+            ],
+        });
+
+        let match_expression = MatchExpression {
+            condition_ty: Ty::Enum {
+                name: "Maybe".to_string(),
+            },
+            arms: vec![],
+        };
+
+        let result = check_match(&match_expression, &td);
+        assert_eq!(result.verdict, Verdict::NonExhaustive);
+        let witnesses = result
+            .witnesses
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(witnesses, vec!["Just"]);
+
+        // Just だけを書けば網羅的。
+        let match_expression = MatchExpression {
+            arms: vec![MatchArm {
+                pattern: Pattern::Constructor {
+                    name: "Just".to_string(),
+                    args: vec![],
+                },
+            }],
+            ..match_expression
+        };
+        assert!(check_match(&match_expression, &td).is_exhaustive);
+    }
+
+    /// TyDatabase を使わない型システム。bool と、bool の組だけがある。
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    enum ToyTy {
+        Bool,
+        Constructor(&'static str),
+    }
+
+    struct Toy;
+
+    impl TySystem for Toy {
+        type Ty = ToyTy;
+
+        fn can_decompose(&self, _: &ToyTy) -> bool {
+            true
+        }
+
+        fn decompose(&self, ty: &ToyTy) -> Decomposition<ToyTy> {
+            match ty {
+                ToyTy::Bool => Decomposition::Union(vec![
+                    ToyTy::Constructor("true"),
+                    ToyTy::Constructor("false"),
+                ]),
+                ToyTy::Constructor(name) => Decomposition::Constructor {
+                    name: name.to_string(),
+                    arg_tys: match *name {
+                        "pair" => vec![ToyTy::Bool, ToyTy::Bool],
+                        _ => vec![],
+                    },
+                },
+            }
+        }
+
+        fn is_subtype_of(&self, subty: &ToyTy, super_ty: &ToyTy) -> bool {
+            subty == super_ty
+                || (*super_ty == ToyTy::Bool
+                    && matches!(
+                        subty,
+                        ToyTy::Constructor("true") | ToyTy::Constructor("false")
+                    ))
+        }
+
+        fn is_constructor_ty(&self, ty: &ToyTy, constructor_name: &str) -> bool {
+            matches!(ty, ToyTy::Constructor(name) if *name == constructor_name)
+        }
+
+        fn is_inhabited(&self, _: &ToyTy) -> bool {
+            true
+        }
+
+        fn constructor_arity(&self, constructor_name: &str) -> Option<usize> {
+            match constructor_name {
+                "pair" => Some(2),
+                "true" | "false" => Some(0),
+                _ => None,
+            }
+        }
+    }
+
+    fn toy_pattern(name: &str, args: Vec<Pattern<ToyTy>>) -> Pattern<ToyTy> {
+        Pattern::Constructor {
+            name: name.to_string(),
+            args,
+        }
+    }
+
+    #[test]
+    fn test_custom_ty_system() {
+        let discard = Pattern::Discard { ty: ToyTy::Bool };
+
+        // match p { pair(true, _) => {} }
+        let mut match_expression = MatchExpression {
+            condition_ty: ToyTy::Constructor("pair"),
+            arms: vec![MatchArm {
+                pattern: toy_pattern("pair", vec![toy_pattern("true", vec![]), discard.clone()]),
+            }],
+        };
+
+        let result = check_match(&match_expression, &Toy);
+        assert!(!result.is_exhaustive);
+        let witnesses = result
+            .witnesses
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(witnesses, vec!["pair(false, _)"]);
+
+        // pair(false, _) => {} を追加する。
+        match_expression.arms.push(MatchArm {
+            pattern: toy_pattern("pair", vec![toy_pattern("false", vec![]), discard]),
+        });
+
+        assert!(check_match(&match_expression, &Toy).is_exhaustive);
+    }
+
+    #[test]
+    fn test_memoization_stats() {
+        let discard = Pattern::Discard { ty: ToyTy::Bool };
+
+        // match p { pair(true, true) => {}, pair(_, _) => {} }
+        // 2つ目のアームについて、1つ目のアームとの差を冗長性の判定で再び計算する。
+        let match_expression = MatchExpression {
+            condition_ty: ToyTy::Constructor("pair"),
+            arms: vec![
+                MatchArm {
+                    pattern: toy_pattern(
+                        "pair",
+                        vec![toy_pattern("true", vec![]), toy_pattern("true", vec![])],
+                    ),
+                },
+                MatchArm {
+                    pattern: toy_pattern("pair", vec![discard.clone(), discard]),
+                },
+            ],
+        };
+
+        let stats = check_match(&match_expression, &Toy).stats;
+        assert!(stats.subtraction.hits > 0, "{:?}", stats);
+        assert!(stats.subtraction.misses > 0, "{:?}", stats);
+        assert!(stats.emptiness.hits > 0, "{:?}", stats);
+    }
+
+    #[test]
+    fn test_covers_counts_in_shared_budget() {
+        let td = new_ty_database();
+        let true_pattern = Pattern::Constructor {
+            name: "True".to_string(),
+            args: vec![],
+        };
+        let discard = Pattern::Discard {
+            ty: Ty::Enum {
+                name: "Boolean".to_string(),
+            },
+        };
+
+        let mut cx = SpaceCx::new(&td);
+        assert!(covers(&discard, &true_pattern, &mut cx));
+        assert!(!covers(&true_pattern, &discard, &mut cx));
+
+        // 上限に達した後は、覆っていても false になる。(呼び出しごとに上限が戻ったりはしない。)
+        let budget = Budget {
+            max_steps: 1,
+            ..Budget::default()
+        };
+        let mut cx = SpaceCx::with_budget(&td, budget);
+        assert!(!covers(&discard, &true_pattern, &mut cx));
+        assert_eq!(cx.budget.exceeded(), Some(BudgetExceeded::Steps));
+        assert!(!covers(&true_pattern, &true_pattern, &mut cx));
+    }
+
+    #[test]
+    fn test_budget_exceeded() {
+        let td = new_ty_database();
+        let boolean_ty = Ty::Enum {
+            name: "Boolean".to_string(),
+        };
+        let true_arm = MatchArm {
+            pattern: Pattern::Constructor {
+                name: "True".to_string(),
+                args: vec![],
+            },
+        };
+        let budget = Budget {
+            max_steps: 1,
+            ..Budget::default()
+        };
+
+        // match bool_value { true => {} }
+        // Boolean を分解した後の差を計算できないので、判定できない。
+        let mut match_expression = MatchExpression {
+            condition_ty: boolean_ty.clone(),
+            arms: vec![true_arm.clone()],
+        };
+        let result = check_match_with_budget(&match_expression, &td, budget);
+        assert_eq!(result.verdict, Verdict::Undetermined(BudgetExceeded::Steps));
+        assert!(!result.is_exhaustive);
+        assert!(result.witnesses.is_empty());
+
+        let budget = Budget {
+            max_union_width: 1,
+            ..Budget::default()
+        };
+        let result = check_match_with_budget(&match_expression, &td, budget);
+        assert_eq!(
+            result.verdict,
+            Verdict::Undetermined(BudgetExceeded::UnionWidth)
+        );
+
+        // match bool_value { _ => {}, true => {} }
+        // 上限に達しても、差が空になったなら網羅的といえる。
+        match_expression.arms.insert(
+            0,
+            MatchArm {
+                pattern: Pattern::Discard { ty: boolean_ty },
+            },
+        );
+        let budget = Budget {
+            max_steps: 2,
+            ..Budget::default()
+        };
+        let result = check_match_with_budget(&match_expression, &td, budget);
+        assert_eq!(result.verdict, Verdict::Exhaustive);
+    }
+
+    #[test]
+    fn test_redundant_arms() {
+        let td = new_ty_database();
+        let boolean_ty = Ty::Enum {
+            name: "Boolean".to_string(),
+        };
+        let true_pattern = Pattern::Constructor {
+            name: "True".to_string(),
+            args: vec![],
+        };
+
+        // match bool_value { true => {}, _ => {}, true => {} }
+        let match_expression = MatchExpression {
+            condition_ty: boolean_ty.clone(),
+            arms: vec![
+                MatchArm {
+                    pattern: true_pattern.clone(),
+                },
+                MatchArm {
+                    pattern: Pattern::Discard { ty: boolean_ty },
+                },
+                MatchArm {
+                    pattern: true_pattern,
+                },
+            ],
+        };
+
+        assert_eq!(
+            redundant_arms(&match_expression, &mut SpaceCx::new(&td)),
+            vec![2]
+        );
+    }
 }
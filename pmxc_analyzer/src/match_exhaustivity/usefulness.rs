@@ -0,0 +1,411 @@
+//! 有用性 (usefulness) に基づく網羅性検査
+//!
+//! OCaml や rustc が使っている、パターン行列を使うアルゴリズム。
+//! (L. Maranget, "Warnings for pattern matching", 2007)
+//! スペースに基づくアルゴリズムと比べるために、同じ入力を受け取って同じ形の結果を返す。
+//!
+//! パターンの行 q が行列 P に対して有用であるとは、P のどの行にもマッチせず q にマッチする値があること。
+//!
+//! - match 式が網羅的 ⇔ アームを並べた行列に対して `_` が有用でない。
+//! - i 番目のアームが冗長 ⇔ それより前のアームを並べた行列に対して、そのアームが有用でない。
+//!
+//! 有用性は、行列と行の先頭の列をコンストラクタごとに特殊化して再帰的に調べる。
+//! 計算量の上限は手順数と再帰の深さだけを数える。(ユニオンスペースは作らない。)
+
+use super::budget::{Budget, BudgetMeter};
+use super::space_from_ty::TySystem;
+use super::use_cases::{CheckResult, Checker, Verdict};
+use super::*;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// 有用性に基づくアルゴリズム
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UsefulnessChecker {
+    pub budget: Budget,
+}
+
+impl Checker for UsefulnessChecker {
+    fn check<S: TySystem>(
+        &self,
+        expression: &MatchExpression<S::Ty>,
+        td: &S,
+    ) -> CheckResult<S::Ty> {
+        let mut cx = UsefulnessCx::new(td, self.budget);
+        let ty = &expression.condition_ty;
+
+        // 型が合わないパターンはどの値にもマッチしないので、行列に含めない。
+        let arms = expression
+            .arms
+            .iter()
+            .map(|arm| lower_pattern(&arm.pattern, ty, &mut cx))
+            .collect::<Vec<_>>();
+        let rows = arms
+            .iter()
+            .flatten()
+            .map(|pat| vec![pat.clone()])
+            .collect::<Vec<_>>();
+
+        let tys = vec![ty.clone()];
+        let witness_opt = usefulness(&rows, &[Pat::Wild], &tys, &mut cx);
+        let verdict = match witness_opt {
+            None => Verdict::Exhaustive,
+            Some(_) => match cx.budget.exceeded() {
+                Some(exceeded) => Verdict::Undetermined(exceeded),
+                None => Verdict::NonExhaustive,
+            },
+        };
+        let witnesses = match (verdict, witness_opt) {
+            (Verdict::NonExhaustive, Some(mut witness)) => vec![witness.remove(0)],
+            _ => vec![],
+        };
+
+        let mut redundant_arms = vec![];
+        let mut previous_rows = vec![];
+        for (i, pat_opt) in arms.into_iter().enumerate() {
+            let pat = match pat_opt {
+                Some(pat) => pat,
+                None => continue,
+            };
+
+            let row = vec![pat];
+            if usefulness(&previous_rows, &row, &tys, &mut cx).is_none() {
+                redundant_arms.push(i);
+            }
+            previous_rows.push(row);
+        }
+
+        CheckResult {
+            verdict,
+            is_exhaustive: verdict == Verdict::Exhaustive,
+            witnesses,
+            redundant_arms,
+            stats: CheckStats::default(),
+        }
+    }
+}
+
+/// 行列の要素になるパターン
+#[derive(Clone, Debug)]
+enum Pat {
+    Wild,
+    Constructor {
+        name: Rc<str>,
+        args: Vec<Pat>,
+    },
+
+    /// いずれかにマッチする。(型より狭い型の `_` を表すのに使う。)
+    Or(Vec<Pat>),
+}
+
+/// 値を持つコンストラクタ
+struct ConstructorInfo<T> {
+    name: Rc<str>,
+    arg_tys: Vec<T>,
+}
+
+/// 型の値を持つコンストラクタの一覧
+type Constructors<T> = Rc<[ConstructorInfo<T>]>;
+
+/// 型システムへの問い合わせの結果を覚えておくもの
+struct UsefulnessCx<'a, S: TySystem> {
+    ts: &'a S,
+
+    /// 型から、その型の値を持つコンストラクタの一覧への対応。(分解できない型は None。)
+    constructors: HashMap<S::Ty, Option<Constructors<S::Ty>>>,
+
+    budget: BudgetMeter,
+}
+
+impl<'a, S: TySystem> UsefulnessCx<'a, S> {
+    fn new(ts: &'a S, budget: Budget) -> Self {
+        UsefulnessCx {
+            ts,
+            constructors: HashMap::new(),
+            budget: BudgetMeter::new(budget),
+        }
+    }
+
+    /// 型の値を持つコンストラクタを列挙する。コンストラクタの集合が分からない型なら None を返す。
+    fn constructors(&mut self, ty: &S::Ty) -> Option<Constructors<S::Ty>> {
+        if let Some(constructors) = self.constructors.get(ty) {
+            return constructors.clone();
+        }
+
+        let mut constructors = vec![];
+        let result = if self.collect_constructors(ty, &mut constructors) {
+            Some(Rc::from(constructors))
+        } else {
+            None
+        };
+        self.constructors.insert(ty.clone(), result.clone());
+        result
+    }
+
+    fn collect_constructors(
+        &self,
+        ty: &S::Ty,
+        constructors: &mut Vec<ConstructorInfo<S::Ty>>,
+    ) -> bool {
+        if !self.ts.can_decompose(ty) {
+            return false;
+        }
+
+        match self.ts.decompose(ty) {
+            Decomposition::Union(tys) => tys
+                .iter()
+                .all(|ty| self.collect_constructors(ty, constructors)),
+            Decomposition::Constructor { name, arg_tys } => {
+                if self.ts.is_inhabited(ty) {
+                    constructors.push(ConstructorInfo {
+                        name: Rc::from(name),
+                        arg_tys,
+                    });
+                }
+                true
+            }
+        }
+    }
+
+    /// 型 `ty` に含まれるコンストラクタ `name` の引数の型を求める。(値を持たないコンストラクタも探す。)
+    fn constructor_arg_tys(&self, ty: &S::Ty, name: &str) -> Option<Vec<S::Ty>> {
+        if !self.ts.can_decompose(ty) {
+            return None;
+        }
+
+        match self.ts.decompose(ty) {
+            Decomposition::Union(tys) => {
+                tys.iter().find_map(|ty| self.constructor_arg_tys(ty, name))
+            }
+            Decomposition::Constructor {
+                name: ty_name,
+                arg_tys,
+            } if ty_name == name => Some(arg_tys),
+            Decomposition::Constructor { .. } => None,
+        }
+    }
+}
+
+/// パターンを型 `ty` の列の要素に変換する。型が合わないなら None を返す。
+fn lower_pattern<S: TySystem>(
+    pattern: &Pattern<S::Ty>,
+    ty: &S::Ty,
+    cx: &mut UsefulnessCx<S>,
+) -> Option<Pat> {
+    match pattern {
+        Pattern::Discard { ty: pattern_ty } => {
+            if pattern_ty == ty || cx.ts.is_subtype_of(ty, pattern_ty) {
+                return Some(Pat::Wild);
+            }
+
+            // 列の型より狭い型の `_` は、その型のコンストラクタのいずれか。
+            if !cx.ts.is_subtype_of(pattern_ty, ty) {
+                return None;
+            }
+            let constructors = cx.constructors(pattern_ty)?;
+            Some(Pat::Or(
+                constructors
+                    .iter()
+                    .map(|constructor| Pat::Constructor {
+                        name: constructor.name.clone(),
+                        args: vec![Pat::Wild; constructor.arg_tys.len()],
+                    })
+                    .collect(),
+            ))
+        }
+        Pattern::Constructor { name, args } => {
+            let arg_tys = cx.constructor_arg_tys(ty, name)?;
+            if arg_tys.len() != args.len() {
+                return None;
+            }
+
+            let args = args
+                .iter()
+                .zip(arg_tys.iter())
+                .map(|(arg, arg_ty)| lower_pattern(arg, arg_ty, cx))
+                .collect::<Option<Vec<_>>>()?;
+            Some(Pat::Constructor {
+                name: Rc::from(name.as_str()),
+                args,
+            })
+        }
+    }
+}
+
+/// 行 `row` が行列 `rows` に対して有用なら、`rows` のどの行にもマッチせず `row` にマッチする値の例を返す。
+///
+/// `tys` は各列の型。計算量の上限に達したら、有用とみなして `_` を並べたものを返す。
+/// (そのため、有用でないという判定は常に正しい。)
+fn usefulness<S: TySystem>(
+    rows: &[Vec<Pat>],
+    row: &[Pat],
+    tys: &[S::Ty],
+    cx: &mut UsefulnessCx<S>,
+) -> Option<Vec<Pattern<S::Ty>>> {
+    debug_assert_eq!(row.len(), tys.len());
+
+    if !cx.budget.enter() {
+        return Some(
+            tys.iter()
+                .map(|ty| Pattern::Discard { ty: ty.clone() })
+                .collect(),
+        );
+    }
+    let result = usefulness_uncounted(rows, row, tys, cx);
+    cx.budget.leave();
+    result
+}
+
+fn usefulness_uncounted<S: TySystem>(
+    rows: &[Vec<Pat>],
+    row: &[Pat],
+    tys: &[S::Ty],
+    cx: &mut UsefulnessCx<S>,
+) -> Option<Vec<Pattern<S::Ty>>> {
+    // 列がなければ、行列が空のときだけ有用。
+    let (head, rest) = match row.split_first() {
+        Some(x) => x,
+        None => return if rows.is_empty() { Some(vec![]) } else { None },
+    };
+    let ty = &tys[0];
+
+    match head {
+        // いずれかの選択肢が有用なら有用。
+        Pat::Or(alternatives) => alternatives.iter().find_map(|alternative| {
+            let row = prepend(vec![alternative.clone()], rest);
+            usefulness(rows, &row, tys, cx)
+        }),
+
+        Pat::Constructor { name, args } => {
+            let arg_tys = cx.constructor_arg_tys(ty, name).unwrap_or_default();
+            specialized_usefulness(rows, name, args, &arg_tys, rest, tys, cx)
+        }
+
+        Pat::Wild => {
+            let mut head_names = vec![];
+            for row in rows.iter() {
+                collect_head_names(&row[0], &mut head_names);
+            }
+
+            match cx.constructors(ty) {
+                // 先頭の列に値を持つコンストラクタがすべて現れているなら、コンストラクタごとに調べる。
+                Some(constructors)
+                    if constructors
+                        .iter()
+                        .all(|constructor| head_names.contains(&constructor.name)) =>
+                {
+                    constructors.iter().find_map(|constructor| {
+                        let args = vec![Pat::Wild; constructor.arg_tys.len()];
+                        specialized_usefulness(
+                            rows,
+                            &constructor.name,
+                            &args,
+                            &constructor.arg_tys,
+                            rest,
+                            tys,
+                            cx,
+                        )
+                    })
+                }
+
+                // 現れていないコンストラクタがあるなら、先頭が `_` の行だけを見ればよい。
+                constructors_opt => {
+                    let default_rows = rows
+                        .iter()
+                        .filter(|row| matches!(row[0], Pat::Wild))
+                        .map(|row| row[1..].to_vec())
+                        .collect::<Vec<_>>();
+                    let witness = usefulness(&default_rows, rest, &tys[1..], cx)?;
+
+                    // 例の先頭は、現れていないコンストラクタにする。
+                    let missing_opt = constructors_opt.and_then(|constructors| {
+                        constructors
+                            .iter()
+                            .find(|constructor| !head_names.contains(&constructor.name))
+                            .map(|constructor| Pattern::Constructor {
+                                name: constructor.name.to_string(),
+                                args: constructor
+                                    .arg_tys
+                                    .iter()
+                                    .map(|ty| Pattern::Discard { ty: ty.clone() })
+                                    .collect(),
+                            })
+                    });
+                    let head = match missing_opt {
+                        Some(pattern) if !head_names.is_empty() => pattern,
+                        _ => Pattern::Discard { ty: ty.clone() },
+                    };
+                    Some(prepend(vec![head], &witness))
+                }
+            }
+        }
+    }
+}
+
+/// 行列と行の先頭の列をコンストラクタ `name` で特殊化して有用性を調べる。
+fn specialized_usefulness<S: TySystem>(
+    rows: &[Vec<Pat>],
+    name: &Rc<str>,
+    args: &[Pat],
+    arg_tys: &[S::Ty],
+    rest: &[Pat],
+    tys: &[S::Ty],
+    cx: &mut UsefulnessCx<S>,
+) -> Option<Vec<Pattern<S::Ty>>> {
+    let arity = args.len();
+
+    let mut specialized_rows = vec![];
+    for row in rows.iter() {
+        specialize(&row[0], &row[1..], name, arity, &mut specialized_rows);
+    }
+
+    let row = prepend(args.to_vec(), rest);
+    let tys = prepend(arg_tys.to_vec(), &tys[1..]);
+    let witness = usefulness(&specialized_rows, &row, &tys, cx)?;
+
+    let mut witness = witness.into_iter();
+    let head = Pattern::Constructor {
+        name: name.to_string(),
+        args: witness.by_ref().take(arity).collect(),
+    };
+    Some(prepend(vec![head], &witness.collect::<Vec<_>>()))
+}
+
+/// 先頭の列が `head` の行を、コンストラクタ `name` で特殊化した行を追加する。
+fn specialize(head: &Pat, rest: &[Pat], name: &str, arity: usize, rows: &mut Vec<Vec<Pat>>) {
+    match head {
+        Pat::Wild => rows.push(prepend(vec![Pat::Wild; arity], rest)),
+        Pat::Constructor {
+            name: head_name,
+            args,
+        } if **head_name == *name => rows.push(prepend(args.clone(), rest)),
+        Pat::Constructor { .. } => {}
+        Pat::Or(alternatives) => {
+            for alternative in alternatives.iter() {
+                specialize(alternative, rest, name, arity, rows);
+            }
+        }
+    }
+}
+
+/// パターンの先頭に現れるコンストラクタの名前を集める。
+fn collect_head_names(head: &Pat, names: &mut Vec<Rc<str>>) {
+    match head {
+        Pat::Wild => {}
+        Pat::Constructor { name, .. } => {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        Pat::Or(alternatives) => {
+            for alternative in alternatives.iter() {
+                collect_head_names(alternative, names);
+            }
+        }
+    }
+}
+
+fn prepend<T: Clone>(mut head: Vec<T>, rest: &[T]) -> Vec<T> {
+    head.extend_from_slice(rest);
+    head
+}
@@ -0,0 +1,357 @@
+//! メッセージカタログ
+//!
+//! 診断やコマンドラインツールが表示する文章を言語ごとに定義する。
+//! 診断は文章そのものではなく [`Message`] を持ち、表示するときに言語を選ぶ。
+
+use crate::match_exhaustivity::BudgetExceeded;
+use crate::syntax::ParseError;
+
+/// 表示する言語
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) enum Locale {
+    #[default]
+    Ja,
+    En,
+}
+
+impl Locale {
+    /// `ja`, `en-US`, `ja_JP.UTF-8` などの言語タグを解釈する。(知らない言語なら None。)
+    pub(crate) fn from_tag(tag: &str) -> Option<Locale> {
+        let language = tag
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        match language.as_str() {
+            "ja" => Some(Locale::Ja),
+            "en" => Some(Locale::En),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Message {
+    // 構文
+    Parse(ParseError),
+
+    // 名前解決・型検査
+    UndefinedTy {
+        name: String,
+    },
+    UndefinedCtor {
+        name: String,
+    },
+    TypeMismatch,
+    CtorOfOtherEnum {
+        ctor_name: String,
+        enum_name: String,
+        expected_ty_name: String,
+    },
+    ArityMismatch {
+        given: usize,
+        expected: usize,
+    },
+    ArityNote {
+        ctor_name: String,
+        arity: usize,
+    },
+    EnumDefinedHere {
+        name: String,
+    },
+
+    // 網羅性検査
+    NonExhaustive {
+        witness_opt: Option<String>,
+    },
+    AlsoNotMatched {
+        pattern: String,
+    },
+    UnreachableArm,
+    CoveredByArm,
+    Undetermined,
+    BudgetExceeded {
+        exceeded: BudgetExceeded,
+        limit: usize,
+    },
+    AddMissingArms,
+    RemoveArm,
+
+    // 名前の変更 (エディタの機能なので wasm からだけ使う)
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+    RenameNoSymbol,
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+    RenameInvalidName,
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+    RenameEnumConflict,
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+    RenameCtorConflict,
+
+    // 処理系の不具合
+    InternalError {
+        message: String,
+    },
+    InternalErrorNote,
+
+    // コマンドラインツール
+    CliUsage,
+    CliCannotReadStdin {
+        error: String,
+    },
+    CliCannotReadFile {
+        path: String,
+        error: String,
+    },
+    CliInvalidJson {
+        error: String,
+    },
+    CliInvalidRequest {
+        error: String,
+    },
+    CliLspError {
+        error: String,
+    },
+}
+
+impl Message {
+    pub(crate) fn render(&self, locale: Locale) -> String {
+        match locale {
+            Locale::Ja => self.render_ja(),
+            Locale::En => self.render_en(),
+        }
+    }
+
+    fn render_ja(&self) -> String {
+        match self {
+            Message::Parse(error) => match error {
+                ParseError::ExpectedCommaOrEol => "',' または改行が必要です",
+                ParseError::ExpectedExpr => "式が必要です",
+                ParseError::ExpectedFatArrow => "'=>' が必要です",
+                ParseError::ExpectedIdent => "識別子が必要です",
+                ParseError::ExpectedLeftBrace => "'{' が必要です",
+                ParseError::ExpectedRightBrace => "'}' が必要です",
+                ParseError::ExpectedRightParen => "')' が必要です",
+                ParseError::UnexpectedChars => "解釈できない文字です",
+                ParseError::NestingTooDeep => "入れ子が深すぎます",
+            }
+            .to_string(),
+            Message::UndefinedTy { name } => format!("型 {} は定義されていません", name),
+            Message::UndefinedCtor { name } => {
+                format!("コンストラクタ {} は定義されていません", name)
+            }
+            Message::TypeMismatch => "型が異なります".to_string(),
+            Message::CtorOfOtherEnum {
+                ctor_name,
+                enum_name,
+                expected_ty_name,
+            } => format!(
+                "{} は {} のコンストラクタですが、{} 型の値が期待されています",
+                ctor_name, enum_name, expected_ty_name
+            ),
+            Message::ArityMismatch { given, expected } => {
+                format!("引数の数が異なります ({} → {})", given, expected)
+            }
+            Message::ArityNote { ctor_name, arity } => {
+                format!("{} の引数は {} 個です", ctor_name, arity)
+            }
+            Message::EnumDefinedHere { name } => format!("enum {} はここで定義されています", name),
+            Message::NonExhaustive {
+                witness_opt: Some(witness),
+            } => format!("網羅的ではありません (例: {})", witness),
+            Message::NonExhaustive { witness_opt: None } => "網羅的ではありません".to_string(),
+            Message::AlsoNotMatched { pattern } => format!("{} にもマッチしません", pattern),
+            Message::UnreachableArm => "このアームにマッチする値はありません".to_string(),
+            Message::CoveredByArm => "先にこのアームがマッチします".to_string(),
+            Message::Undetermined => "網羅的かどうか判定できませんでした".to_string(),
+            Message::BudgetExceeded { exceeded, limit } => match exceeded {
+                BudgetExceeded::Steps => {
+                    format!("スペースの演算の回数が上限 ({}) に達しました", limit)
+                }
+                BudgetExceeded::Depth => {
+                    format!("スペースの演算の再帰の深さが上限 ({}) に達しました", limit)
+                }
+                BudgetExceeded::UnionWidth => {
+                    format!("ユニオンスペースの要素数が上限 ({}) に達しました", limit)
+                }
+            },
+            Message::AddMissingArms => "足りないアームを追加する".to_string(),
+            Message::RemoveArm => "アームを削除する".to_string(),
+            Message::RenameNoSymbol => "名前を変更できる位置ではありません".to_string(),
+            Message::RenameInvalidName => "識別子として正しくない名前です".to_string(),
+            Message::RenameEnumConflict => "同じ名前の enum がすでに定義されています".to_string(),
+            Message::RenameCtorConflict => {
+                "同じ名前のコンストラクタがすでに定義されています".to_string()
+            }
+            Message::InternalError { message } => format!("内部エラーが発生しました: {}", message),
+            Message::InternalErrorNote => {
+                "これは pmxc の不具合です。入力したソースコードを添えて報告してください".to_string()
+            }
+            Message::CliUsage => "\
+使い方:
+    pmxc check [OPTIONS] [FILE]    ソースコードを検査する
+    pmxc check-json [FILE]         JSON で書かれた問題を検査する
+    pmxc lsp                       LSP のサーバーを起動する (標準入出力で通信する)
+    pmxc help                      この説明を表示する
+
+オプション:
+    --locale LOCALE    メッセージの言語 (ja, en)
+    --color WHEN       色を付けるか (auto, always, never)
+    --format FORMAT    出力形式 (human, json, sarif)
+
+FILE が - または省略されたときは標準入力を読む。
+LOCALE の省略時は環境変数 LANG などに従う。
+WHEN が auto のときは端末に出力する場合だけ色を付ける。
+
+終了コード:
+    0    エラーなし (すべての match 式が網羅的)
+    1    エラーあり (網羅的でない match 式がある)
+    2    入力やコマンドライン引数の誤り
+"
+            .to_string(),
+            Message::CliCannotReadStdin { error } => format!("標準入力を読めません: {}", error),
+            Message::CliCannotReadFile { path, error } => {
+                format!("{} を読めません: {}", path, error)
+            }
+            Message::CliInvalidJson { error } => format!("JSON を解釈できません: {}", error),
+            Message::CliInvalidRequest { error } => format!("検査の依頼が不正です: {}", error),
+            Message::CliLspError { error } => {
+                format!("LSP のサーバーでエラーが起きました: {}", error)
+            }
+        }
+    }
+
+    fn render_en(&self) -> String {
+        match self {
+            Message::Parse(error) => match error {
+                ParseError::ExpectedCommaOrEol => "expected ',' or a line break",
+                ParseError::ExpectedExpr => "expected an expression",
+                ParseError::ExpectedFatArrow => "expected '=>'",
+                ParseError::ExpectedIdent => "expected an identifier",
+                ParseError::ExpectedLeftBrace => "expected '{'",
+                ParseError::ExpectedRightBrace => "expected '}'",
+                ParseError::ExpectedRightParen => "expected ')'",
+                ParseError::UnexpectedChars => "unexpected characters",
+                ParseError::NestingTooDeep => "nesting is too deep",
+            }
+            .to_string(),
+            Message::UndefinedTy { name } => format!("type {} is not defined", name),
+            Message::UndefinedCtor { name } => format!("constructor {} is not defined", name),
+            Message::TypeMismatch => "mismatched types".to_string(),
+            Message::CtorOfOtherEnum {
+                ctor_name,
+                enum_name,
+                expected_ty_name,
+            } => format!(
+                "{} is a constructor of {}, but a value of type {} is expected",
+                ctor_name, enum_name, expected_ty_name
+            ),
+            Message::ArityMismatch { given, expected } => format!(
+                "wrong number of arguments (given {}, expected {})",
+                given, expected
+            ),
+            Message::ArityNote { ctor_name, arity } => {
+                format!("{} takes {} argument(s)", ctor_name, arity)
+            }
+            Message::EnumDefinedHere { name } => format!("enum {} is defined here", name),
+            Message::NonExhaustive {
+                witness_opt: Some(witness),
+            } => format!("non-exhaustive patterns: {} not covered", witness),
+            Message::NonExhaustive { witness_opt: None } => "non-exhaustive patterns".to_string(),
+            Message::AlsoNotMatched { pattern } => format!("{} is not covered either", pattern),
+            Message::UnreachableArm => "unreachable arm: no value matches it".to_string(),
+            Message::CoveredByArm => "this arm matches first".to_string(),
+            Message::Undetermined => "could not determine exhaustiveness".to_string(),
+            Message::BudgetExceeded { exceeded, limit } => match exceeded {
+                BudgetExceeded::Steps => {
+                    format!(
+                        "the checker reached the limit of {} space operations",
+                        limit
+                    )
+                }
+                BudgetExceeded::Depth => {
+                    format!("the checker reached the recursion depth limit of {}", limit)
+                }
+                BudgetExceeded::UnionWidth => {
+                    format!("a union of spaces reached the size limit of {}", limit)
+                }
+            },
+            Message::AddMissingArms => "Add missing arms".to_string(),
+            Message::RemoveArm => "Remove the arm".to_string(),
+            Message::RenameNoSymbol => "this is not a name that can be renamed".to_string(),
+            Message::RenameInvalidName => "the new name is not a valid identifier".to_string(),
+            Message::RenameEnumConflict => {
+                "an enum with the same name is already defined".to_string()
+            }
+            Message::RenameCtorConflict => {
+                "a constructor with the same name is already defined".to_string()
+            }
+            Message::InternalError { message } => format!("internal error: {}", message),
+            Message::InternalErrorNote => {
+                "this is a bug in pmxc; please report it with the source code".to_string()
+            }
+            Message::CliUsage => "\
+Usage:
+    pmxc check [OPTIONS] [FILE]    check source code
+    pmxc check-json [FILE]         check a problem written in JSON
+    pmxc lsp                       start an LSP server on standard input/output
+    pmxc help                      show this help
+
+Options:
+    --locale LOCALE    language of messages (ja, en)
+    --color WHEN       whether to color the output (auto, always, never)
+    --format FORMAT    output format (human, json, sarif)
+
+Reads standard input when FILE is - or omitted.
+LOCALE defaults to the LANG environment variable and friends.
+WHEN=auto colors the output only when it is a terminal.
+
+Exit status:
+    0    no errors (every match is exhaustive)
+    1    errors found (some match is not exhaustive)
+    2    invalid input or command-line arguments
+"
+            .to_string(),
+            Message::CliCannotReadStdin { error } => {
+                format!("cannot read standard input: {}", error)
+            }
+            Message::CliCannotReadFile { path, error } => {
+                format!("cannot read {}: {}", path, error)
+            }
+            Message::CliInvalidJson { error } => format!("invalid JSON: {}", error),
+            Message::CliInvalidRequest { error } => format!("invalid request: {}", error),
+            Message::CliLspError { error } => format!("LSP server error: {}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_from_tag() {
+        assert_eq!(Locale::from_tag("ja"), Some(Locale::Ja));
+        assert_eq!(Locale::from_tag("en-US"), Some(Locale::En));
+        assert_eq!(Locale::from_tag("ja_JP.UTF-8"), Some(Locale::Ja));
+        assert_eq!(Locale::from_tag("C"), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let message = Message::NonExhaustive {
+            witness_opt: Some("False".to_string()),
+        };
+        assert_eq!(
+            message.render(Locale::Ja),
+            "網羅的ではありません (例: False)"
+        );
+        assert_eq!(
+            message.render(Locale::En),
+            "non-exhaustive patterns: False not covered"
+        );
+
+        let message = Message::Parse(ParseError::ExpectedRightBrace);
+        assert_eq!(message.render(Locale::En), "expected '}'");
+    }
+}
@@ -0,0 +1,337 @@
+//! 診断を端末向けに整形する機能
+//!
+//! rustc のように、診断の範囲を含む行を引用して下線を引く。
+//!
+//! ```text
+//! error[E0001]: non-exhaustive patterns: False not covered
+//!  --> check.pmxclang:5:1
+//!   |
+//! 1 | enum Boolean {
+//!   |      ------- enum Boolean is defined here
+//! ...
+//! 5 | match True {
+//!   | ^^^^^
+//!   |
+//!   = note: ...
+//! ```
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::messages::{Locale, Message};
+use crate::syntax::TextRange;
+use std::fmt::Write;
+
+const TAB_WIDTH: usize = 4;
+
+const STYLE_ERROR: &str = "\x1b[1;31m";
+const STYLE_WARNING: &str = "\x1b[1;33m";
+const STYLE_GUTTER: &str = "\x1b[1;34m";
+const STYLE_BOLD: &str = "\x1b[1m";
+const STYLE_RESET: &str = "\x1b[0m";
+
+/// 端末で2文字分の幅を持つ文字か？ (東アジアの文字や絵文字を大まかに判定する。)
+fn is_wide(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1100..=0x115F
+            | 0x2E80..=0x303E
+            | 0x3041..=0x33FF
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xA000..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x1F300..=0x1F64F
+            | 0x1F900..=0x1F9FF
+            | 0x20000..=0x3FFFD
+    )
+}
+
+fn char_width(c: char) -> usize {
+    match c {
+        '\t' => TAB_WIDTH,
+        _ if is_wide(c) => 2,
+        _ => 1,
+    }
+}
+
+/// 行の中の列 (UTF-16 単位、1 から始まる) を、表示上の桁 (0 から始まる) に変換する。
+fn display_column(line: &str, character: usize) -> usize {
+    let mut index = 1;
+    let mut column = 0;
+    for c in line.chars() {
+        if index >= character {
+            break;
+        }
+        index += c.len_utf16();
+        column += char_width(c);
+    }
+    column
+}
+
+/// タブを空白に展開する。(下線の位置と揃えるため。)
+fn expand_tabs(line: &str) -> String {
+    line.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+/// 引用した行に付ける下線
+struct Label<'a> {
+    line: usize,
+    start: usize,
+    end: usize,
+    is_primary: bool,
+    message_opt: Option<&'a Message>,
+}
+
+pub(crate) struct Renderer<'a> {
+    path: &'a str,
+    lines: Vec<&'a str>,
+    locale: Locale,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub(crate) fn new(path: &'a str, source_code: &'a str, locale: Locale, color: bool) -> Self {
+        let lines = source_code
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .collect();
+
+        Renderer {
+            path,
+            lines,
+            locale,
+            color,
+        }
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, STYLE_RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn line_text(&self, line: usize) -> &'a str {
+        self.lines.get(line - 1).copied().unwrap_or("")
+    }
+
+    /// 範囲に対する下線を作る。(複数行にまたがる範囲は、最初の行の末尾までとする。)
+    fn label(
+        &self,
+        range: TextRange,
+        is_primary: bool,
+        message_opt: Option<&'a Message>,
+    ) -> Label<'a> {
+        let (start, end) = (range.start(), range.end());
+        let text = self.line_text(start.line());
+
+        let start_column = display_column(text, start.character());
+        let end_column = if end.line() == start.line() {
+            display_column(text, end.character())
+        } else {
+            display_column(text, usize::MAX)
+        };
+
+        Label {
+            line: start.line(),
+            start: start_column,
+            end: end_column.max(start_column + 1),
+            is_primary,
+            message_opt,
+        }
+    }
+
+    /// 診断を複数行の文字列にする。(末尾に空行を含む。)
+    pub(crate) fn render(&self, diagnostic: &'a Diagnostic) -> String {
+        let severity_style = match diagnostic.severity {
+            Severity::Error => STYLE_ERROR,
+            Severity::Warning => STYLE_WARNING,
+        };
+
+        let mut labels = vec![self.label(diagnostic.range, true, None)];
+        for related in diagnostic.related.iter() {
+            labels.push(self.label(related.range, false, Some(&related.message)));
+        }
+        labels.sort_by_key(|label| (label.line, !label.is_primary, label.start));
+
+        let gutter_width = labels
+            .iter()
+            .map(|label| label.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let blank_gutter = " ".repeat(gutter_width);
+        let bar = self.paint(STYLE_GUTTER, "|");
+
+        let mut out = String::new();
+        let header = format!(
+            "{}[{}]",
+            diagnostic.severity.as_str(),
+            diagnostic.code.code()
+        );
+        writeln!(
+            out,
+            "{}{}",
+            self.paint(severity_style, &header),
+            self.paint(
+                STYLE_BOLD,
+                &format!(": {}", diagnostic.message.render(self.locale))
+            )
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "{}{} {}:{}",
+            blank_gutter,
+            self.paint(STYLE_GUTTER, "-->"),
+            self.path,
+            diagnostic.range.start()
+        )
+        .unwrap();
+        writeln!(out, "{} {}", blank_gutter, bar).unwrap();
+
+        let mut last_line_opt: Option<usize> = None;
+        for label in labels.iter() {
+            if last_line_opt != Some(label.line) {
+                if last_line_opt.is_some_and(|last| label.line > last + 1) {
+                    writeln!(out, "{}", self.paint(STYLE_GUTTER, "...")).unwrap();
+                }
+
+                let line_number = format!("{:>width$}", label.line, width = gutter_width);
+                writeln!(
+                    out,
+                    "{} {} {}",
+                    self.paint(STYLE_GUTTER, &line_number),
+                    bar,
+                    expand_tabs(self.line_text(label.line))
+                )
+                .unwrap();
+                last_line_opt = Some(label.line);
+            }
+
+            let (mark, style) = if label.is_primary {
+                ("^", severity_style)
+            } else {
+                ("-", STYLE_GUTTER)
+            };
+            let mut underline = mark.repeat(label.end - label.start);
+            if let Some(message) = label.message_opt {
+                write!(underline, " {}", message.render(self.locale)).unwrap();
+            }
+            writeln!(
+                out,
+                "{} {} {}{}",
+                blank_gutter,
+                bar,
+                " ".repeat(label.start),
+                self.paint(style, &underline)
+            )
+            .unwrap();
+        }
+
+        if !diagnostic.notes.is_empty() {
+            writeln!(out, "{} {}", blank_gutter, bar).unwrap();
+        }
+        for note in diagnostic.notes.iter() {
+            writeln!(
+                out,
+                "{} {} {}",
+                blank_gutter,
+                self.paint(STYLE_GUTTER, "="),
+                self.paint(STYLE_BOLD, &format!("note: {}", note.render(self.locale)))
+            )
+            .unwrap();
+        }
+
+        out.push('\n');
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::{collect_diagnostics, DiagnosticCode};
+    use crate::syntax::TextPosition;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_render_with_related_and_notes() {
+        let source_code = "\
+enum Boolean {
+    True,
+    False,
+}
+
+enum Option {
+    None,
+    Some(Boolean),
+}
+
+match None {
+    Some(True) => {}
+}
+";
+        let diagnostics = collect_diagnostics(Rc::new(source_code.to_string()));
+        let renderer = Renderer::new("a.pmxclang", source_code, Locale::En, false);
+        let output = renderer.render(&diagnostics[0]);
+
+        assert_eq!(
+            output,
+            "\
+error[E0001]: non-exhaustive patterns: None not covered
+  --> a.pmxclang:11:1
+   |
+ 6 | enum Option {
+   |      ------ enum Option is defined here
+...
+11 | match None {
+   | ^^^^^
+   |
+   = note: Some(False) is not covered either
+
+"
+        );
+    }
+
+    #[test]
+    fn test_render_wide_chars() {
+        let source_code = "match x {\n\tあい => {}\n}\n";
+        let range = TextRange::new(TextPosition::new(2, 2), TextPosition::new(2, 4));
+        let diagnostic = Diagnostic::new(
+            DiagnosticCode::UndefinedName,
+            range,
+            Message::UndefinedCtor {
+                name: "あい".to_string(),
+            },
+        );
+
+        let renderer = Renderer::new("a.pmxclang", source_code, Locale::Ja, false);
+        let output = renderer.render(&diagnostic);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[3], "2 |     あい => {}");
+        assert_eq!(lines[4], "  |     ^^^^");
+    }
+
+    #[test]
+    fn test_render_with_color() {
+        let source_code = "match x {\n}\n";
+        let range = TextRange::new(TextPosition::new(1, 7), TextPosition::new(1, 8));
+        let diagnostic = Diagnostic::new(
+            DiagnosticCode::UndefinedName,
+            range,
+            Message::UndefinedCtor {
+                name: "x".to_string(),
+            },
+        );
+
+        let output = Renderer::new("a", source_code, Locale::En, true).render(&diagnostic);
+        assert!(output.starts_with("\x1b[1;31merror[E0101]\x1b[0m"));
+
+        let output = Renderer::new("a", source_code, Locale::En, false).render(&diagnostic);
+        assert!(!output.contains('\x1b'));
+    }
+}
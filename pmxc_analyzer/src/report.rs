@@ -0,0 +1,392 @@
+//! 診断を機械が読める形式 (JSON、SARIF) で出力する機能
+//!
+//! CI などのツールに結果を渡すためのもの。
+//! SARIF は [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html) に従う。
+
+use crate::diagnostic::{Diagnostic, DiagnosticCode, Severity};
+use crate::messages::Locale;
+use crate::syntax::{TextPosition, TextRange};
+use serde::Serialize;
+
+// -----------------------------------------------
+// JSON
+// -----------------------------------------------
+
+/// 1つのファイルに関する診断の一覧
+#[derive(Serialize)]
+pub(crate) struct JsonReport<'a> {
+    path: &'a str,
+    diagnostics: Vec<JsonDiagnostic<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    code: &'static str,
+    name: &'static str,
+    severity: &'static str,
+    message: String,
+    range: JsonRange,
+    related: Vec<JsonRelated>,
+    notes: Vec<String>,
+    witnesses: Vec<&'a str>,
+}
+
+/// 位置 (行と列は 1 から始まり、列は UTF-16 単位)
+#[derive(Serialize)]
+struct JsonPosition {
+    line: usize,
+    character: usize,
+}
+
+#[derive(Serialize)]
+struct JsonRange {
+    start: JsonPosition,
+    end: JsonPosition,
+}
+
+#[derive(Serialize)]
+struct JsonRelated {
+    range: JsonRange,
+    message: String,
+}
+
+impl From<TextPosition> for JsonPosition {
+    fn from(position: TextPosition) -> Self {
+        JsonPosition {
+            line: position.line(),
+            character: position.character(),
+        }
+    }
+}
+
+impl From<TextRange> for JsonRange {
+    fn from(range: TextRange) -> Self {
+        JsonRange {
+            start: range.start().into(),
+            end: range.end().into(),
+        }
+    }
+}
+
+pub(crate) fn json_report<'a>(
+    path: &'a str,
+    diagnostics: &'a [Diagnostic],
+    locale: Locale,
+) -> JsonReport<'a> {
+    let diagnostics = diagnostics
+        .iter()
+        .map(|diagnostic| JsonDiagnostic {
+            code: diagnostic.code.code(),
+            name: diagnostic.code.slug(),
+            severity: diagnostic.severity.as_str(),
+            message: diagnostic.message.render(locale),
+            range: diagnostic.range.into(),
+            related: diagnostic
+                .related
+                .iter()
+                .map(|related| JsonRelated {
+                    range: related.range.into(),
+                    message: related.message.render(locale),
+                })
+                .collect(),
+            notes: diagnostic
+                .notes
+                .iter()
+                .map(|note| note.render(locale))
+                .collect(),
+            witnesses: diagnostic.witnesses(),
+        })
+        .collect();
+
+    JsonReport { path, diagnostics }
+}
+
+// -----------------------------------------------
+// SARIF
+// -----------------------------------------------
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+#[derive(Serialize)]
+pub(crate) struct SarifLog<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRun<'a> {
+    tool: SarifTool,
+    results: Vec<SarifResult<'a>>,
+
+    /// 列の数え方。(TextPosition と同じく UTF-16 単位。)
+    column_kind: &'static str,
+}
+
+#[derive(Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifDriver {
+    name: &'static str,
+    version: &'static str,
+    rules: Vec<SarifRule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRule {
+    id: &'static str,
+    name: &'static str,
+    default_configuration: SarifConfiguration,
+}
+
+#[derive(Serialize)]
+struct SarifConfiguration {
+    level: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult<'a> {
+    rule_id: &'static str,
+    rule_index: usize,
+    level: &'static str,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related_locations: Vec<SarifLocation>,
+
+    properties: SarifProperties<'a>,
+}
+
+#[derive(Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+
+    physical_location: SarifPhysicalLocation,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<SarifMessage>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    region: SarifRegion,
+}
+
+#[derive(Serialize)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+#[derive(Serialize)]
+struct SarifProperties<'a> {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    notes: Vec<String>,
+
+    /// どのアームにもマッチしない値の例
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    witnesses: Vec<&'a str>,
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+fn sarif_location(uri: &str, range: TextRange) -> SarifPhysicalLocation {
+    SarifPhysicalLocation {
+        artifact_location: SarifArtifactLocation {
+            uri: uri.to_string(),
+        },
+        region: SarifRegion {
+            start_line: range.start().line(),
+            start_column: range.start().character(),
+            end_line: range.end().line(),
+            end_column: range.end().character(),
+        },
+    }
+}
+
+/// ファイルのパスを SARIF の URI (相対参照) にする。
+fn path_to_uri(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+pub(crate) fn sarif_report<'a>(
+    path: &str,
+    diagnostics: &'a [Diagnostic],
+    locale: Locale,
+) -> SarifLog<'a> {
+    let uri = path_to_uri(path);
+
+    let rules = DiagnosticCode::ALL
+        .iter()
+        .map(|&code| SarifRule {
+            id: code.code(),
+            name: code.slug(),
+            default_configuration: SarifConfiguration {
+                level: sarif_level(code.severity()),
+            },
+        })
+        .collect();
+
+    let results = diagnostics
+        .iter()
+        .map(|diagnostic| SarifResult {
+            rule_id: diagnostic.code.code(),
+            rule_index: DiagnosticCode::ALL
+                .iter()
+                .position(|&code| code == diagnostic.code)
+                .unwrap(),
+            level: sarif_level(diagnostic.severity),
+            message: SarifMessage {
+                text: diagnostic.message.render(locale),
+            },
+            locations: vec![SarifLocation {
+                id: None,
+                physical_location: sarif_location(&uri, diagnostic.range),
+                message: None,
+            }],
+            related_locations: diagnostic
+                .related
+                .iter()
+                .enumerate()
+                .map(|(i, related)| SarifLocation {
+                    id: Some(i),
+                    physical_location: sarif_location(&uri, related.range),
+                    message: Some(SarifMessage {
+                        text: related.message.render(locale),
+                    }),
+                })
+                .collect(),
+            properties: SarifProperties {
+                notes: diagnostic
+                    .notes
+                    .iter()
+                    .map(|note| note.render(locale))
+                    .collect(),
+                witnesses: diagnostic.witnesses(),
+            },
+        })
+        .collect();
+
+    SarifLog {
+        schema: SARIF_SCHEMA,
+        version: SARIF_VERSION,
+        runs: vec![SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver {
+                    name: "pmxc",
+                    version: env!("CARGO_PKG_VERSION"),
+                    rules,
+                },
+            },
+            results,
+            column_kind: "utf16CodeUnits",
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::collect_diagnostics;
+    use serde_json::{json, Value};
+    use std::rc::Rc;
+
+    const SOURCE_CODE: &str = "\
+enum Boolean {
+    True,
+    False,
+}
+
+enum Option {
+    None,
+    Some(Boolean),
+}
+
+match None {
+    Some(True) => {}
+}
+";
+
+    #[test]
+    fn test_json_report() {
+        let diagnostics = collect_diagnostics(Rc::new(SOURCE_CODE.to_string()));
+        let report = json_report("a.pmxclang", &diagnostics, Locale::En);
+        let report = serde_json::to_value(&report).unwrap();
+
+        let diagnostic = &report["diagnostics"][0];
+        assert_eq!(diagnostic["code"], json!("E0001"));
+        assert_eq!(diagnostic["name"], json!("non-exhaustive"));
+        assert_eq!(
+            diagnostic["range"],
+            json!({ "start": { "line": 11, "character": 1 }, "end": { "line": 11, "character": 6 } })
+        );
+        assert_eq!(diagnostic["witnesses"], json!(["None", "Some(False)"]));
+    }
+
+    #[test]
+    fn test_sarif_report() {
+        let diagnostics = collect_diagnostics(Rc::new(SOURCE_CODE.to_string()));
+        let log = sarif_report("tests\\a.pmxclang", &diagnostics, Locale::En);
+        let log = serde_json::to_value(&log).unwrap();
+        assert_eq!(log["version"], json!("2.1.0"));
+
+        let run = &log["runs"][0];
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], json!("E0001"));
+        assert_eq!(result["level"], json!("error"));
+
+        let rule_index = result["ruleIndex"].as_u64().unwrap() as usize;
+        assert_eq!(
+            run["tool"]["driver"]["rules"][rule_index]["id"],
+            json!("E0001")
+        );
+
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(
+            location["artifactLocation"]["uri"],
+            json!("tests/a.pmxclang")
+        );
+        assert_eq!(location["region"]["startLine"], json!(11));
+        assert_eq!(
+            result["relatedLocations"][0]["message"]["text"],
+            json!("enum Option is defined here")
+        );
+        assert_eq!(
+            result["properties"]["witnesses"],
+            json!(["None", "Some(False)"])
+        );
+        assert!(matches!(result["properties"]["notes"], Value::Array(_)));
+    }
+}
@@ -0,0 +1,207 @@
+//! スナップショットテストのための補助
+//!
+//! `tests/<種類>/*.pmxclang` のそれぞれについて出力を作り、同じディレクトリにある
+//! `<名前>_snapshot.txt` と比較する。一致しなければ差分を表示してテストを失敗させる。
+//!
+//! 環境変数 `UPDATE_SNAPSHOTS=1` を指定してテストを実行すると、比較する代わりにスナップショットを更新する。
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SOURCE_EXT: &str = "pmxclang";
+const SNAPSHOT_SUFFIX: &str = "_snapshot.txt";
+
+/// 差分を表示するときに、変更された行の前後に表示する行数
+const CONTEXT_LINES: usize = 3;
+
+fn should_update() -> bool {
+    env::var("UPDATE_SNAPSHOTS").is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+fn tests_dir(kind: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(kind)
+}
+
+fn snapshot_path(source_path: &Path) -> PathBuf {
+    let stem = source_path.file_stem().unwrap().to_string_lossy();
+    source_path.with_file_name(format!("{}{}", stem, SNAPSHOT_SUFFIX))
+}
+
+/// 行単位の差分の1行
+#[derive(Debug, PartialEq, Eq)]
+enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// 最長共通部分列を使って、行単位の差分を計算する。
+fn diff_lines<'a>(expected: &[&'a str], actual: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let (n, m) = (expected.len(), actual.len());
+
+    // lcs[i][j] = expected[i..] と actual[j..] の最長共通部分列の長さ
+    let mut lcs = vec![vec![0_usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            lines.push(DiffLine::Same(expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(DiffLine::Removed(expected[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(actual[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// unified diff に近い形式で差分を表示する。(変更のない行は前後の数行だけ表示する。)
+fn format_diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+    let lines = diff_lines(&expected, &actual);
+
+    let is_changed = |i: usize| !matches!(lines[i], DiffLine::Same(_));
+    let is_visible = |i: usize| {
+        let start = i.saturating_sub(CONTEXT_LINES);
+        let end = (i + CONTEXT_LINES + 1).min(lines.len());
+        (start..end).any(is_changed)
+    };
+
+    let mut out = String::new();
+    let mut expected_line = 1;
+    let mut skipped = true;
+    for (i, line) in lines.iter().enumerate() {
+        if !is_visible(i) {
+            skipped = true;
+        } else {
+            if skipped {
+                writeln!(out, "@@ line {} @@", expected_line).unwrap();
+                skipped = false;
+            }
+
+            match line {
+                DiffLine::Same(text) => writeln!(out, " {}", text).unwrap(),
+                DiffLine::Removed(text) => writeln!(out, "-{}", text).unwrap(),
+                DiffLine::Added(text) => writeln!(out, "+{}", text).unwrap(),
+            }
+        }
+
+        if !matches!(line, DiffLine::Added(_)) {
+            expected_line += 1;
+        }
+    }
+    out
+}
+
+/// `tests/<kind>` にあるすべてのソースコード (`*.pmxclang`) のパスを名前順に列挙する。
+pub(crate) fn source_paths(kind: &str) -> Vec<PathBuf> {
+    let dir = tests_dir(kind);
+    let mut source_paths = fs::read_dir(&dir)
+        .unwrap_or_else(|err| panic!("cannot read {}: {}", dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == SOURCE_EXT))
+        .collect::<Vec<_>>();
+    source_paths.sort();
+    assert!(
+        !source_paths.is_empty(),
+        "no *.{} in {}",
+        SOURCE_EXT,
+        dir.display()
+    );
+    source_paths
+}
+
+/// `tests/<kind>` にあるすべてのソースコードについて、`render` の出力をスナップショットと比較する。
+pub(crate) fn assert_snapshots(kind: &str, render: impl Fn(String) -> String) {
+    let source_paths = source_paths(kind);
+    let update = should_update();
+    let mut failures = vec![];
+
+    for source_path in source_paths {
+        let source_code = fs::read_to_string(&source_path).unwrap();
+        let actual = render(source_code);
+
+        let snapshot_path = snapshot_path(&source_path);
+        let expected_opt = fs::read_to_string(&snapshot_path)
+            .ok()
+            .map(|text| text.replace("\r\n", "\n"));
+
+        if expected_opt.as_deref() == Some(actual.as_str()) {
+            continue;
+        }
+
+        if update {
+            fs::write(&snapshot_path, &actual).unwrap();
+            continue;
+        }
+
+        let failure = match expected_opt {
+            Some(expected) => format!(
+                "{} does not match:\n{}",
+                snapshot_path.display(),
+                format_diff(&expected, &actual)
+            ),
+            None => format!("{} does not exist", snapshot_path.display()),
+        };
+        failures.push(failure);
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "{}\n(Run the tests with UPDATE_SNAPSHOTS=1 to accept the new output.)",
+            failures.join("\n")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_diff() {
+        let expected = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let actual = "a\nb\nc\nd\nE\nf\ng\nh\ni\nj\n";
+
+        assert_eq!(
+            format_diff(expected, actual),
+            "\
+@@ line 2 @@
+ b
+ c
+ d
+-e
++E
+ f
+ g
+ h
+ i
++j
+"
+        );
+    }
+
+    #[test]
+    fn test_format_diff_same() {
+        assert_eq!(format_diff("a\nb\n", "a\nb\n"), "");
+    }
+}
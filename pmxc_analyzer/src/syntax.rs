@@ -26,8 +26,10 @@ pub(crate) use text_cursor::TextCursor;
 pub(crate) use token::{Token, TokenData};
 pub(crate) use trivia::Trivia;
 
+use std::fmt;
+
 /// 行番号と列番号で表されるテキスト上の位置。(1 から始まる。)
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) struct TextPosition {
     /// 1-based index.
     line: usize,
@@ -53,7 +55,14 @@ impl TextPosition {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+/// `行:列` の形式で表示する。
+impl fmt::Display for TextPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.character)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub(crate) struct TextRange {
     start: TextPosition,
     end: TextPosition,
@@ -71,14 +80,26 @@ impl TextRange {
     pub(crate) fn end(&self) -> TextPosition {
         self.end
     }
+
+    /// 位置が範囲に含まれるか？ (カーソルが末尾にある場合も含む。)
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+    pub(crate) fn contains(&self, position: TextPosition) -> bool {
+        self.start <= position && position <= self.end
+    }
+}
+
+/// `行:列-行:列` の形式で表示する。
+impl fmt::Display for TextRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs;
+    use crate::snapshot::assert_snapshots;
     use std::io::{self, Write};
-    use std::path::PathBuf;
     use std::rc::Rc;
 
     fn indent(depth: usize) -> &'static str {
@@ -120,45 +141,122 @@ mod tests {
 
     #[test]
     fn test_tokenize() {
-        let root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let tests_dir = root_dir.join("./tests");
+        assert_snapshots("tokenize", |source_code| {
+            let tokens = tokenize::tokenize(Rc::new(source_code));
 
-        let source_code = fs::read_to_string(&tests_dir.join("tokenize.pmxclang")).unwrap();
+            let mut snapshot = vec![];
+            for token in tokens.iter() {
+                go(token, "", &mut snapshot).unwrap();
+            }
+            String::from_utf8(snapshot).unwrap()
+        });
+    }
 
-        let tokens = tokenize::tokenize(Rc::new(source_code));
+    /// 以前は字句解析でパニックしていた入力 (どの規則にも当てはまらない文字を含む)
+    const KNOWN_PANIC_INPUTS: &[&str] = &[
+        "|",
+        "/",
+        "\r",
+        "match x {\n    A | B => {}\n}\n",
+        "a / b",
+        "enum A {\r    B,\r}\r",
+        "//\r/",
+    ];
 
-        let mut snapshot = vec![];
-        for token in tokens.iter() {
-            go(token, "", &mut snapshot).unwrap();
+    #[test]
+    fn test_known_panic_inputs() {
+        for &source_code in KNOWN_PANIC_INPUTS {
+            assert_eq!(crate::fuzzing::tokenize(source_code), source_code);
+            assert_eq!(crate::fuzzing::parse(source_code), source_code);
+
+            let diagnostics =
+                crate::diagnostic::collect_diagnostics(Rc::new(source_code.to_string()));
+            assert!(
+                diagnostics
+                    .iter()
+                    .any(|d| d.code == crate::diagnostic::DiagnosticCode::UnexpectedChars),
+                "{:?}: {:?}",
+                source_code,
+                diagnostics
+            );
         }
+    }
 
-        fs::write(&tests_dir.join("tokenize_snapshot.txt"), snapshot).unwrap();
+    /// `T(T(...T(inner)...))` の形の、n 段に入れ子になった式またはパターン
+    fn nest(n: usize, inner: &str) -> String {
+        format!("{}{}{}", "T(".repeat(n), inner, ")".repeat(n))
+    }
+
+    fn nesting_errors(source_code: &str) -> usize {
+        assert_eq!(crate::fuzzing::parse(source_code), source_code);
+
+        crate::diagnostic::collect_diagnostics(Rc::new(source_code.to_string()))
+            .iter()
+            .filter(|d| d.code == crate::diagnostic::DiagnosticCode::NestingTooDeep)
+            .count()
+    }
+
+    /// 以前はスタックが溢れていた、深く入れ子になったパターンや式
+    #[test]
+    fn test_deep_nesting_is_rejected() {
+        let decl = "enum T {\n    T(T),\n    U,\n}\n";
+
+        let pat = format!("{}match U {{\n    {} => {{}}\n}}\n", decl, nest(3000, "_"));
+        assert_eq!(nesting_errors(&pat), 1);
+
+        let expr = format!("{}match {} {{\n    _ => {{}}\n}}\n", decl, nest(3000, "U"));
+        assert_eq!(nesting_errors(&expr), 1);
+
+        let unclosed = format!("match {}", "T(".repeat(3000));
+        assert_eq!(nesting_errors(&unclosed), 1);
+    }
+
+    #[test]
+    fn test_nesting_within_limit_is_checked() {
+        let depth = parse_context::MAX_NESTING_DEPTH - 1;
+        let source_code = format!(
+            "enum T {{\n    T(T),\n    U,\n}}\nmatch U {{\n    {} => {{}}\n    U => {{}}\n}}\n",
+            nest(depth, "_")
+        );
+
+        // 網羅性検査は計算量の上限で打ち切られることがあるが、スタックは溢れない。
+        let diagnostics = crate::diagnostic::collect_diagnostics(Rc::new(source_code));
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert!(
+            matches!(
+                diagnostics[0].code,
+                crate::diagnostic::DiagnosticCode::NonExhaustive
+                    | crate::diagnostic::DiagnosticCode::Undetermined
+            ),
+            "{:?}",
+            diagnostics
+        );
     }
 
     pub(crate) fn snapshot_node(node: &NodeData, w: &mut Vec<u8>) -> io::Result<()> {
         fn on_token(token: &TokenData, depth: usize, w: &mut Vec<u8>) -> io::Result<()> {
             if !token.leading().is_empty() {
-                write!(w, "{}v [\n", indent(depth))?;
+                writeln!(w, "{}v [", indent(depth))?;
                 for trivia in token.leading() {
                     on_trivia(trivia, depth + 1, w)?;
                 }
-                write!(w, "{}]\n", indent(depth))?;
+                writeln!(w, "{}]", indent(depth))?;
             }
 
-            write!(
+            writeln!(
                 w,
-                "{}T({:?}) {:?}\n",
+                "{}T({:?}) {:?}",
                 indent(depth),
                 token.token(),
                 token.text()
             )?;
 
             if !token.trailing().is_empty() {
-                write!(w, "{}^ [\n", indent(depth))?;
+                writeln!(w, "{}^ [", indent(depth))?;
                 for trivia in token.trailing() {
                     on_trivia(trivia, depth + 1, w)?;
                 }
-                write!(w, "{}]\n", indent(depth))?;
+                writeln!(w, "{}]", indent(depth))?;
             }
 
             Ok(())
@@ -202,40 +300,32 @@ mod tests {
 
     #[test]
     fn test_parse() {
-        let root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let tests_dir = root_dir.join("./tests");
-
-        let source_code = fs::read_to_string(&tests_dir.join("parse.pmxclang")).unwrap();
-
-        let root = parse::parse(Rc::new(source_code));
+        assert_snapshots("parse", |source_code| {
+            let root = parse::parse(Rc::new(source_code));
 
-        let mut cursor = TextCursor::default();
-        let mut errors = vec![];
-        parse::collect_errors(&root, &mut cursor, &mut errors);
+            let mut cursor = TextCursor::default();
+            let mut errors = vec![];
+            parse::collect_errors(&root, &mut cursor, &mut errors);
 
-        let mut snapshot = vec![];
-        snapshot_node(&root, &mut snapshot).unwrap();
+            let mut snapshot = vec![];
+            snapshot_node(&root, &mut snapshot).unwrap();
 
-        if !errors.is_empty() {
-            write!(snapshot, "\n{:#?}\n", errors).unwrap();
-        }
-
-        fs::write(&tests_dir.join("parse_snapshot.txt"), snapshot).unwrap();
+            if !errors.is_empty() {
+                writeln!(snapshot).unwrap();
+                for error in errors {
+                    writeln!(snapshot, "{}", error).unwrap();
+                }
+            }
+            String::from_utf8(snapshot).unwrap()
+        });
     }
 
     #[test]
     fn test_ast() {
-        let root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let tests_dir = root_dir.join("./tests");
-
-        let source_code = fs::read_to_string(&tests_dir.join("ast.pmxclang")).unwrap();
-
-        let root = Rc::new(parse::parse(Rc::new(source_code)));
-        let ast = ast_gen::gen_root(root);
-
-        let mut snapshot = vec![];
-        write!(snapshot, "{:#?}\n", ast).unwrap();
-
-        fs::write(&tests_dir.join("ast_snapshot.txt"), snapshot).unwrap();
+        assert_snapshots("ast", |source_code| {
+            let root = Rc::new(parse::parse(Rc::new(source_code)));
+            let ast = ast_gen::gen_root(root);
+            format!("{:#?}\n", ast)
+        });
     }
 }
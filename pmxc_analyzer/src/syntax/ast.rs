@@ -9,7 +9,9 @@ pub(crate) struct Ty {
     pub(crate) node: Rc<NodeData>,
 }
 
+// 構文ノードは解析に使わない場合も保持しておく。(抽象構文木のスナップショットには表示される。)
 #[derive(Debug)]
+#[allow(dead_code)]
 pub(crate) struct DiscardPat {
     pub(crate) node: Rc<NodeData>,
 }
@@ -23,13 +25,15 @@ pub(crate) struct CtorPat {
 
 #[derive(Debug)]
 pub(crate) enum Pat {
-    Discard(DiscardPat),
+    Discard(#[allow(dead_code)] DiscardPat),
     Ctor(CtorPat),
 }
 
 #[derive(Debug)]
 pub(crate) struct CtorExpr {
     pub(crate) name_opt: Option<String>,
+    // 引数の式はエディタの機能だけが使う。
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
     pub(crate) tuple_opt: Option<Vec<Expr>>,
     pub(crate) node: Rc<NodeData>,
 }
@@ -55,6 +59,8 @@ pub(crate) struct MatchStmt {
 #[derive(Debug)]
 pub(crate) struct TupleDecl {
     pub(crate) fields: Vec<Ty>,
+    // 構文ノードは解析に使わない場合も保持しておく。
+    #[allow(dead_code)]
     pub(crate) node: Rc<NodeData>,
 }
 
@@ -62,6 +68,7 @@ pub(crate) struct TupleDecl {
 pub(crate) struct CtorDecl {
     pub(crate) name_opt: Option<String>,
     pub(crate) tuple_decl_opt: Option<TupleDecl>,
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
     pub(crate) node: Rc<NodeData>,
 }
 
@@ -9,18 +9,6 @@ pub(crate) static KEYWORD_TABLE: &[(Token, &str)] = &[
 ];
 
 impl Token {
-    pub(crate) fn is_control_keyword(self) -> bool {
-        self == Token::Match
-    }
-
-    pub(crate) fn is_keyword(self) -> bool {
-        self == Token::Enum
-            || self == Token::Let
-            || self == Token::Struct
-            || self == Token::Underscore
-            || self.is_control_keyword()
-    }
-
     pub(crate) fn parse_keyword(text: &str) -> Option<Token> {
         KEYWORD_TABLE
             .iter()
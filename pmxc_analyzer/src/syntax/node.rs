@@ -147,5 +147,48 @@ pub(crate) mod token_range_map {
         pub(crate) fn get(&self, key: &TokenData) -> Option<&TextRange> {
             self.map.get(&Self::get_key(key))
         }
+
+        /// ノードの直下にある、指定した種類の最初の字句の範囲を求める。
+        pub(crate) fn token_range(&self, node: &NodeData, token: Token) -> Option<TextRange> {
+            node.first_token(|t| t.token() == token)
+                .and_then(|t| self.get(t))
+                .cloned()
+        }
+
+        /// ノードの名前を表す識別子の範囲を求める。
+        ///
+        /// `K` のように識別子が直下にある場合と、`K(..)` のように Name ノードの下にある場合がある。
+        pub(crate) fn name_range(&self, node: &NodeData) -> Option<TextRange> {
+            self.token_range(node, Token::Ident).or_else(|| {
+                let name = node.first_node(|child| child.node() == Node::Name)?;
+                self.token_range(&name, Token::Ident)
+            })
+        }
+
+        /// ノードに含まれる最初の字句の始点から、最後の字句の終点までの範囲を求める。
+        pub(crate) fn node_range(&self, node: &NodeData) -> Option<TextRange> {
+            fn first_token(node: &NodeData) -> Option<&TokenData> {
+                node.children().iter().find_map(|element| match element {
+                    Element::Token(token) => Some(token),
+                    Element::Error(_) => None,
+                    Element::Node(node) => first_token(node),
+                })
+            }
+
+            fn last_token(node: &NodeData) -> Option<&TokenData> {
+                node.children()
+                    .iter()
+                    .rev()
+                    .find_map(|element| match element {
+                        Element::Token(token) => Some(token),
+                        Element::Error(_) => None,
+                        Element::Node(node) => last_token(node),
+                    })
+            }
+
+            let start = self.get(first_token(node)?)?.start();
+            let end = self.get(last_token(node)?)?.end();
+            Some(TextRange::new(start, end))
+        }
     }
 }
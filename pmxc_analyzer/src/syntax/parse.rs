@@ -1,6 +1,8 @@
 use super::parse_context::ParseContext;
 use super::parse_stmts::parse_root;
 use super::*;
+use crate::diagnostic::Diagnostic;
+use crate::messages::Message;
 use std::rc::Rc;
 
 pub(crate) fn parse_tokens(tokens: Rc<[TokenData]>) -> NodeData {
@@ -18,9 +20,9 @@ pub(crate) fn parse(source_code: Rc<String>) -> NodeData {
 pub(crate) fn collect_errors(
     node: &NodeData,
     cursor: &mut TextCursor,
-    errors: &mut Vec<(TextRange, String)>,
+    errors: &mut Vec<Diagnostic>,
 ) {
-    fn on_token(token: &TokenData, cursor: &mut TextCursor, errors: &mut Vec<(TextRange, String)>) {
+    fn on_token(token: &TokenData, cursor: &mut TextCursor, errors: &mut Vec<Diagnostic>) {
         for trivia in token.leading() {
             let start = cursor.current();
             let range = TextRange::new(start, start);
@@ -39,15 +41,15 @@ pub(crate) fn collect_errors(
         }
     }
 
-    fn on_error(error: ParseError, range: TextRange, errors: &mut Vec<(TextRange, String)>) {
-        errors.push((range, format!("{:?}", error)));
+    fn on_error(error: ParseError, range: TextRange, errors: &mut Vec<Diagnostic>) {
+        errors.push(Diagnostic::new(error.into(), range, Message::Parse(error)));
     }
 
     fn on_trivia(
         trivia: &Trivia,
         parent_range: TextRange,
         cursor: &mut TextCursor,
-        errors: &mut Vec<(TextRange, String)>,
+        errors: &mut Vec<Diagnostic>,
     ) {
         match trivia {
             Trivia::Token(token) => on_token(token, cursor, errors),
@@ -55,17 +57,13 @@ pub(crate) fn collect_errors(
         }
     }
 
-    fn on_node(node: &NodeData, cursor: &mut TextCursor, errors: &mut Vec<(TextRange, String)>) {
+    fn on_node(node: &NodeData, cursor: &mut TextCursor, errors: &mut Vec<Diagnostic>) {
         for child in node.children() {
             on_element(child, cursor, errors);
         }
     }
 
-    fn on_element(
-        element: &Element,
-        cursor: &mut TextCursor,
-        errors: &mut Vec<(TextRange, String)>,
-    ) {
+    fn on_element(element: &Element, cursor: &mut TextCursor, errors: &mut Vec<Diagnostic>) {
         match element {
             Element::Token(token) => on_token(token, cursor, errors),
             Element::Error(error) => {
@@ -7,14 +7,27 @@ type TokenIndex = usize;
 
 type TokenList = Rc<[TokenData]>;
 
+/// 式やパターンの入れ子の深さの上限
+///
+/// 構文木をたどる処理 (抽象構文木の生成や網羅性検査など) は再帰するので、入れ子が深すぎるとスタックが溢れる。
+/// 上限を超えた部分は構文エラーとして読み飛ばし、構文木を深くしない。
+pub(crate) const MAX_NESTING_DEPTH: usize = 128;
+
 pub(crate) struct ParseContext {
     tokens: TokenList,
     index: TokenIndex,
+
+    /// 解析中の式やパターンの入れ子の深さ
+    depth: usize,
 }
 
 impl ParseContext {
     pub(crate) fn new(tokens: TokenList) -> Self {
-        ParseContext { tokens, index: 0 }
+        ParseContext {
+            tokens,
+            index: 0,
+            depth: 0,
+        }
     }
 
     pub(crate) fn assert_invariants(&self) {
@@ -90,6 +103,39 @@ impl ParseContext {
         true
     }
 
+    /// 入れ子を1段深くして f を呼ぶ。上限に達しているときは f を呼ばずに None を返す。
+    pub(crate) fn nested<T>(&mut self, f: impl FnOnce(&mut ParseContext) -> T) -> Option<T> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return None;
+        }
+
+        self.depth += 1;
+        let value = f(self);
+        self.depth -= 1;
+        Some(value)
+    }
+
+    /// 上限を超えた入れ子を、構文木を深くせずにノードに読み込む。
+    ///
+    /// 対応の取れていない閉じカッコ、カンマ、波カッコ、文の始まりのいずれかの手前まで読む。
+    pub(crate) fn skip_nested(&mut self, node: &mut NodeData) {
+        node.push_error(ParseError::NestingTooDeep);
+
+        let mut depth = 0_usize;
+        loop {
+            match self.next() {
+                Token::Eof | Token::LeftBrace | Token::RightBrace | Token::Match | Token::Enum => {
+                    break
+                }
+                Token::RightParen | Token::Comma if depth == 0 => break,
+                Token::LeftParen => depth += 1,
+                Token::RightParen => depth -= 1,
+                _ => {}
+            }
+            self.bump(node);
+        }
+    }
+
     pub(crate) fn finish(mut self, root: &mut NodeData) {
         assert_eq!(self.index, self.tokens.len() - 1);
         assert_eq!(root.node(), Node::Root);
@@ -8,4 +8,5 @@ pub(crate) enum ParseError {
     ExpectedRightBrace,
     ExpectedRightParen,
     UnexpectedChars,
+    NestingTooDeep,
 }
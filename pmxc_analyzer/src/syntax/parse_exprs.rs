@@ -79,5 +79,15 @@ pub(crate) fn parse_cond(p: &mut ParseContext) -> Option<NodeData> {
 }
 
 pub(crate) fn parse_expr(p: &mut ParseContext) -> Option<NodeData> {
-    parse_call(p)
+    if let Some(expr) = p.nested(parse_call) {
+        return expr;
+    }
+
+    if !p.next().is_expr_first() {
+        return None;
+    }
+
+    let mut node = NodeData::new();
+    p.skip_nested(&mut node);
+    Some(node.set_node(Node::Group))
 }
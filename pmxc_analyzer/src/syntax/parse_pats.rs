@@ -9,6 +9,7 @@ impl Token {
             || self == Token::Underscore
     }
 
+    #[allow(dead_code)]
     pub(crate) fn is_pat_first(self) -> bool {
         self.is_pat_atom_first()
     }
@@ -82,5 +83,15 @@ pub(crate) fn parse_pat_call(p: &mut ParseContext) -> Option<NodeData> {
 }
 
 pub(crate) fn parse_pat(p: &mut ParseContext) -> Option<NodeData> {
-    parse_pat_call(p)
+    if let Some(pat) = p.nested(parse_pat_call) {
+        return pat;
+    }
+
+    if !p.next().is_pat_atom_first() {
+        return None;
+    }
+
+    let mut node = NodeData::new();
+    p.skip_nested(&mut node);
+    Some(node.set_node(Node::Group))
 }
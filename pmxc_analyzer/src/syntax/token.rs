@@ -98,6 +98,7 @@ impl TokenData {
         self.leading.push(token.into());
     }
 
+    #[allow(dead_code)]
     pub(crate) fn push_leading_error(&mut self, error: ParseError) {
         self.leading.push(error.into());
     }
@@ -17,9 +17,20 @@ fn char_is_ident_first(c: char) -> bool {
     char_is_ident(c) && !c.is_ascii_digit()
 }
 
+/// 文字列が (キーワードではない) 識別子として字句解析されるか？
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+pub(crate) fn text_is_ident(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(char_is_ident_first)
+        && chars.all(char_is_ident)
+        && Token::parse_keyword(text).is_none()
+}
+
 /// 文字が約物の先頭になるか？
 fn char_is_pun_first(c: char) -> bool {
-    "()<>[]{}:,.=-|;".contains(c)
+    PUN_TABLE
+        .iter()
+        .any(|&(_, pun_text)| pun_text.starts_with(c))
 }
 
 /// 文字が解釈不能か？
@@ -114,6 +125,10 @@ pub(crate) fn tokenize_all(t: &mut TokenizeContext) {
         tokenize_pun(t);
         tokenize_other(t);
 
-        assert_ne!(t.current_index(), start_index, "無限ループを検出しました");
+        // どの規則にも当てはまらなかった文字 (単独の `/` や `\r` など) は、1文字だけ解釈不能な字句にする。
+        if t.current_index() == start_index {
+            t.bump();
+            t.commit(Token::Other);
+        }
     }
 }
@@ -0,0 +1,248 @@
+//! 注釈付きのソースコードを使うテスト (UI テスト)
+//!
+//! `tests/ui/*.pmxclang` に書かれた `//~` で始まるコメント (注釈) を読み、
+//! 注釈の直後にある文について報告された診断と照合する。
+//!
+//! - `//~ ERROR <名前>`: エラーが報告される。(名前は `non-exhaustive` のような slug か、`E0001` のようなコード)
+//! - `//~ WARNING <名前>`: 警告が報告される。
+//! - `//~ WITNESS <パターン>`: パターンがマッチしない値の例として報告される。
+//!   (1つでも書いたら、その文のすべての例を書く。)
+//!
+//! 注釈のない診断が報告されたときも失敗とする。
+
+use crate::diagnostic::{collect_diagnostics, Diagnostic, Severity};
+use crate::snapshot;
+use crate::syntax::{ast_gen, parse, Stmt, TextRange, TokenRangeMap};
+use std::fs;
+use std::rc::Rc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expectation {
+    Diagnostic { severity: Severity, name: String },
+    Witness(String),
+}
+
+/// 注釈 (と、それが書かれている行の番号)
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Annotation {
+    line: usize,
+    expectation: Expectation,
+}
+
+fn parse_annotations(source_code: &str) -> Result<Vec<Annotation>, String> {
+    let mut annotations = vec![];
+
+    for (i, text) in source_code.lines().enumerate() {
+        let line = i + 1;
+        let rest = match text.trim_start().strip_prefix("//~") {
+            Some(rest) => rest.trim(),
+            None => continue,
+        };
+
+        let (kind, argument) = rest.split_once(' ').unwrap_or((rest, ""));
+        let argument = argument.trim().to_string();
+        if argument.is_empty() {
+            return Err(format!("line {}: missing argument: {:?}", line, text));
+        }
+
+        let expectation = match kind {
+            "ERROR" => Expectation::Diagnostic {
+                severity: Severity::Error,
+                name: argument,
+            },
+            "WARNING" => Expectation::Diagnostic {
+                severity: Severity::Warning,
+                name: argument,
+            },
+            "WITNESS" => Expectation::Witness(argument),
+            _ => return Err(format!("line {}: unknown annotation: {:?}", line, text)),
+        };
+        annotations.push(Annotation { line, expectation });
+    }
+
+    Ok(annotations)
+}
+
+/// トップレベルの文の範囲を列挙する。
+fn stmt_ranges(source_code: &str) -> Vec<TextRange> {
+    let root = Rc::new(parse::parse(Rc::new(source_code.to_string())));
+    let token_range_map = TokenRangeMap::new(&root);
+    let ast = ast_gen::gen_root(root);
+
+    ast.stmts
+        .iter()
+        .filter_map(|stmt| {
+            let node = match stmt {
+                Stmt::Match(stmt) => &stmt.node,
+                Stmt::Enum(stmt) => &stmt.node,
+            };
+            token_range_map.node_range(node)
+        })
+        .collect()
+}
+
+fn matches_name(diagnostic: &Diagnostic, severity: Severity, name: &str) -> bool {
+    diagnostic.severity == severity
+        && (diagnostic.code.slug() == name || diagnostic.code.code() == name)
+}
+
+/// ソースコードの注釈と診断を照合して、食い違いを列挙する。
+fn verify(source_code: &str) -> Result<(), Vec<String>> {
+    let annotations = parse_annotations(source_code).map_err(|err| vec![err])?;
+    let stmt_ranges = stmt_ranges(source_code);
+    let diagnostics = collect_diagnostics(Rc::new(source_code.to_string()));
+
+    // 文ごとに注釈と診断を分類する。
+    // 注釈はその行より後にある最初の文に、診断はその始点を含む (か、その直前にある) 文に属する。
+    let mut expected = vec![vec![]; stmt_ranges.len()];
+    let mut actual = vec![vec![]; stmt_ranges.len().max(1)];
+    let mut errors = vec![];
+
+    for annotation in annotations {
+        match stmt_ranges
+            .iter()
+            .position(|range| range.start().line() > annotation.line)
+        {
+            Some(i) => expected[i].push(annotation),
+            None => errors.push(format!(
+                "line {}: no statement follows the annotation",
+                annotation.line
+            )),
+        }
+    }
+
+    for diagnostic in diagnostics.iter() {
+        let start = diagnostic.range.start();
+        let i = stmt_ranges
+            .iter()
+            .rposition(|range| range.start() <= start)
+            .unwrap_or(0);
+        actual[i].push(diagnostic);
+    }
+
+    for (i, annotations) in expected.iter().enumerate() {
+        let group = actual[i].clone();
+        let mut remaining = group.clone();
+
+        for annotation in annotations.iter() {
+            if let Expectation::Diagnostic { severity, ref name } = annotation.expectation {
+                match remaining
+                    .iter()
+                    .position(|diagnostic| matches_name(diagnostic, severity, name))
+                {
+                    Some(j) => {
+                        remaining.remove(j);
+                    }
+                    None => errors.push(format!(
+                        "line {}: expected {} {} was not reported",
+                        annotation.line,
+                        severity.as_str(),
+                        name
+                    )),
+                }
+            }
+        }
+        actual[i] = remaining;
+
+        let mut expected_witnesses = annotations
+            .iter()
+            .filter_map(|annotation| match annotation.expectation {
+                Expectation::Witness(ref pattern) => Some(pattern.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !expected_witnesses.is_empty() {
+            let mut actual_witnesses = group
+                .iter()
+                .flat_map(|diagnostic| diagnostic.witnesses())
+                .collect::<Vec<_>>();
+            expected_witnesses.sort_unstable();
+            actual_witnesses.sort_unstable();
+
+            if expected_witnesses != actual_witnesses {
+                errors.push(format!(
+                    "{}: expected witnesses {:?}, but found {:?}",
+                    stmt_ranges[i].start(),
+                    expected_witnesses,
+                    actual_witnesses
+                ));
+            }
+        }
+    }
+
+    for diagnostic in actual.iter().flatten() {
+        errors.push(format!("unexpected diagnostic: {}", diagnostic));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[test]
+fn test_ui() {
+    let mut failures = vec![];
+
+    for path in snapshot::source_paths("ui") {
+        let source_code = fs::read_to_string(&path).unwrap();
+        if let Err(errors) = verify(&source_code) {
+            failures.push(format!(
+                "{}:\n    {}",
+                path.display(),
+                errors.join("\n    ")
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_parse_annotations() {
+    let source_code = "//~ ERROR non-exhaustive\n    //~ WITNESS Some(True, Zero)\n";
+    assert_eq!(
+        parse_annotations(source_code),
+        Ok(vec![
+            Annotation {
+                line: 1,
+                expectation: Expectation::Diagnostic {
+                    severity: Severity::Error,
+                    name: "non-exhaustive".to_string(),
+                },
+            },
+            Annotation {
+                line: 2,
+                expectation: Expectation::Witness("Some(True, Zero)".to_string()),
+            },
+        ])
+    );
+
+    assert!(parse_annotations("//~ NOTE hello\n").is_err());
+    assert!(parse_annotations("//~ ERROR\n").is_err());
+}
+
+#[test]
+fn test_verify_reports_mismatches() {
+    let source_code = "\
+enum Boolean {
+    True,
+    False,
+}
+
+//~ ERROR E0001
+//~ WITNESS True
+match True {
+    True => {}
+}
+
+match True {
+    True => {}
+}
+";
+    let errors = verify(source_code).unwrap_err();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors[0].contains("expected witnesses [\"True\"], but found [\"False\"]"));
+    assert!(errors[1].starts_with("unexpected diagnostic: 12:1-12:6"));
+}
@@ -0,0 +1,676 @@
+//! WebAssembly 向けのエントリーポイント
+//!
+//! pmxc_playground (monaco editor) から呼ばれる関数と、monaco の型に合わせたデータ構造を定義する。
+
+use crate::crash;
+use crate::diagnostic::{self, Diagnostic, Severity};
+use crate::ide;
+use crate::json::VerdictKind;
+use crate::match_exhaustivity::{trace_match, Budget, Derivation};
+use crate::messages::Locale;
+use crate::syntax::{self, *};
+use monaco::*;
+use serde::Serialize;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    fn alert(s: &str);
+}
+
+pub(crate) mod monaco {
+    use crate::syntax::TextRange;
+    use serde::{Serialize, Serializer};
+
+    /// 値を持たない enum を (名前ではなく) 数値としてシリアライズする。
+    macro_rules! serialize_as_number {
+        ($($t:ty),*) => {
+            $(
+                impl Serialize for $t {
+                    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                        serializer.serialize_u8(*self as u8)
+                    }
+                }
+            )*
+        };
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct IRange {
+        /// 1-based index
+        pub start_line_number: usize,
+        pub start_column: usize,
+        pub end_line_number: usize,
+        pub end_column: usize,
+    }
+
+    impl From<TextRange> for IRange {
+        fn from(range: TextRange) -> IRange {
+            IRange {
+                start_line_number: range.start().line(),
+                start_column: range.start().character(),
+                end_line_number: range.end().line(),
+                end_column: range.end().character(),
+            }
+        }
+    }
+
+    pub mod editor {
+        use super::*;
+
+        #[derive(Clone, Copy)]
+        #[allow(unused)]
+        pub enum MarkerSeverity {
+            Hint = 1,
+            Info = 2,
+            Warning = 4,
+            Error = 8,
+        }
+
+        #[derive(Clone, Copy)]
+        #[allow(unused)]
+        pub enum MarkerTag {
+            Unnecessary = 1,
+            Deprecated = 2,
+        }
+
+        serialize_as_number!(MarkerSeverity, MarkerTag);
+
+        /// 関連する箇所。(resource は JavaScript 側でモデルの URI を設定する。)
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct RelatedInformation {
+            pub message: String,
+            pub start_line_number: usize,
+            pub start_column: usize,
+            pub end_line_number: usize,
+            pub end_column: usize,
+        }
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct MarkerData {
+            pub code: String,
+            pub severity: MarkerSeverity,
+            pub message: String,
+            pub source: String,
+            /// 1-based index
+            pub start_line_number: usize,
+            pub start_column: usize,
+            pub end_line_number: usize,
+            pub end_column: usize,
+            pub related_information: Vec<RelatedInformation>,
+            pub tags: Vec<MarkerTag>,
+        }
+    }
+
+    pub mod languages {
+        use super::*;
+
+        #[derive(Default, Serialize)]
+        pub struct State;
+
+        #[derive(Clone, Copy)]
+        #[allow(unused)]
+        pub enum DocumentHighlightKind {
+            Text = 0,
+            Read = 1,
+            Write = 2,
+        }
+
+        serialize_as_number!(DocumentHighlightKind);
+
+        #[derive(Serialize)]
+        pub struct DocumentHighlight {
+            pub range: IRange,
+            pub kind: DocumentHighlightKind,
+        }
+
+        #[derive(Serialize)]
+        pub struct TextEdit {
+            pub range: IRange,
+            pub text: String,
+        }
+
+        #[derive(Serialize)]
+        pub struct RenameLocation {
+            pub range: IRange,
+            pub text: String,
+        }
+
+        #[derive(Clone, Copy)]
+        #[allow(unused)]
+        pub enum SymbolKind {
+            Enum = 9,
+            EnumMember = 21,
+            Operator = 24,
+        }
+
+        serialize_as_number!(SymbolKind);
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct DocumentSymbol {
+            pub name: String,
+            pub detail: String,
+            pub kind: SymbolKind,
+            pub tags: Vec<u8>,
+            pub range: IRange,
+            pub selection_range: IRange,
+            pub children: Vec<DocumentSymbol>,
+        }
+
+        #[derive(Serialize)]
+        pub struct FoldingRange {
+            pub start: usize,
+            pub end: usize,
+        }
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct SemanticTokensLegend {
+            pub token_types: Vec<&'static str>,
+            pub token_modifiers: Vec<&'static str>,
+        }
+
+        /// セマンティックトークン。(data は JavaScript 側で Uint32Array に変換する。)
+        #[derive(Default, Serialize)]
+        pub struct SemanticTokens {
+            pub data: Vec<u32>,
+        }
+
+        /// 補完候補。(range や kind は JavaScript 側で設定する。)
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct CompletionItem {
+            pub label: String,
+            pub detail: String,
+            pub insert_text: String,
+            pub sort_text: String,
+        }
+
+        /// 修正案。(edits の resource は JavaScript 側で設定する。)
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct CodeAction {
+            pub title: String,
+            /// 修正する診断のコード
+            pub code: String,
+            pub edits: Vec<TextEdit>,
+        }
+
+        /// 名前の変更の結果。変更できないときは reject_reason に理由が入る。
+        #[derive(Default, Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct RenameResult {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub location: Option<RenameLocation>,
+            pub edits: Vec<TextEdit>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub reject_reason: Option<String>,
+        }
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct Token {
+            pub start_index: usize,
+            pub scopes: String,
+        }
+
+        #[derive(Default, Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct LineTokens {
+            pub tokens: Vec<Token>,
+            pub end_state: State,
+        }
+    }
+}
+
+// JSON を経由して変換する。(None は null になる。playground はこの形を前提にしている。)
+#[allow(deprecated)]
+fn to_js_value<T: Serialize>(value: &T) -> JsValue {
+    JsValue::from_serde(value).unwrap()
+}
+
+/// 関数を呼び出して、結果を JavaScript の値にする。内部エラーが起きたときは既定値 (空の配列など) を返す。
+///
+/// (パニックを捕捉できるのはネイティブでテストするときだけで、wasm32 ではトラップになる。crash モジュールを参照。)
+fn guard<T: Serialize + Default>(f: impl FnOnce() -> T) -> JsValue {
+    to_js_value(&crash::catch_panic(f).unwrap_or_default())
+}
+
+/// 字句解析して、各トークンの種類と開始位置 (UTF-16 での位置) を返す。(monaco の位置は UTF-16 で数える。)
+fn tokenize_with_utf16_indices(source_code: Rc<String>) -> Box<[(Token, usize)]> {
+    fn go(token: &TokenData, token_indices: &mut Vec<(Token, usize)>, index: &mut usize) {
+        for trivia in token.leading() {
+            match trivia {
+                Trivia::Token(token) => {
+                    go(token, token_indices, index);
+                }
+                Trivia::Error(_) => continue,
+            }
+        }
+
+        let start = *index;
+        *index += token.text().encode_utf16().count();
+
+        token_indices.push((token.token(), start));
+
+        for trivia in token.trailing() {
+            match trivia {
+                Trivia::Token(token) => {
+                    go(token, token_indices, index);
+                }
+                Trivia::Error(_) => continue,
+            }
+        }
+    }
+
+    let tokens = syntax::tokenize::tokenize(source_code);
+
+    let mut token_indices = vec![];
+    let mut index = 0;
+
+    for token in tokens.iter() {
+        go(token, &mut token_indices, &mut index);
+    }
+
+    token_indices.into_boxed_slice()
+}
+
+#[wasm_bindgen]
+pub fn tokenize(source_code: String) -> JsValue {
+    guard(|| {
+        let token_indices = tokenize_with_utf16_indices(Rc::new(source_code));
+
+        let tokens = token_indices
+            .iter()
+            .map(|&(token, start_index)| {
+                let scopes = match token {
+                    Token::Comment => "comment",
+                    Token::Match => "keyword.control",
+                    Token::Enum | Token::Let | Token::Struct | Token::Underscore => "keyword",
+                    Token::Other => "invalid",
+                    _ => "none",
+                };
+
+                languages::Token {
+                    start_index,
+                    scopes: scopes.to_string(),
+                }
+            })
+            .collect();
+
+        languages::LineTokens {
+            tokens,
+            end_state: languages::State,
+        }
+    })
+}
+
+/// JavaScript から渡された言語タグを解釈する。(省略されたときや、知らない言語なら既定の言語。)
+fn locale_from_tag(tag_opt: Option<String>) -> Locale {
+    tag_opt
+        .as_deref()
+        .and_then(Locale::from_tag)
+        .unwrap_or_default()
+}
+
+impl editor::MarkerData {
+    pub(crate) fn new(diagnostic: &Diagnostic, locale: Locale) -> Self {
+        let range = diagnostic.range;
+
+        // 注記はメッセージの後ろに付け加える。
+        let mut message = diagnostic.message.render(locale);
+        for note in diagnostic.notes.iter() {
+            message += "\n";
+            message += &note.render(locale);
+        }
+
+        editor::MarkerData {
+            code: diagnostic.code.code().to_string(),
+            severity: match diagnostic.severity {
+                Severity::Warning => editor::MarkerSeverity::Warning,
+                Severity::Error => editor::MarkerSeverity::Error,
+            },
+            message,
+            source: "pmxc".to_string(),
+            start_line_number: range.start().line(),
+            start_column: range.start().character(),
+            end_line_number: range.end().line(),
+            end_column: range.end().character(),
+            related_information: diagnostic
+                .related
+                .iter()
+                .map(|related| editor::RelatedInformation {
+                    message: related.message.render(locale),
+                    start_line_number: related.range.start().line(),
+                    start_column: related.range.start().character(),
+                    end_line_number: related.range.end().line(),
+                    end_column: related.range.end().character(),
+                })
+                .collect(),
+            tags: if diagnostic.is_unnecessary {
+                vec![editor::MarkerTag::Unnecessary]
+            } else {
+                vec![]
+            },
+        }
+    }
+}
+
+/// ソースコードを検査する。locale は `ja` や `en-US` などの言語タグ。
+#[wasm_bindgen]
+pub fn validate(source_code: String, locale: Option<String>) -> JsValue {
+    let locale = locale_from_tag(locale);
+    let diagnostics = crash::catch_panic(|| diagnostic::collect_diagnostics(Rc::new(source_code)))
+        .unwrap_or_else(|message| vec![Diagnostic::internal_error(message)]);
+
+    let markers = diagnostics
+        .iter()
+        .map(|diagnostic| editor::MarkerData::new(diagnostic, locale))
+        .collect::<Vec<_>>();
+    to_js_value(&markers)
+}
+
+/// 指定した範囲に重なる診断の修正案を列挙する。
+#[wasm_bindgen]
+pub fn code_actions(
+    source_code: String,
+    start_line_number: usize,
+    start_column: usize,
+    end_line_number: usize,
+    end_column: usize,
+    locale: Option<String>,
+) -> JsValue {
+    guard(|| {
+        let locale = locale_from_tag(locale);
+        let start = TextPosition::new(start_line_number, start_column);
+        let end = TextPosition::new(end_line_number, end_column);
+        let diagnostics = diagnostic::collect_diagnostics(Rc::new(source_code));
+
+        diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.range.start() <= end && start <= diagnostic.range.end())
+            .flat_map(|diagnostic| {
+                diagnostic
+                    .fixes
+                    .iter()
+                    .map(move |fix| languages::CodeAction {
+                        title: fix.title.render(locale),
+                        code: diagnostic.code.code().to_string(),
+                        edits: fix
+                            .edits
+                            .iter()
+                            .map(|(range, text)| languages::TextEdit {
+                                range: IRange::from(*range),
+                                text: text.clone(),
+                            })
+                            .collect(),
+                    })
+            })
+            .collect::<Vec<_>>()
+    })
+}
+
+#[wasm_bindgen]
+pub fn definition(source_code: String, line_number: usize, column: usize) -> JsValue {
+    guard(|| {
+        let analysis = ide::Analysis::new(Rc::new(source_code));
+        let position = TextPosition::new(line_number, column);
+
+        ide::navigation::definition(&analysis.symbol_index, position).map(IRange::from)
+    })
+}
+
+#[wasm_bindgen]
+pub fn references(
+    source_code: String,
+    line_number: usize,
+    column: usize,
+    include_declaration: bool,
+) -> JsValue {
+    guard(|| {
+        let analysis = ide::Analysis::new(Rc::new(source_code));
+        let position = TextPosition::new(line_number, column);
+
+        ide::navigation::references(&analysis.symbol_index, position, include_declaration)
+            .into_iter()
+            .map(IRange::from)
+            .collect::<Vec<_>>()
+    })
+}
+
+#[wasm_bindgen]
+pub fn document_highlights(source_code: String, line_number: usize, column: usize) -> JsValue {
+    guard(|| {
+        let analysis = ide::Analysis::new(Rc::new(source_code));
+        let position = TextPosition::new(line_number, column);
+
+        ide::navigation::document_highlights(&analysis.symbol_index, position)
+            .into_iter()
+            .map(|(range, kind)| languages::DocumentHighlight {
+                range: range.into(),
+                kind: match kind {
+                    ide::navigation::HighlightKind::Read => languages::DocumentHighlightKind::Read,
+                    ide::navigation::HighlightKind::Write => {
+                        languages::DocumentHighlightKind::Write
+                    }
+                },
+            })
+            .collect::<Vec<_>>()
+    })
+}
+
+#[wasm_bindgen]
+pub fn prepare_rename(
+    source_code: String,
+    line_number: usize,
+    column: usize,
+    locale: Option<String>,
+) -> JsValue {
+    guard(|| {
+        let locale = locale_from_tag(locale);
+        let analysis = ide::Analysis::new(Rc::new(source_code));
+        let position = TextPosition::new(line_number, column);
+
+        match ide::rename::prepare_rename(&analysis.symbol_index, position) {
+            Ok((range, text)) => languages::RenameResult {
+                location: Some(languages::RenameLocation {
+                    range: range.into(),
+                    text,
+                }),
+                ..Default::default()
+            },
+            Err(err) => languages::RenameResult {
+                reject_reason: Some(err.message().render(locale)),
+                ..Default::default()
+            },
+        }
+    })
+}
+
+#[wasm_bindgen]
+pub fn rename(
+    source_code: String,
+    line_number: usize,
+    column: usize,
+    new_name: String,
+    locale: Option<String>,
+) -> JsValue {
+    guard(|| {
+        let locale = locale_from_tag(locale);
+        let analysis = ide::Analysis::new(Rc::new(source_code));
+        let position = TextPosition::new(line_number, column);
+
+        match ide::rename::rename(&analysis.symbol_index, position, &new_name) {
+            Ok(edits) => languages::RenameResult {
+                edits: edits
+                    .into_iter()
+                    .map(|edit| languages::TextEdit {
+                        range: edit.range.into(),
+                        text: edit.new_text,
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+            Err(err) => languages::RenameResult {
+                reject_reason: Some(err.message().render(locale)),
+                ..Default::default()
+            },
+        }
+    })
+}
+
+#[wasm_bindgen]
+pub fn completion(source_code: String, line_number: usize, column: usize) -> JsValue {
+    guard(|| {
+        let analysis = ide::Analysis::new(Rc::new(source_code));
+        let position = TextPosition::new(line_number, column);
+
+        ide::completion::completion(&analysis, position)
+            .into_iter()
+            .map(|item| languages::CompletionItem {
+                label: item.label,
+                detail: item.detail,
+                insert_text: item.insert_text,
+                sort_text: item.sort_text,
+            })
+            .collect::<Vec<_>>()
+    })
+}
+
+#[wasm_bindgen]
+pub fn document_symbols(source_code: String) -> JsValue {
+    guard(|| {
+        fn convert(symbol: ide::outline::DocumentSymbol) -> languages::DocumentSymbol {
+            languages::DocumentSymbol {
+                name: symbol.name,
+                detail: String::new(),
+                kind: match symbol.kind {
+                    ide::outline::DocumentSymbolKind::Enum => languages::SymbolKind::Enum,
+                    ide::outline::DocumentSymbolKind::Ctor => languages::SymbolKind::EnumMember,
+                    ide::outline::DocumentSymbolKind::Match => languages::SymbolKind::Operator,
+                },
+                tags: vec![],
+                range: symbol.range.into(),
+                selection_range: symbol.selection_range.into(),
+                children: symbol.children.into_iter().map(convert).collect(),
+            }
+        }
+
+        let analysis = ide::Analysis::new(Rc::new(source_code));
+
+        ide::outline::document_symbols(&analysis)
+            .into_iter()
+            .map(convert)
+            .collect::<Vec<_>>()
+    })
+}
+
+#[wasm_bindgen]
+pub fn folding_ranges(source_code: String) -> JsValue {
+    guard(|| {
+        let analysis = ide::Analysis::new(Rc::new(source_code));
+
+        ide::outline::folding_ranges(&analysis)
+            .into_iter()
+            .map(|range| languages::FoldingRange {
+                start: range.start_line,
+                end: range.end_line,
+            })
+            .collect::<Vec<_>>()
+    })
+}
+
+#[wasm_bindgen]
+pub fn semantic_tokens_legend() -> JsValue {
+    to_js_value(&languages::SemanticTokensLegend {
+        token_types: ide::semantic_tokens::TOKEN_TYPES.to_vec(),
+        token_modifiers: ide::semantic_tokens::TOKEN_MODIFIERS.to_vec(),
+    })
+}
+
+#[wasm_bindgen]
+pub fn semantic_tokens(source_code: String) -> JsValue {
+    guard(|| {
+        let analysis = ide::Analysis::new(Rc::new(source_code));
+
+        let tokens = ide::semantic_tokens::semantic_tokens(&analysis.symbol_index);
+        languages::SemanticTokens {
+            data: ide::semantic_tokens::encode(&tokens),
+        }
+    })
+}
+
+/// JSON で書かれた網羅性検査の問題を解く。(形式は json モジュールを参照。)
+///
+/// 結果を JSON 文字列で返す。入力が不正なときは `{"error": "..."}` を返す。
+#[wasm_bindgen]
+pub fn check_json(input: String) -> String {
+    match crash::catch_panic(|| crate::json::check_json(&input)) {
+        Ok(Ok(output)) => output,
+        Ok(Err(error)) => error,
+        Err(message) => serde_json::json!({ "error": message }).to_string(),
+    }
+}
+
+/// 1つの match 式の網羅性検査の導出
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MatchTraceReport {
+    /// match キーワードの範囲
+    range: IRange,
+
+    /// match 式の最後の行。(説明はこの行の下に表示する。)
+    end_line_number: usize,
+
+    verdict: VerdictKind,
+
+    /// 導出が多すぎて、一部を省略したか？
+    truncated: bool,
+
+    derivation: Derivation,
+}
+
+/// ソースコードにある match 式のそれぞれについて、網羅性検査の導出木を求める。
+///
+/// プレイグラウンドで「なぜ網羅的 (でない) のか」を説明するのに使う。結果を JSON 文字列で返す。
+#[wasm_bindgen]
+pub fn match_traces(source_code: String) -> String {
+    let reports = crash::catch_panic(|| {
+        let analysis = ide::Analysis::new(Rc::new(source_code));
+        let model = &analysis.model;
+
+        model
+            .match_expressions
+            .iter()
+            .map(|lowered| {
+                let trace = trace_match(&lowered.expression, &model.ty_database, Budget::default());
+                let end_range = lowered.r_brace_range.unwrap_or(lowered.range);
+
+                MatchTraceReport {
+                    range: IRange::from(lowered.range),
+                    end_line_number: end_range.end().line(),
+                    verdict: VerdictKind::from(trace.verdict),
+                    truncated: trace.truncated,
+                    derivation: trace.derivation,
+                }
+            })
+            .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+
+    serde_json::to_string(&reports).unwrap()
+}
+
+/// 最後に起きたパニックのメッセージ。
+///
+/// wasm ではパニックを捕捉できず、呼び出した関数が例外を投げる。そのあとで理由を調べるために使う。
+/// (トラップしたインスタンスは壊れているので、呼び出し側はこれを読んだらインスタンスを捨てること。)
+#[wasm_bindgen]
+pub fn last_panic_message() -> Option<String> {
+    crash::last_panic_message()
+}
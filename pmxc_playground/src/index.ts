@@ -149,6 +149,68 @@ match One {
 }
 `
 
+/** pmxc_analyzer の wasm モジュール (wasm-pack で `-t web` を指定して生成したもの) */
+type Analyzer = typeof import("../dist/pmxc_analyzer")
+
+/**
+ * 使用中の wasm モジュール (作り直している間は undefined)
+ *
+ * wasm ではパニックが巻き戻されずにトラップになり、そのインスタンスはスタックポインタなどが壊れた状態で残る。
+ * そのため、トラップが起きたインスタンスは捨てて、モジュールを読み込み直す。
+ */
+let currentAnalyzer: Analyzer | undefined
+
+let analyzerGeneration = 0
+
+const loadAnalyzer = async (): Promise<Analyzer> => {
+    analyzerGeneration++
+
+    // URL が異なると別のモジュールとして評価されるので、wasm のインスタンスも新しく作られる。
+    // (webpack にはまとめさせず、dist/pmxc_analyzer にあるファイルをそのまま読み込む。)
+    const url = `./pmxc_analyzer/pmxc_analyzer.js?generation=${analyzerGeneration}`
+    const analyzer = await import(/* webpackIgnore: true */ url) as Analyzer
+    await analyzer.default()
+    return analyzer
+}
+
+/** トラップが起きたインスタンスを捨てて、新しいインスタンスを読み込む。 */
+const reloadAnalyzer = () => {
+    currentAnalyzer = undefined
+    loadAnalyzer()
+        .then(analyzer => {
+            currentAnalyzer = analyzer
+        })
+        .catch(err => console.error(err))
+}
+
+/**
+ * wasm の関数を呼ぶ。インスタンスを作り直している間は undefined を返す。
+ *
+ * トラップが起きたときは、パニックのメッセージを例外にして投げ直す。
+ * (同じ入力で再びトラップしないように、作り直した後で検証をやり直すことはしない。)
+ */
+const withAnalyzer = <T>(f: (analyzer: Analyzer) => T): T | undefined => {
+    const analyzer = currentAnalyzer
+    if (!analyzer) {
+        return undefined
+    }
+
+    try {
+        return f(analyzer)
+    } catch (err) {
+        // 捨てる前に、パニックのメッセージだけ読み出しておく。(それも失敗したら例外の内容を使う。)
+        let message: string | undefined
+        try {
+            message = analyzer.last_panic_message()
+        } catch {
+            message = undefined
+        }
+
+        reloadAnalyzer()
+        throw new Error(message ?? String(err))
+    }
+}
+
 const THE_STATE: monaco.languages.IState = {
     clone: () => THE_STATE,
     equals: (_other: monaco.languages.IState) => true,
@@ -157,8 +219,17 @@ const THE_STATE: monaco.languages.IState = {
 const getInitialState = (): monaco.languages.IState =>
     THE_STATE
 
-const monacoTokenize = (doTokenize: (line: string) => any) => (line: string): monaco.languages.ILineTokens => {
-    let result = doTokenize(line)
+const monacoTokenize = (line: string): monaco.languages.ILineTokens => {
+    let result: any
+    try {
+        result = withAnalyzer(analyzer => analyzer.tokenize(line))
+    } catch (err) {
+        console.error(err)
+    }
+    if (!result) {
+        return { tokens: [], endState: THE_STATE }
+    }
+
     console.log(result)
     return {
         tokens: result.tokens.map((token: any): monaco.languages.IToken => ({
@@ -169,7 +240,7 @@ const monacoTokenize = (doTokenize: (line: string) => any) => (line: string): mo
     }
 }
 
-const monacoValidate = (editor: monaco.editor.ICodeEditor, doValidate: (sourceCode: string) => monaco.editor.IMarkerData[]) => {
+const monacoValidate = (editor: monaco.editor.ICodeEditor) => {
     const model = editor.getModel()
     if (!model) {
         return
@@ -177,19 +248,167 @@ const monacoValidate = (editor: monaco.editor.ICodeEditor, doValidate: (sourceCo
 
     try {
         const sourceCode = model.getValue()
-        const markers = doValidate(sourceCode) as monaco.editor.IMarkerData[]
+        const markers = withAnalyzer(analyzer => analyzer.validate(sourceCode, navigator.language)) as monaco.editor.IMarkerData[] | undefined
+        if (!markers) {
+            // インスタンスを作り直している。(前回の診断を残しておく。)
+            return
+        }
         console.log(markers)
 
+        // 関連箇所はすべて同じモデルの中にある。
+        for (const marker of markers) {
+            for (const related of marker.relatedInformation ?? []) {
+                related.resource = model.uri
+            }
+        }
+
         monaco.editor.setModelMarkers(model, "pmxclang", markers)
     } catch (err) {
         console.error(err)
+
+        // wasm の中でパニックが起きた。エディタの先頭に内部エラーとして表示する。
+        const message = err instanceof Error ? err.message : String(err)
+        monaco.editor.setModelMarkers(model, "pmxclang", [{
+            severity: monaco.MarkerSeverity.Error,
+            code: "E0901",
+            message: `internal error: ${message}`,
+            startLineNumber: 1,
+            startColumn: 1,
+            endLineNumber: 1,
+            endColumn: 1,
+        }])
     }
 }
 
 type A = monaco.editor.IMarkerData
 
+/** スペースの演算の導出 (pmxc_analyzer の trace.rs を参照) */
+interface Derivation {
+    operation: "subtraction" | "intersection" | "decomposition"
+    rule: string
+    inputs: string[]
+    output: string
+    children: Derivation[]
+}
+
+interface MatchTrace {
+    range: monaco.IRange
+    endLineNumber: number
+    verdict: "exhaustive" | "non_exhaustive" | "undetermined"
+    truncated: boolean
+    derivation: Derivation
+}
+
+const VERDICT_LABELS: Record<MatchTrace["verdict"], string> = {
+    exhaustive: "網羅的",
+    non_exhaustive: "網羅的でない",
+    undetermined: "判定できない",
+}
+
+const derivationText = (derivation: Derivation): string => {
+    const inputs = derivation.inputs.map(input => input.includes(" | ") ? `(${input})` : input)
+    switch (derivation.operation) {
+        case "subtraction":
+            return `${inputs.join(" \\ ")} = ${derivation.output}`
+        case "intersection":
+            return `${inputs.join(" ∩ ")} = ${derivation.output}`
+        case "decomposition":
+            return `decompose ${inputs.join(", ")} = ${derivation.output}`
+    }
+}
+
+/** 導出木を、子を折りたためる入れ子の details 要素にする。 */
+const renderDerivation = (derivation: Derivation): HTMLElement => {
+    const rule = document.createElement("span")
+    rule.className = "pmxc-trace-rule"
+    rule.textContent = derivation.rule
+
+    const text = document.createElement("code")
+    text.textContent = derivationText(derivation)
+
+    if (derivation.children.length === 0) {
+        const leaf = document.createElement("div")
+        leaf.className = "pmxc-trace-leaf"
+        leaf.append(rule, " ", text)
+        return leaf
+    }
+
+    const details = document.createElement("details")
+    const summary = document.createElement("summary")
+    summary.append(rule, " ", text)
+    details.append(summary, ...derivation.children.map(renderDerivation))
+    return details
+}
+
+const renderMatchTrace = (trace: MatchTrace): HTMLElement => {
+    const details = document.createElement("details")
+    details.className = "pmxc-trace"
+
+    const summary = document.createElement("summary")
+    summary.textContent = `なぜ${VERDICT_LABELS[trace.verdict]}か`
+    details.append(summary, renderDerivation(trace.derivation))
+
+    if (trace.truncated) {
+        const note = document.createElement("div")
+        note.className = "pmxc-trace-leaf"
+        note.textContent = "(導出が多すぎるため、一部を省略しています)"
+        details.append(note)
+    }
+    return details
+}
+
+/** 各 match 式の下に、網羅性検査の導出を展開できる説明として表示する。 */
+const monacoShowTraces = (() => {
+    let zoneIds: string[] = []
+
+    return (editor: monaco.editor.ICodeEditor) => {
+        const model = editor.getModel()
+        if (!model) {
+            return
+        }
+
+        let traces: MatchTrace[] = []
+        try {
+            const sourceCode = model.getValue()
+            traces = JSON.parse(withAnalyzer(analyzer => analyzer.match_traces(sourceCode)) ?? "[]") as MatchTrace[]
+        } catch (err) {
+            // 診断の方で内部エラーとして報告されるので、ここでは説明を出さないだけにする。
+            console.error(err)
+        }
+
+        editor.changeViewZones(accessor => {
+            for (const id of zoneIds) {
+                accessor.removeZone(id)
+            }
+            zoneIds = []
+
+            for (const trace of traces) {
+                const domNode = document.createElement("div")
+                domNode.className = "pmxc-trace-zone"
+                domNode.append(renderMatchTrace(trace))
+
+                const zone: monaco.editor.IViewZone = {
+                    afterLineNumber: trace.endLineNumber,
+                    heightInLines: 1,
+                    domNode,
+                }
+                const id = accessor.addZone(zone)
+                zoneIds.push(id)
+
+                // 開閉したら、中身の高さに合わせてゾーンの高さを変える。
+                domNode.addEventListener("toggle", () => {
+                    zone.heightInLines = undefined
+                    zone.heightInPx = domNode.scrollHeight
+                    editor.changeViewZones(accessor => accessor.layoutZone(id))
+                }, true)
+            }
+        })
+    }
+})()
+
 const main = async () => {
-    const { tokenize: doTokenize, validate: doValidate } = await import("../dist/pmxc_analyzer")
+    const analyzer = await loadAnalyzer()
+    currentAnalyzer = analyzer
 
     const editorElement = document.getElementById("editor")!
 
@@ -205,7 +424,7 @@ const main = async () => {
 
     monaco.languages.setTokensProvider("pmxclang", {
         getInitialState,
-        tokenize: monacoTokenize(doTokenize),
+        tokenize: monacoTokenize,
     })
 
     monaco.languages.setLanguageConfiguration("pmxclang", {
@@ -219,17 +438,159 @@ const main = async () => {
         ],
     })
 
+    monaco.languages.registerDefinitionProvider("pmxclang", {
+        provideDefinition: (model, position) => {
+            const range = withAnalyzer(analyzer => analyzer.definition(model.getValue(), position.lineNumber, position.column)) as monaco.IRange | undefined
+            if (!range) {
+                return null
+            }
+
+            return { uri: model.uri, range }
+        },
+    })
+
+    monaco.languages.registerReferenceProvider("pmxclang", {
+        provideReferences: (model, position, context) => {
+            const ranges = withAnalyzer(analyzer => analyzer.references(model.getValue(), position.lineNumber, position.column, context.includeDeclaration)) as monaco.IRange[] | undefined
+            return (ranges ?? []).map(range => ({ uri: model.uri, range }))
+        },
+    })
+
+    monaco.languages.registerDocumentHighlightProvider("pmxclang", {
+        provideDocumentHighlights: (model, position) =>
+            withAnalyzer(analyzer => analyzer.document_highlights(model.getValue(), position.lineNumber, position.column)) as monaco.languages.DocumentHighlight[] | undefined,
+    })
+
+    monaco.languages.registerRenameProvider("pmxclang", {
+        resolveRenameLocation: (model, position) => {
+            const result = withAnalyzer(analyzer => analyzer.prepare_rename(model.getValue(), position.lineNumber, position.column, navigator.language))
+            if (!result) {
+                return null
+            }
+            if (result.rejectReason) {
+                return { range: new monaco.Range(position.lineNumber, position.column, position.lineNumber, position.column), text: "", rejectReason: result.rejectReason }
+            }
+
+            return result.location as monaco.languages.RenameLocation
+        },
+        provideRenameEdits: (model, position, newName) => {
+            const result = withAnalyzer(analyzer => analyzer.rename(model.getValue(), position.lineNumber, position.column, newName, navigator.language))
+            if (!result) {
+                return null
+            }
+            if (result.rejectReason) {
+                return { edits: [], rejectReason: result.rejectReason as string }
+            }
+
+            return {
+                edits: (result.edits as monaco.languages.TextEdit[]).map(textEdit => ({
+                    resource: model.uri,
+                    edit: textEdit,
+                    modelVersionId: model.getVersionId(),
+                })),
+            }
+        },
+    })
+
+    monaco.languages.registerCompletionItemProvider("pmxclang", {
+        triggerCharacters: ["(", ","],
+        provideCompletionItems: (model, position) => {
+            const word = model.getWordUntilPosition(position)
+            const range = new monaco.Range(position.lineNumber, word.startColumn, position.lineNumber, word.endColumn)
+
+            const items = withAnalyzer(analyzer => analyzer.completion(model.getValue(), position.lineNumber, position.column)) as any[] | undefined
+            return {
+                suggestions: (items ?? []).map((item): monaco.languages.CompletionItem => ({
+                    label: item.label as string,
+                    kind: monaco.languages.CompletionItemKind.EnumMember,
+                    detail: item.detail as string,
+                    insertText: item.insertText as string,
+                    insertTextRules: monaco.languages.CompletionItemInsertTextRule.InsertAsSnippet,
+                    sortText: item.sortText as string,
+                    range,
+                })),
+            }
+        },
+    })
+
+    monaco.languages.registerDocumentSymbolProvider("pmxclang", {
+        provideDocumentSymbols: model =>
+            withAnalyzer(analyzer => analyzer.document_symbols(model.getValue())) as monaco.languages.DocumentSymbol[] | undefined,
+    })
+
+    monaco.languages.registerFoldingRangeProvider("pmxclang", {
+        provideFoldingRanges: model =>
+            withAnalyzer(analyzer => analyzer.folding_ranges(model.getValue())) as monaco.languages.FoldingRange[] | undefined,
+    })
+
+    monaco.languages.registerCodeActionProvider("pmxclang", {
+        provideCodeActions: (model, range, context) => {
+            const actions = withAnalyzer(analyzer => analyzer.code_actions(
+                model.getValue(),
+                range.startLineNumber,
+                range.startColumn,
+                range.endLineNumber,
+                range.endColumn,
+                navigator.language,
+            )) as { title: string, code: string, edits: { range: monaco.IRange, text: string }[] }[] | undefined
+
+            return {
+                actions: (actions ?? []).map(action => ({
+                    title: action.title,
+                    kind: "quickfix",
+                    diagnostics: context.markers.filter(marker => marker.code === action.code),
+                    edit: {
+                        edits: action.edits.map(edit => ({
+                            resource: model.uri,
+                            edit,
+                        })),
+                    },
+                })),
+                dispose: () => {},
+            }
+        },
+    })
+
+    const legend = analyzer.semantic_tokens_legend() as monaco.languages.SemanticTokensLegend
+
+    monaco.languages.registerDocumentSemanticTokensProvider("pmxclang", {
+        getLegend: () => legend,
+        provideDocumentSemanticTokens: model => {
+            const tokens = withAnalyzer(analyzer => analyzer.semantic_tokens(model.getValue())) as { data: number[] } | undefined
+            return { data: new Uint32Array(tokens?.data ?? []) }
+        },
+        releaseDocumentSemanticTokens: () => {},
+    })
+
+    // セマンティックトークンはテーマに規則がないと色がつかない。
+    monaco.editor.defineTheme("pmxclang-theme", {
+        base: "vs",
+        inherit: true,
+        rules: [
+            { token: "enum", foreground: "267f99" },
+            { token: "enumMember", foreground: "0070c1" },
+            { token: "enumMember.unused", foreground: "8fb8d8" },
+            { token: "enum.unused", foreground: "9cc3cc" },
+            { token: "undefined", foreground: "cd3131", fontStyle: "underline" },
+        ],
+        colors: {},
+    })
+
     const editor = monaco.editor.create(editorElement, {
         automaticLayout: true,
+        theme: "pmxclang-theme",
+        "semanticHighlighting.enabled": true,
         language: "pmxclang",
         value: INITIAL_SOURCE_CODE,
     })
 
     editor.onDidChangeModelContent(() => {
-        monacoValidate(editor, doValidate)
+        monacoValidate(editor)
+        monacoShowTraces(editor)
     })
 
-    monacoValidate(editor, doValidate)
+    monacoValidate(editor)
+    monacoShowTraces(editor)
 }
 
 document.addEventListener("DOMContentLoaded", main)
@@ -57,7 +57,4 @@ module.exports = {
         compress: true,
         port: 8080,
     },
-    experiments: {
-        asyncWebAssembly: true,
-    },
 }